@@ -0,0 +1,14 @@
+-- A client id can now be registered on multiple devices, so the id alone is no
+-- longer unique. Notifications are deduplicated per client id and are cleaned up
+-- explicitly when the last device of a client is removed.
+ALTER TABLE public.notifications
+    DROP CONSTRAINT fk_notifications_client_id;
+
+ALTER TABLE public.clients
+    ADD COLUMN device_id varchar(255) NOT NULL DEFAULT 'default';
+
+ALTER TABLE public.clients
+    DROP CONSTRAINT clients_pkey;
+
+ALTER TABLE public.clients
+    ADD PRIMARY KEY (id, device_id);
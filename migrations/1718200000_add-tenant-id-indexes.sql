@@ -0,0 +1,3 @@
+-- Tenant deletion removes clients and notifications in batches by tenant id
+create index if not exists clients_tenant_id_idx on public.clients (tenant_id);
+create index if not exists notifications_tenant_id_idx on public.notifications (tenant_id);
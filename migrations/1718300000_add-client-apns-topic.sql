@@ -0,0 +1,4 @@
+-- APNs topic (bundle id) the device registered for, NULL uses the tenant's
+-- default topic
+ALTER TABLE public.clients
+    ADD COLUMN apns_topic varchar(255);
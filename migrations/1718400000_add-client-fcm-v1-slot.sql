@@ -0,0 +1,4 @@
+-- FCM v1 credentials slot the device registered for, NULL uses the tenant's
+-- default credentials
+ALTER TABLE public.clients
+    ADD COLUMN fcm_v1_slot varchar(64);
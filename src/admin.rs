@@ -0,0 +1,579 @@
+//! Commands of the `echo-admin` binary, used by operators instead of editing
+//! the databases by hand
+#[cfg(feature = "analytics")]
+use crate::{analytics, config::AnalyticsExportBackend};
+use {
+    crate::{
+        cli::Args,
+        config::Config,
+        connect_database,
+        error::{Error, Result},
+        handlers::{
+            emit_credentials_updated, emit_tenant_suspended,
+            get_tenant::GetTenantResponse,
+            update_apns::{apns_certificate_not_after, validate_apns_auth},
+            update_fcm_v1::validate_fcm_v1_credentials,
+        },
+        log::prelude::*,
+        providers::{PushMessage, PushProvider},
+        state::{self, AppState},
+        stores::{
+            client::{Client, ClientStore},
+            tenant::{
+                validate_fcm_v1_slot, CredentialsKind, Tenant, TenantApnsUpdateAuth,
+                TenantApnsUpdateParams, TenantFcmV1UpdateParams, TenantStore, TenantUpdateParams,
+                DEFAULT_FCM_V1_SLOT,
+            },
+        },
+        tenant_deletion,
+        webhooks::{self, WebhookEvent, Webhooks},
+    },
+    base64::Engine as _,
+    moka::future::Cache,
+    serde::Serialize,
+    sqlx::{
+        migrate::{Migrate, Migrator},
+        PgPool,
+    },
+    std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
+};
+
+pub const USAGE: &str = "\
+usage:
+    echo-admin tenant create <tenant-id>
+    echo-admin tenant get <tenant-id>
+    echo-admin tenant delete <tenant-id>
+    echo-admin tenant suspend <tenant-id> --reason <reason> [--provider <apns|fcm|fcm_v1>]
+    echo-admin tenant unsuspend <tenant-id> [--provider <apns|fcm|fcm_v1>]
+    echo-admin credentials apns-certificate <tenant-id> <p12-file> [--password <password>] [--topic <topic>]
+    echo-admin credentials apns-token <tenant-id> <p8-file> --key-id <key-id> --team-id <team-id> [--topic <topic>]
+    echo-admin credentials fcm-v1 <tenant-id> <service-account-file> [--slot <slot>]
+    echo-admin client get <tenant-id> <client-id>
+    echo-admin client find-token <token>
+    echo-admin test-push <tenant-id> <client-id> [--title <title>] [--body <body>]
+    echo-admin migrations status
+    echo-admin migrations run";
+
+const DEFAULT_TEST_TITLE: &str = "Test notification";
+const DEFAULT_TEST_BODY: &str = "Sent by echo-admin";
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+static TENANT_MIGRATOR: Migrator = sqlx::migrate!("./tenant_migrations");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    CreateTenant {
+        tenant_id: String,
+    },
+    GetTenant {
+        tenant_id: String,
+    },
+    /// Deletes the tenant with its clients and notifications, waiting for the
+    /// deletion to finish
+    DeleteTenant {
+        tenant_id: String,
+    },
+    /// Suspends the provider's credentials, or all of them when no provider
+    /// is given. FCM v1 credentials are suspended in every slot
+    SuspendTenant {
+        tenant_id: String,
+        reason: String,
+        credentials: Vec<CredentialsKind>,
+    },
+    UnsuspendTenant {
+        tenant_id: String,
+        credentials: Vec<CredentialsKind>,
+    },
+    UploadApnsCertificate {
+        tenant_id: String,
+        certificate: PathBuf,
+        password: Option<String>,
+        topic: Option<String>,
+    },
+    UploadApnsToken {
+        tenant_id: String,
+        pkcs8_pem: PathBuf,
+        key_id: String,
+        team_id: String,
+        topic: Option<String>,
+    },
+    UploadFcmV1Credentials {
+        tenant_id: String,
+        service_account: PathBuf,
+        /// Named slot, the default credentials are replaced when not given
+        slot: Option<String>,
+    },
+    GetClient {
+        tenant_id: String,
+        client_id: String,
+    },
+    FindClientByToken {
+        token: String,
+    },
+    /// Sends a plain notification to every device of the client
+    TestPush {
+        tenant_id: String,
+        client_id: String,
+        title: String,
+        body: String,
+    },
+    MigrationStatus,
+    RunMigrations,
+}
+
+#[derive(Serialize, Debug)]
+struct ClientOutput {
+    id: String,
+    tenant_id: String,
+    #[serde(rename = "type")]
+    push_type: String,
+    token: String,
+    always_raw: bool,
+    device_id: String,
+    apns_topic: Option<String>,
+    fcm_v1_slot: Option<String>,
+}
+
+impl ClientOutput {
+    fn new(id: String, client: Client) -> Self {
+        ClientOutput {
+            id,
+            tenant_id: client.tenant_id,
+            push_type: client.push_type.as_str().to_string(),
+            token: client.token,
+            always_raw: client.always_raw,
+            device_id: client.device_id,
+            apns_topic: client.apns_topic,
+            fcm_v1_slot: client.fcm_v1_slot,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct MigrationOutput {
+    database: &'static str,
+    version: i64,
+    description: String,
+    applied: bool,
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let Some(mut args) = Args::parse(args)? else {
+            return Err(Error::InvalidOptionsProvided(USAGE.to_string()));
+        };
+
+        let name = args.command.clone();
+        let positional = std::mem::take(&mut args.positional);
+        let required = |args: &mut Args, option: &str| {
+            args.option(option).ok_or_else(|| {
+                Error::InvalidOptionsProvided(format!("{name}: --{option} is required\n{USAGE}"))
+            })
+        };
+
+        let command = match (name.as_str(), positional.as_slice()) {
+            ("tenant", [action, tenant_id]) => {
+                let tenant_id = tenant_id.clone();
+                match action.as_str() {
+                    "create" => Self::CreateTenant { tenant_id },
+                    "get" => Self::GetTenant { tenant_id },
+                    "delete" => Self::DeleteTenant { tenant_id },
+                    "suspend" => Self::SuspendTenant {
+                        tenant_id,
+                        reason: required(&mut args, "reason")?,
+                        credentials: parse_credentials(args.option("provider"))?,
+                    },
+                    "unsuspend" => Self::UnsuspendTenant {
+                        tenant_id,
+                        credentials: parse_credentials(args.option("provider"))?,
+                    },
+                    _ => return Err(args.invalid(USAGE)),
+                }
+            }
+            ("credentials", [kind, tenant_id, file]) => {
+                let tenant_id = tenant_id.clone();
+                let file = PathBuf::from(file);
+                match kind.as_str() {
+                    "apns-certificate" => Self::UploadApnsCertificate {
+                        tenant_id,
+                        certificate: file,
+                        password: args.option("password"),
+                        topic: args.option("topic"),
+                    },
+                    "apns-token" => Self::UploadApnsToken {
+                        tenant_id,
+                        pkcs8_pem: file,
+                        key_id: required(&mut args, "key-id")?,
+                        team_id: required(&mut args, "team-id")?,
+                        topic: args.option("topic"),
+                    },
+                    "fcm-v1" => Self::UploadFcmV1Credentials {
+                        tenant_id,
+                        service_account: file,
+                        slot: args.option("slot"),
+                    },
+                    _ => return Err(args.invalid(USAGE)),
+                }
+            }
+            ("client", [action, tenant_id, client_id]) if action == "get" => Self::GetClient {
+                tenant_id: tenant_id.clone(),
+                client_id: client_id.clone(),
+            },
+            ("client", [action, token]) if action == "find-token" => Self::FindClientByToken {
+                token: token.clone(),
+            },
+            ("test-push", [tenant_id, client_id]) => Self::TestPush {
+                tenant_id: tenant_id.clone(),
+                client_id: client_id.clone(),
+                title: args
+                    .option("title")
+                    .unwrap_or_else(|| DEFAULT_TEST_TITLE.to_string()),
+                body: args
+                    .option("body")
+                    .unwrap_or_else(|| DEFAULT_TEST_BODY.to_string()),
+            },
+            ("migrations", [action]) if action == "status" => Self::MigrationStatus,
+            ("migrations", [action]) if action == "run" => Self::RunMigrations,
+            _ => return Err(args.invalid(USAGE)),
+        };
+        args.finish(USAGE)?;
+
+        Ok(command)
+    }
+
+    pub async fn run(self, config: Config) -> Result<()> {
+        let store = connect_database(&config.database_url).await?;
+        let tenant_store = connect_database(&config.tenant_database_url).await?;
+
+        let mut state = state::new_state(
+            config.clone(),
+            Arc::new(store.clone()),
+            Arc::new(store.clone()),
+            Arc::new(tenant_store.clone()),
+        )?;
+        // Queued events are delivered by the server's dispatcher
+        state.webhooks = Some(Webhooks::new(Arc::new(tenant_store.clone())));
+        #[cfg(feature = "analytics")]
+        {
+            let s3_client = match config.analytics_export_backend {
+                AnalyticsExportBackend::S3 => Some(crate::get_s3_client(&config).await),
+                AnalyticsExportBackend::Local => None,
+            };
+            state.analytics = analytics::initialize(&config, s3_client, state.public_ip, None);
+        }
+
+        let result = self.execute(&config, &store, &tenant_store, &state).await;
+
+        // Export what the command recorded before exiting
+        #[cfg(feature = "analytics")]
+        if let Some(analytics) = state.analytics.take() {
+            analytics.flush().await;
+        }
+
+        result
+    }
+
+    async fn execute(
+        self,
+        config: &Config,
+        store: &PgPool,
+        tenant_store: &PgPool,
+        state: &AppState,
+    ) -> Result<()> {
+        match self {
+            Self::CreateTenant { tenant_id } => {
+                let tenant = tenant_store
+                    .create_tenant(TenantUpdateParams { id: tenant_id })
+                    .await?;
+                print_json(&GetTenantResponse::new(tenant, &config.public_url))
+            }
+            Self::GetTenant { tenant_id } => {
+                let tenant = tenant_store.get_tenant(&tenant_id).await?;
+                print_json(&GetTenantResponse::new(tenant, &config.public_url))
+            }
+            Self::DeleteTenant { tenant_id } => {
+                tenant_store
+                    .mark_tenant_pending_deletion(&tenant_id)
+                    .await?;
+                tenant_deletion::purge_tenant(state, &tenant_id).await
+            }
+            Self::SuspendTenant {
+                tenant_id,
+                reason,
+                credentials,
+            } => {
+                let tenant = tenant_store.get_tenant(&tenant_id).await?;
+                for credentials in credentials {
+                    for slot in credentials_slots(&tenant, credentials) {
+                        tenant_store
+                            .suspend_tenant_credentials(
+                                &tenant_id,
+                                credentials,
+                                slot.as_deref(),
+                                &reason,
+                            )
+                            .await?;
+                    }
+                    emit_tenant_suspended(state, &tenant_id, credentials, &reason).await;
+                }
+                Ok(())
+            }
+            Self::UnsuspendTenant {
+                tenant_id,
+                credentials,
+            } => {
+                let tenant = tenant_store.get_tenant(&tenant_id).await?;
+                for credentials in credentials {
+                    let mut unsuspended = false;
+                    for slot in credentials_slots(&tenant, credentials) {
+                        unsuspended |= tenant
+                            .slot_suspended_reason(credentials, slot.as_deref())
+                            .is_some();
+                        tenant_store
+                            .unsuspend_tenant_credentials(&tenant_id, credentials, slot.as_deref())
+                            .await?;
+                    }
+                    if !unsuspended {
+                        continue;
+                    }
+                    webhooks::enqueue(
+                        tenant_store,
+                        &tenant_id,
+                        WebhookEvent::TenantUnsuspended,
+                        serde_json::json!({ "credentials": credentials }),
+                    )
+                    .await?;
+                }
+                Ok(())
+            }
+            Self::UploadApnsCertificate {
+                tenant_id,
+                certificate,
+                password,
+                topic,
+            } => {
+                let auth = TenantApnsUpdateAuth::Certificate {
+                    apns_certificate: read_base64(&certificate)?,
+                    apns_certificate_password: password.unwrap_or_default(),
+                };
+                update_apns(state, &tenant_id, auth, topic).await
+            }
+            Self::UploadApnsToken {
+                tenant_id,
+                pkcs8_pem,
+                key_id,
+                team_id,
+                topic,
+            } => {
+                let auth = TenantApnsUpdateAuth::Token {
+                    apns_pkcs8_pem: read_base64(&pkcs8_pem)?,
+                    apns_key_id: key_id,
+                    apns_team_id: team_id,
+                };
+                update_apns(state, &tenant_id, auth, topic).await
+            }
+            Self::UploadFcmV1Credentials {
+                tenant_id,
+                service_account,
+                slot,
+            } => {
+                let slot = slot.filter(|slot| slot != DEFAULT_FCM_V1_SLOT);
+                if let Some(slot) = &slot {
+                    validate_fcm_v1_slot(slot)?;
+                }
+                tenant_store.get_tenant(&tenant_id).await?;
+                let fcm_v1_credentials = std::fs::read_to_string(service_account)?;
+                validate_fcm_v1_credentials(
+                    &reqwest::Client::new(),
+                    &config.fcm_v1_endpoints(),
+                    &fcm_v1_credentials,
+                )
+                .await?;
+
+                let params = TenantFcmV1UpdateParams { fcm_v1_credentials };
+                let tenant = match &slot {
+                    Some(slot) => {
+                        tenant_store
+                            .update_tenant_fcm_v1_slot(&tenant_id, slot, params)
+                            .await?
+                    }
+                    None => {
+                        tenant_store
+                            .update_tenant_fcm_v1(&tenant_id, params)
+                            .await?
+                    }
+                };
+                let unsuspended = tenant
+                    .slot_suspended_reason(CredentialsKind::FcmV1, slot.as_deref())
+                    .is_some();
+                if unsuspended {
+                    tenant_store
+                        .unsuspend_tenant_credentials(
+                            &tenant_id,
+                            CredentialsKind::FcmV1,
+                            slot.as_deref(),
+                        )
+                        .await?;
+                }
+                emit_credentials_updated(
+                    state,
+                    &tenant_id,
+                    CredentialsKind::FcmV1,
+                    false,
+                    unsuspended,
+                )
+                .await;
+                Ok(())
+            }
+            Self::GetClient {
+                tenant_id,
+                client_id,
+            } => {
+                let devices = store.get_client_devices(&tenant_id, &client_id).await?;
+                print_json(
+                    &devices
+                        .into_iter()
+                        .map(|client| ClientOutput::new(client_id.clone(), client))
+                        .collect::<Vec<_>>(),
+                )
+            }
+            Self::FindClientByToken { token } => {
+                let (id, client) = store.get_client_by_token(&token).await?;
+                print_json(&ClientOutput::new(id, client))
+            }
+            Self::TestPush {
+                tenant_id,
+                client_id,
+                title,
+                body,
+            } => {
+                let tenant = tenant_store.get_tenant(&tenant_id).await?;
+                let devices = store.get_client_devices(&tenant_id, &client_id).await?;
+                let http_client = reqwest::Client::new();
+                let provider_cache = Cache::new(1);
+                for client in devices {
+                    let provider = tenant
+                        .provider(
+                            &client.push_type,
+                            client.fcm_v1_slot.as_deref(),
+                            http_client.clone(),
+                            &provider_cache,
+                        )
+                        .await?
+                        .with_apns_topic(client.apns_topic.as_deref());
+                    provider
+                        .send_notification(client.token, PushMessage::plain(&title, &body)?)
+                        .await?;
+                    info!(%tenant_id, %client_id, device_id = %client.device_id, "sent test push");
+                }
+                Ok(())
+            }
+            Self::MigrationStatus => {
+                let mut status = migration_status("database", &MIGRATOR, store).await?;
+                status.extend(
+                    migration_status("tenant_database", &TENANT_MIGRATOR, tenant_store).await?,
+                );
+                print_json(&status)
+            }
+            Self::RunMigrations => {
+                MIGRATOR.run(store).await?;
+                TENANT_MIGRATOR.run(tenant_store).await?;
+                info!("migrations applied");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Every set of credentials when no provider is given
+fn parse_credentials(provider: Option<String>) -> Result<Vec<CredentialsKind>> {
+    match provider {
+        Some(provider) => Ok(vec![provider.as_str().try_into()?]),
+        None => Ok(CredentialsKind::ALL.to_vec()),
+    }
+}
+
+/// Slots the credentials are suspended by, every FCM v1 slot of the tenant
+/// along with the default slot
+fn credentials_slots(tenant: &Tenant, credentials: CredentialsKind) -> Vec<Option<String>> {
+    let mut slots = vec![None];
+    if credentials == CredentialsKind::FcmV1 {
+        slots.extend(tenant.fcm_v1_slots.keys().cloned().map(Some));
+    }
+    slots
+}
+
+async fn update_apns(
+    state: &AppState,
+    tenant_id: &str,
+    auth: TenantApnsUpdateAuth,
+    topic: Option<String>,
+) -> Result<()> {
+    let tenant_store = &state.tenant_store;
+    tenant_store.get_tenant(tenant_id).await?;
+    validate_apns_auth(&auth)?;
+    let certificate_not_after = apns_certificate_not_after(&auth)?;
+
+    if let Some(apns_topic) = topic {
+        tenant_store
+            .update_tenant_apns(tenant_id, TenantApnsUpdateParams { apns_topic })
+            .await?;
+    }
+
+    let mut tenant = tenant_store
+        .update_tenant_apns_auth(tenant_id, auth)
+        .await?;
+    if let Some(not_after) = certificate_not_after {
+        tenant = tenant_store
+            .update_tenant_apns_certificate_expiry(tenant_id, not_after)
+            .await?;
+    }
+    let unsuspended = tenant.suspended_reason(CredentialsKind::Apns).is_some();
+    if unsuspended {
+        tenant_store
+            .unsuspend_tenant_credentials(tenant_id, CredentialsKind::Apns, None)
+            .await?;
+    }
+    emit_credentials_updated(state, tenant_id, CredentialsKind::Apns, false, unsuspended).await;
+
+    Ok(())
+}
+
+async fn migration_status(
+    database: &'static str,
+    migrator: &Migrator,
+    pool: &PgPool,
+) -> Result<Vec<MigrationOutput>> {
+    let mut connection = pool.acquire().await?;
+    connection.ensure_migrations_table().await?;
+    let applied = connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect::<HashSet<_>>();
+
+    Ok(migrator
+        .iter()
+        .map(|migration| MigrationOutput {
+            database,
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect())
+}
+
+fn read_base64(path: &Path) -> Result<String> {
+    Ok(base64::engine::general_purpose::STANDARD.encode(std::fs::read(path)?))
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    let output = serde_json::to_string_pretty(value).map_err(Error::InternalSerializationError)?;
+    println!("{output}");
+    Ok(())
+}
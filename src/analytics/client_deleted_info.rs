@@ -0,0 +1,20 @@
+use {parquet_derive::ParquetRecordWriter, serde::Serialize, std::sync::Arc};
+
+/// Reason of a deletion requested by the client
+pub const CLIENT_DELETED_REQUESTED: &str = "requested";
+/// Reason of a deletion after the provider rejected the device token
+pub const CLIENT_DELETED_BAD_TOKEN: &str = "bad_token";
+/// Reason of the deletion of every device of a deleted tenant
+pub const CLIENT_DELETED_TENANT_DELETED: &str = "tenant_deleted";
+
+#[derive(Debug, Clone, Serialize, ParquetRecordWriter)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientDeletedInfo {
+    pub project_id: Arc<str>,
+    pub client_id: Arc<str>,
+    /// Only set when a single device of the client was deleted
+    pub device_id: Option<Arc<str>>,
+    pub push_provider: Option<Arc<str>>,
+    pub reason: Arc<str>,
+    pub deleted_at: chrono::NaiveDateTime,
+}
@@ -0,0 +1,68 @@
+use {
+    std::{net::IpAddr, path::PathBuf},
+    wc::analytics::Exporter,
+};
+
+/// Where [`LocalExporter`] writes the batches, mirrors the S3 exporter's
+/// config
+#[derive(Debug, Clone)]
+pub struct LocalConfig {
+    pub export_dir: PathBuf,
+    pub export_prefix: String,
+    pub export_name: String,
+    pub node_addr: IpAddr,
+    pub file_extension: String,
+}
+
+/// Writes each batch to its own file in a local directory, using the same
+/// time-partitioned layout as the S3 exporter:
+/// `{export_prefix}/dt={date}/hr={hour}/{timestamp}_{export_name}_{node_addr}_{id}.{file_extension}`.
+/// The random id keeps batches exported at the same time, e.g. by instances
+/// sharing the directory without a public IP, from overwriting each other
+#[derive(Debug, Clone)]
+pub struct LocalExporter {
+    config: LocalConfig,
+}
+
+impl LocalExporter {
+    pub fn new(config: LocalConfig) -> Self {
+        Self { config }
+    }
+
+    fn export_path(&self, now: chrono::DateTime<chrono::Utc>) -> PathBuf {
+        let LocalConfig {
+            export_dir,
+            export_prefix,
+            export_name,
+            node_addr,
+            file_extension,
+        } = &self.config;
+
+        export_dir
+            .join(export_prefix)
+            .join(format!("dt={}", now.format("%Y-%m-%d")))
+            .join(format!("hr={}", now.format("%H")))
+            .join(format!(
+                "{}_{export_name}_{node_addr}_{}.{file_extension}",
+                now.format("%Y%m%dT%H%M%S%.6f"),
+                uuid::Uuid::new_v4().simple()
+            ))
+    }
+}
+
+impl Exporter for LocalExporter {
+    type Error = std::io::Error;
+
+    async fn export(self, data: Vec<u8>) -> Result<(), Self::Error> {
+        let path = self.export_path(chrono::Utc::now());
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        // Written under a temporary name first so that readers never see a
+        // partial batch
+        let partial_path = path.with_extension("partial");
+        tokio::fs::write(&partial_path, data).await?;
+        tokio::fs::rename(&partial_path, &path).await
+    }
+}
@@ -1,11 +1,28 @@
 use {
     crate::{
-        analytics::{client_info::ClientInfo, message_info::MessageInfo},
-        config::Config,
+        analytics::{
+            client_deleted_info::ClientDeletedInfo,
+            client_info::ClientInfo,
+            local::{LocalConfig, LocalExporter},
+            message_info::MessageInfo,
+            tenant_credentials_updated_info::TenantCredentialsUpdatedInfo,
+            tenant_suspended_info::TenantSuspendedInfo,
+        },
+        config::{AnalyticsExportBackend, Config},
         log::prelude::*,
+        networking,
     },
     aws_sdk_s3::Client as S3Client,
-    std::{net::IpAddr, sync::Arc, time::Duration},
+    std::{
+        net::{IpAddr, Ipv4Addr},
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    },
+    tokio::sync::Notify,
     wc::{
         analytics::{
             self, AnalyticsExt, ArcCollector, AwsConfig, AwsExporter, BatchCollector,
@@ -17,8 +34,12 @@ use {
     },
 };
 
+pub mod client_deleted_info;
 pub mod client_info;
+pub mod local;
 pub mod message_info;
+pub mod tenant_credentials_updated_info;
+pub mod tenant_suspended_info;
 
 const ANALYTICS_EXPORT_TIMEOUT: Duration = Duration::from_secs(30);
 const DATA_QUEUE_CAPACITY: usize = 8192;
@@ -27,6 +48,9 @@ const DATA_QUEUE_CAPACITY: usize = 8192;
 enum DataKind {
     Messages,
     Clients,
+    ClientDeletions,
+    TenantSuspensions,
+    TenantCredentialsUpdates,
 }
 
 impl DataKind {
@@ -35,9 +59,20 @@ impl DataKind {
         match self {
             Self::Messages => "messages",
             Self::Clients => "clients",
+            Self::ClientDeletions => "client_deletions",
+            Self::TenantSuspensions => "tenant_suspensions",
+            Self::TenantCredentialsUpdates => "tenant_credentials_updates",
         }
     }
 
+    fn export_prefix(&self) -> String {
+        format!("echo/{}", self.as_str())
+    }
+
+    fn export_name(&self) -> String {
+        format!("push_{}", self.as_str())
+    }
+
     #[inline]
     fn as_kv(&self) -> otel::KeyValue {
         otel::KeyValue::new("data_kind", self.as_str())
@@ -48,8 +83,16 @@ fn success_kv(success: bool) -> otel::KeyValue {
     otel::KeyValue::new("success", success)
 }
 
-#[derive(Clone, Copy)]
-struct Observer(DataKind);
+/// Records collected since the last export, used to wait for the final
+/// export on shutdown
+#[derive(Clone, Default)]
+struct Pending {
+    records: Arc<AtomicUsize>,
+    exported: Arc<Notify>,
+}
+
+#[derive(Clone)]
+struct Observer(DataKind, Pending);
 
 impl<T, E> BatchObserver<T, E> for Observer
 where
@@ -87,6 +130,10 @@ where
     E: std::error::Error,
 {
     fn observe_collection(&self, res: &Result<(), E>) {
+        if res.is_ok() {
+            self.1.records.fetch_add(1, Ordering::Relaxed);
+        }
+
         wc::metrics::counter!(
             "analytics_records_collected",
             1,
@@ -108,6 +155,9 @@ where
     E: std::error::Error,
 {
     fn observe_export(&self, elapsed: Duration, res: &Result<(), E>) {
+        self.1.records.store(0, Ordering::Relaxed);
+        self.1.exported.notify_one();
+
         wc::metrics::counter!(
             "analytics_batches_exported",
             1,
@@ -133,11 +183,37 @@ where
     }
 }
 
+/// Batches the records of `$data_kind` into parquet files that are exported by
+/// the exporter `$exporter` returns for the data kind, the collector's
+/// [`Pending`] is added to `$pending`
+macro_rules! parquet_collector {
+    ($data_kind:expr, $exporter:expr, $pending:expr) => {{
+        let data_kind = $data_kind;
+        let pending = Pending::default();
+        $pending.push(pending.clone());
+        let observer = Observer(data_kind, pending);
+        BatchCollector::new(
+            CollectorConfig {
+                data_queue_capacity: DATA_QUEUE_CAPACITY,
+                ..Default::default()
+            },
+            ParquetBatchFactory::new(Default::default()).with_observer(observer.clone()),
+            $exporter(data_kind).with_observer(observer.clone()),
+        )
+        .with_observer(observer)
+        .boxed_shared()
+    }};
+}
+
 #[derive(Clone)]
 pub struct PushAnalytics {
     pub messages: ArcCollector<MessageInfo>,
     pub clients: ArcCollector<ClientInfo>,
+    pub client_deletions: ArcCollector<ClientDeletedInfo>,
+    pub tenant_suspensions: ArcCollector<TenantSuspendedInfo>,
+    pub tenant_credentials_updates: ArcCollector<TenantCredentialsUpdatedInfo>,
     pub geoip_resolver: Option<Arc<MaxMindResolver>>,
+    pending: Vec<Pending>,
 }
 
 impl PushAnalytics {
@@ -147,7 +223,11 @@ impl PushAnalytics {
         Self {
             messages: analytics::noop_collector().boxed_shared(),
             clients: analytics::noop_collector().boxed_shared(),
+            client_deletions: analytics::noop_collector().boxed_shared(),
+            tenant_suspensions: analytics::noop_collector().boxed_shared(),
+            tenant_credentials_updates: analytics::noop_collector().boxed_shared(),
             geoip_resolver: None,
+            pending: vec![],
         }
     }
 
@@ -157,58 +237,65 @@ impl PushAnalytics {
         node_addr: IpAddr,
         geoip_resolver: Option<Arc<MaxMindResolver>>,
     ) -> Self {
-        let messages = {
-            let data_kind = DataKind::Messages;
-            let observer = Observer(data_kind);
-            BatchCollector::new(
-                CollectorConfig {
-                    data_queue_capacity: DATA_QUEUE_CAPACITY,
-                    ..Default::default()
-                },
-                ParquetBatchFactory::new(Default::default()).with_observer(observer),
-                AwsExporter::new(AwsConfig {
-                    export_prefix: "echo/messages".to_string(),
-                    export_name: "push_messages".to_string(),
-                    node_addr,
-                    file_extension: "parquet".to_owned(),
-                    bucket_name: export_bucket.to_owned(),
-                    s3_client: s3_client.clone(),
-                    upload_timeout: ANALYTICS_EXPORT_TIMEOUT,
-                })
-                .with_observer(observer),
-            )
-            .with_observer(observer)
-            .boxed_shared()
+        let exporter = |data_kind: DataKind| {
+            AwsExporter::new(AwsConfig {
+                export_prefix: data_kind.export_prefix(),
+                export_name: data_kind.export_name(),
+                node_addr,
+                file_extension: "parquet".to_owned(),
+                bucket_name: export_bucket.to_owned(),
+                s3_client: s3_client.clone(),
+                upload_timeout: ANALYTICS_EXPORT_TIMEOUT,
+            })
         };
 
-        let clients = {
-            let data_kind = DataKind::Clients;
-            let observer = Observer(data_kind);
-            BatchCollector::new(
-                CollectorConfig {
-                    data_queue_capacity: DATA_QUEUE_CAPACITY,
-                    ..Default::default()
-                },
-                ParquetBatchFactory::new(Default::default()).with_observer(observer),
-                AwsExporter::new(AwsConfig {
-                    export_prefix: "echo/clients".to_string(),
-                    export_name: "push_clients".to_string(),
-                    node_addr,
-                    file_extension: "parquet".to_owned(),
-                    bucket_name: export_bucket.to_owned(),
-                    s3_client: s3_client.clone(),
-                    upload_timeout: ANALYTICS_EXPORT_TIMEOUT,
-                })
-                .with_observer(observer),
-            )
-            .with_observer(observer)
-            .boxed_shared()
+        let mut pending = vec![];
+        Self {
+            messages: parquet_collector!(DataKind::Messages, exporter, pending),
+            clients: parquet_collector!(DataKind::Clients, exporter, pending),
+            client_deletions: parquet_collector!(DataKind::ClientDeletions, exporter, pending),
+            tenant_suspensions: parquet_collector!(DataKind::TenantSuspensions, exporter, pending),
+            tenant_credentials_updates: parquet_collector!(
+                DataKind::TenantCredentialsUpdates,
+                exporter,
+                pending
+            ),
+            geoip_resolver,
+            pending,
+        }
+    }
+
+    /// Writes the batches to `export_dir` instead of S3
+    pub fn with_local_export(
+        export_dir: PathBuf,
+        node_addr: IpAddr,
+        geoip_resolver: Option<Arc<MaxMindResolver>>,
+    ) -> Self {
+        info!(export_dir = %export_dir.display(), "initializing analytics with local export");
+
+        let exporter = |data_kind: DataKind| {
+            LocalExporter::new(LocalConfig {
+                export_dir: export_dir.clone(),
+                export_prefix: data_kind.export_prefix(),
+                export_name: data_kind.export_name(),
+                node_addr,
+                file_extension: "parquet".to_owned(),
+            })
         };
 
+        let mut pending = vec![];
         Self {
-            messages,
-            clients,
+            messages: parquet_collector!(DataKind::Messages, exporter, pending),
+            clients: parquet_collector!(DataKind::Clients, exporter, pending),
+            client_deletions: parquet_collector!(DataKind::ClientDeletions, exporter, pending),
+            tenant_suspensions: parquet_collector!(DataKind::TenantSuspensions, exporter, pending),
+            tenant_credentials_updates: parquet_collector!(
+                DataKind::TenantCredentialsUpdates,
+                exporter,
+                pending
+            ),
             geoip_resolver,
+            pending,
         }
     }
 
@@ -232,6 +319,77 @@ impl PushAnalytics {
         }
     }
 
+    pub fn client_deleted(&self, data: ClientDeletedInfo) {
+        if let Err(err) = self.client_deletions.collect(data) {
+            tracing::warn!(
+                ?err,
+                data_kind = DataKind::ClientDeletions.as_str(),
+                "failed to collect analytics"
+            );
+        }
+    }
+
+    pub fn tenant_suspended(&self, data: TenantSuspendedInfo) {
+        if let Err(err) = self.tenant_suspensions.collect(data) {
+            tracing::warn!(
+                ?err,
+                data_kind = DataKind::TenantSuspensions.as_str(),
+                "failed to collect analytics"
+            );
+        }
+    }
+
+    pub fn tenant_credentials_updated(&self, data: TenantCredentialsUpdatedInfo) {
+        if let Err(err) = self.tenant_credentials_updates.collect(data) {
+            tracing::warn!(
+                ?err,
+                data_kind = DataKind::TenantCredentialsUpdates.as_str(),
+                "failed to collect analytics"
+            );
+        }
+    }
+
+    /// Records collected but not exported yet. Approximate, as an export
+    /// resets the count to 0 even when records were collected after the batch
+    /// was taken
+    pub fn pending_records(&self) -> usize {
+        self.pending
+            .iter()
+            .map(|pending| pending.records.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Drops the collectors, which exports their pending batches once no other
+    /// references are left, and waits for those exports
+    pub async fn flush(self) {
+        let Self {
+            messages,
+            clients,
+            client_deletions,
+            tenant_suspensions,
+            tenant_credentials_updates,
+            pending,
+            ..
+        } = self;
+        drop(messages);
+        drop(clients);
+        drop(client_deletions);
+        drop(tenant_suspensions);
+        drop(tenant_credentials_updates);
+
+        let exported = async {
+            for pending in &pending {
+                while pending.records.load(Ordering::Relaxed) > 0 {
+                    pending.exported.notified().await;
+                }
+            }
+        };
+        match tokio::time::timeout(ANALYTICS_EXPORT_TIMEOUT, exported).await {
+            Ok(()) => info!("analytics flushed"),
+            Err(_) => warn!("timed out waiting for analytics to be exported"),
+        }
+    }
+
     pub fn lookup_geo_data(&self, addr: IpAddr) -> Option<geoip::Data> {
         self.geoip_resolver
             .as_ref()?
@@ -244,16 +402,43 @@ impl PushAnalytics {
     }
 }
 
-pub async fn initialize(
+/// The S3 backend needs `s3_client` and the public IP address of the machine,
+/// the local backend names its files after a local address when there is no
+/// public one
+pub fn initialize(
     config: &Config,
-    s3_client: S3Client,
-    echo_ip: IpAddr,
+    s3_client: Option<S3Client>,
+    public_ip: Option<IpAddr>,
     geoip_resolver: Option<Arc<MaxMindResolver>>,
-) -> PushAnalytics {
-    PushAnalytics::with_aws_export(
-        s3_client,
-        &config.analytics_export_bucket,
-        echo_ip,
-        geoip_resolver,
-    )
+) -> Option<PushAnalytics> {
+    match config.analytics_export_backend {
+        AnalyticsExportBackend::S3 => {
+            let Some(public_ip) = public_ip else {
+                warn!("analytics are disabled, the machine has no public IP address");
+                return None;
+            };
+
+            Some(PushAnalytics::with_aws_export(
+                s3_client?,
+                &config.analytics_export_bucket,
+                public_ip,
+                geoip_resolver,
+            ))
+        }
+        AnalyticsExportBackend::Local => {
+            let node_addr = public_ip
+                .or_else(networking::find_local_ip_addr)
+                .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+            Some(PushAnalytics::with_local_export(
+                config
+                    .analytics_export_dir
+                    .clone()
+                    .unwrap_or_default()
+                    .into(),
+                node_addr,
+                geoip_resolver,
+            ))
+        }
+    }
 }
@@ -0,0 +1,12 @@
+use {parquet_derive::ParquetRecordWriter, serde::Serialize, std::sync::Arc};
+
+#[derive(Debug, Clone, Serialize, ParquetRecordWriter)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantCredentialsUpdatedInfo {
+    pub project_id: Arc<str>,
+    pub credentials: Arc<str>,
+    pub removed: bool,
+    /// The update lifted a suspension of the credentials
+    pub unsuspended: bool,
+    pub updated_at: chrono::NaiveDateTime,
+}
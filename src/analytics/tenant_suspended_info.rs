@@ -0,0 +1,10 @@
+use {parquet_derive::ParquetRecordWriter, serde::Serialize, std::sync::Arc};
+
+#[derive(Debug, Clone, Serialize, ParquetRecordWriter)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantSuspendedInfo {
+    pub project_id: Arc<str>,
+    pub credentials: Arc<str>,
+    pub reason: Arc<str>,
+    pub suspended_at: chrono::NaiveDateTime,
+}
@@ -0,0 +1,157 @@
+//! Periodic check of the expiry of tenants' APNs certificates.
+//!
+//! The expiry is read from the p12 when it's uploaded, or by this check for
+//! certificates uploaded before expiries were recorded. Tenants are warned once
+//! when their certificate expires within
+//! `APNS_CERTIFICATE_EXPIRY_WARNING_DAYS`, and the APNs credentials of expired
+//! certificates are suspended without waiting for APNs to reject a push.
+//!
+//! The check runs on every instance, the warning and the suspension are
+//! claimed with conditional updates so that each is only reported once.
+use {
+    crate::{
+        error::Result,
+        handlers::{
+            emit_tenant_suspended, push_message::APNS_CERTIFICATE_EXPIRED_REASON,
+            update_apns::p12_not_after,
+        },
+        increment_counter,
+        log::prelude::*,
+        state::AppState,
+        stores::tenant::CredentialsKind,
+        webhooks::WebhookEvent,
+    },
+    chrono::Utc,
+    serde_json::json,
+    std::{sync::Arc, time::Duration},
+};
+
+/// How often certificates are checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically warns about expiring certificates and suspends expired ones
+pub async fn run_expiry_check(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = check_certificates(&state).await {
+            warn!("failed to check APNs certificate expiry: {e:?}");
+        }
+    }
+}
+
+pub async fn check_certificates(state: &AppState) -> Result<()> {
+    backfill_expiries(state).await?;
+
+    let now = Utc::now();
+    let warning_window =
+        chrono::Duration::days(state.config.apns_certificate_expiry_warning_days.into());
+    let tenants = state
+        .tenant_store
+        .get_tenants_with_apns_certificate_expiring(now + warning_window)
+        .await?;
+
+    for tenant in tenants {
+        let Some(not_after) = tenant.apns_certificate_not_after else {
+            continue;
+        };
+
+        if not_after <= now {
+            if tenant.suspended_reason(CredentialsKind::Apns).is_some() {
+                continue;
+            }
+
+            let reason = APNS_CERTIFICATE_EXPIRED_REASON;
+            let suspended = state
+                .tenant_store
+                .suspend_tenant_credentials_once(&tenant.id, CredentialsKind::Apns, None, reason)
+                .await?;
+            if !suspended {
+                // Suspended by another instance
+                continue;
+            }
+            increment_counter!(state.metrics, expired_apns_certificates);
+            increment_counter!(state.metrics, tenant_suspensions);
+            warn!(
+                tenant_id = %tenant.id,
+                %not_after,
+                "tenant's APNs credentials have been suspended as the certificate expired"
+            );
+
+            emit_tenant_suspended(state, &tenant.id, CredentialsKind::Apns, reason).await;
+        } else if tenant.apns_certificate_expiry_notified_at.is_none() {
+            let notified = state
+                .tenant_store
+                .mark_tenant_apns_certificate_expiry_notified(&tenant.id)
+                .await?;
+            if !notified {
+                // Warned by another instance
+                continue;
+            }
+
+            let days_remaining = (not_after - now).num_days();
+            increment_counter!(state.metrics, apns_certificate_expiry_warnings);
+            warn!(
+                tenant_id = %tenant.id,
+                %not_after,
+                %days_remaining,
+                "tenant's APNs certificate is about to expire"
+            );
+
+            if let Some(webhooks) = &state.webhooks {
+                webhooks
+                    .emit(
+                        &tenant.id,
+                        WebhookEvent::CertificateExpiring,
+                        json!({
+                            "credentials": CredentialsKind::Apns,
+                            "not_after": not_after,
+                            "days_remaining": days_remaining,
+                        }),
+                    )
+                    .await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Records the expiry of certificates uploaded before expiries were recorded
+async fn backfill_expiries(state: &AppState) -> Result<()> {
+    let tenants = state
+        .tenant_store
+        .get_tenants_missing_apns_certificate_expiry()
+        .await?;
+
+    for tenant in tenants {
+        let Some(apns_certificate) = &tenant.apns_certificate else {
+            continue;
+        };
+        let password = tenant
+            .apns_certificate_password
+            .as_deref()
+            .unwrap_or_default();
+        match p12_not_after(apns_certificate, password) {
+            Ok(not_after) => {
+                state
+                    .tenant_store
+                    .backfill_tenant_apns_certificate_expiry(
+                        &tenant.id,
+                        apns_certificate,
+                        not_after,
+                    )
+                    .await?;
+            }
+            Err(e) => {
+                warn!(
+                    tenant_id = %tenant.id,
+                    "failed to read the APNs certificate's expiry: {e:?}"
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use {
+    dotenv::dotenv,
+    echo_server::{admin, config, log},
+};
+
+#[tokio::main]
+async fn main() -> echo_server::error::Result<()> {
+    dotenv().ok();
+    let config = config::get_config()
+        .expect("Failed to load config, please ensure all env vars are defined.");
+    let logger = log::Logger::init(&config).expect("Failed to start logging");
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let command = admin::Command::parse(&args).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(2);
+    });
+
+    let result = command.run(config).await;
+
+    logger.stop();
+
+    result
+}
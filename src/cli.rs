@@ -0,0 +1,212 @@
+//! Subcommands of the server binary, the server is started when none is given
+use {
+    crate::{
+        config::{self, Config},
+        connect_database,
+        error::{
+            Error::{self, InvalidConfiguration, InvalidOptionsProvided, InvalidTenantArchive},
+            Result,
+        },
+        log::prelude::*,
+        tenant_archive::{self, TenantArchive},
+    },
+    std::path::PathBuf,
+};
+
+pub const USAGE: &str = "\
+usage:
+    echo-server
+    echo-server --check-config
+    echo-server export-tenant <tenant-id> [--recipient-key <file>] [--output <file>]
+    echo-server import-tenant <file> [--private-key <file>]";
+
+/// Command line arguments split into the command name, positional arguments
+/// and `--name value` options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args {
+    pub command: String,
+    pub positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    /// Returns `None` when no arguments were given
+    pub fn parse(args: &[String]) -> Result<Option<Self>> {
+        let Some((command, args)) = args.split_first() else {
+            return Ok(None);
+        };
+
+        let mut positional = vec![];
+        let mut options = vec![];
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if let Some(option) = arg.strip_prefix("--") {
+                let value = args.next().ok_or_else(|| {
+                    InvalidOptionsProvided(format!("{command}: --{option} requires a value"))
+                })?;
+                options.push((option.to_string(), value.clone()));
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+
+        Ok(Some(Self {
+            command: command.clone(),
+            positional,
+            options,
+        }))
+    }
+
+    /// Removes and returns the value of the option
+    pub fn option(&mut self, name: &str) -> Option<String> {
+        self.options
+            .iter()
+            .position(|(option, _)| option == name)
+            .map(|index| self.options.remove(index).1)
+    }
+
+    /// Errors if any options were not consumed by [`Args::option`]
+    pub fn finish(self, usage: &str) -> Result<()> {
+        match self.options.first() {
+            Some((option, _)) => Err(InvalidOptionsProvided(format!(
+                "{}: unknown option --{option}\n{usage}",
+                self.command
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn invalid(&self, usage: &str) -> Error {
+        InvalidOptionsProvided(format!("{}\n{usage}", self.command))
+    }
+}
+
+/// Whether the arguments ask to print every problem with the config instead
+/// of starting the server. Checked before the config is loaded, which is why
+/// it isn't a [`Command`]
+pub fn is_check_config(args: &[String]) -> bool {
+    matches!(args, [arg] if arg == "--check-config")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Writes the tenant archive to the output file, or stdout when omitted
+    ExportTenant {
+        tenant_id: String,
+        recipient_key: Option<PathBuf>,
+        output: Option<PathBuf>,
+    },
+    /// Imports the tenant archive, falling back to `TENANT_ARCHIVE_PRIVATE_KEY`
+    /// to decrypt credentials when no private key file is given
+    ImportTenant {
+        input: PathBuf,
+        private_key: Option<PathBuf>,
+    },
+}
+
+impl Command {
+    /// Parses the arguments following the binary name, returns `None` when no
+    /// subcommand was given
+    pub fn parse(args: &[String]) -> Result<Option<Self>> {
+        let Some(mut args) = Args::parse(args)? else {
+            return Ok(None);
+        };
+
+        let name = args.command.clone();
+        let positional = std::mem::take(&mut args.positional);
+        let command = match (name.as_str(), positional.as_slice()) {
+            ("export-tenant", [tenant_id]) => Self::ExportTenant {
+                tenant_id: tenant_id.clone(),
+                recipient_key: args.option("recipient-key").map(PathBuf::from),
+                output: args.option("output").map(PathBuf::from),
+            },
+            ("import-tenant", [input]) => Self::ImportTenant {
+                input: PathBuf::from(input),
+                private_key: args.option("private-key").map(PathBuf::from),
+            },
+            _ => return Err(args.invalid(USAGE)),
+        };
+        args.finish(USAGE)?;
+
+        Ok(Some(command))
+    }
+
+    pub async fn run(self, config: Config) -> Result<()> {
+        let store = connect_database(&config.database_url).await?;
+        sqlx::migrate!("./migrations").run(&store).await?;
+
+        let tenant_store = connect_database(&config.tenant_database_url).await?;
+        sqlx::migrate!("./tenant_migrations")
+            .run(&tenant_store)
+            .await?;
+
+        match self {
+            Self::ExportTenant {
+                tenant_id,
+                recipient_key,
+                output,
+            } => {
+                let recipient_key = recipient_key.map(std::fs::read_to_string).transpose()?;
+                let archive = tenant_archive::export_tenant(
+                    &tenant_store,
+                    &store,
+                    &tenant_id,
+                    recipient_key.as_deref(),
+                )
+                .await?;
+
+                let archive = serde_json::to_vec_pretty(&archive)
+                    .map_err(Error::InternalSerializationError)?;
+                match output {
+                    Some(output) => std::fs::write(&output, archive)?,
+                    None => std::io::Write::write_all(&mut std::io::stdout(), &archive)?,
+                }
+
+                info!(%tenant_id, "exported tenant");
+            }
+            Self::ImportTenant { input, private_key } => {
+                let archive: TenantArchive = serde_json::from_slice(&std::fs::read(&input)?)
+                    .map_err(|e| InvalidTenantArchive(e.to_string()))?;
+                let private_key = match private_key {
+                    Some(path) => Some(std::fs::read_to_string(path)?),
+                    None => config.tenant_archive_private_key.clone(),
+                };
+
+                let summary = tenant_archive::import_tenant(
+                    &tenant_store,
+                    &store,
+                    archive,
+                    private_key.as_deref(),
+                    None,
+                )
+                .await?;
+
+                let summary =
+                    serde_json::to_string(&summary).map_err(Error::InternalSerializationError)?;
+                println!("{summary}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Loads the config from the environment and prints every problem found,
+/// errors when there were any
+pub fn check_config() -> Result<()> {
+    match config::load_config(std::env::vars()) {
+        Ok(config) => {
+            println!("config is valid, tenancy mode: {:?}", config.tenancy_mode);
+            Ok(())
+        }
+        Err(problems) => {
+            for problem in &problems {
+                eprintln!("- {problem}");
+            }
+            Err(InvalidConfiguration(format!(
+                "found {} problem(s)",
+                problems.len()
+            )))
+        }
+    }
+}
@@ -8,10 +8,83 @@ use {
         stores::tenant::ApnsType,
     },
     serde::Deserialize,
+    std::{collections::BTreeMap, path::Path},
 };
 
-#[cfg(not(feature = "multitenant"))]
-use crate::providers::ProviderKind;
+/// Path of an optional TOML config file, environment variables take
+/// precedence over its values
+pub const CONFIG_FILE_VAR: &str = "CONFIG_FILE";
+
+/// Secrets that can also be read from the file at `<NAME>_FILE`
+const SECRET_VARS: &[&str] = &[
+    "DATABASE_URL",
+    "TENANT_DATABASE_URL",
+    "JWT_SECRET",
+    "RELAY_PUBLIC_KEY",
+    "TENANT_ARCHIVE_PRIVATE_KEY",
+    "APNS_CERTIFICATE",
+    "APNS_CERTIFICATE_PASSWORD",
+    "APNS_PKCS8_PEM",
+    "FCM_API_KEY",
+    "FCM_V1_CREDENTIALS",
+];
+
+/// Variables without a default, checked up front so that all missing ones
+/// are reported together
+const REQUIRED_VARS: &[&str] = &[
+    "PUBLIC_URL",
+    "RELAY_PUBLIC_KEY",
+    "DATABASE_URL",
+    "TENANT_DATABASE_URL",
+    #[cfg(feature = "geoblock")]
+    "BLOCKED_COUNTRIES",
+];
+
+use crate::providers::{fcm_v1_validation::FcmV1Endpoints, ProviderKind};
+
+/// Whether the server hosts a single tenant configured through the
+/// environment or many tenants managed through the `/tenants` API
+#[derive(Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TenancyMode {
+    #[default]
+    Single,
+    Multi,
+}
+
+/// Where analytics batches are exported to
+#[cfg(feature = "analytics")]
+#[derive(Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyticsExportBackend {
+    /// `ANALYTICS_EXPORT_BUCKET` on S3, or an S3 compatible store at
+    /// `S3_ENDPOINT`
+    #[default]
+    S3,
+    /// Files in `ANALYTICS_EXPORT_DIR`
+    Local,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+    /// One JSON object per event
+    Json,
+}
+
+/// How often the `LOG_FILE` is rotated
+#[derive(Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
 
 #[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct Config {
@@ -20,13 +93,30 @@ pub struct Config {
     pub public_url: String,
     #[serde(default = "default_log_level")]
     pub log_level: String,
+    /// Filter of the spans exported over OTLP, same syntax as `LOG_LEVEL`
     #[serde(default = "default_log_level_otel")]
     pub log_level_otel: String,
+    /// `text` (default) or `json`
+    #[serde(default)]
+    pub log_format: LogFormat,
+    /// Logs are also written to this file
+    pub log_file: Option<String>,
+    #[serde(default)]
+    pub log_file_rotation: LogRotation,
     #[serde(default = "default_disable_header")]
     pub disable_header: bool,
     pub relay_public_key: String,
     #[serde(default = "default_validate_signatures")]
     pub validate_signatures: bool,
+    /// How long the servers keep serving after a shutdown signal while the
+    /// readiness probe fails, so that the instance is taken out of rotation
+    /// before it stops accepting connections
+    #[serde(default = "default_shutdown_grace_period_secs")]
+    pub shutdown_grace_period_secs: u64,
+    /// How long in-flight requests, and then the tasks they left running, get
+    /// to finish once the servers stop accepting connections
+    #[serde(default = "default_shutdown_drain_timeout_secs")]
+    pub shutdown_drain_timeout_secs: u64,
     pub database_url: String,
     #[serde(default = "default_is_test", skip)]
     /// This is an internal flag to disable logging, cannot be defined by user
@@ -37,37 +127,67 @@ pub struct Config {
     pub cors_allowed_origins: Vec<String>,
 
     // TELEMETRY
+    /// OTLP gRPC endpoint traces are exported to, e.g.
+    /// `http://localhost:4317`
     pub otel_exporter_otlp_endpoint: Option<String>,
     pub telemetry_prometheus_port: Option<u16>,
+    /// Tenants whose tenant specific metrics are labelled with their id, the
+    /// rest share the `other` label. The label is left out when empty
+    #[serde(default)]
+    pub metrics_tenant_labels: Vec<String>,
 
     // APNS
-    #[cfg(not(feature = "multitenant"))]
     pub apns_type: Option<ApnsType>,
-    #[cfg(not(feature = "multitenant"))]
     pub apns_topic: Option<String>,
 
-    #[cfg(not(feature = "multitenant"))]
     pub apns_certificate: Option<String>,
-    #[cfg(not(feature = "multitenant"))]
     pub apns_certificate_password: Option<String>,
 
-    #[cfg(not(feature = "multitenant"))]
     pub apns_pkcs8_pem: Option<String>,
-    #[cfg(not(feature = "multitenant"))]
     pub apns_key_id: Option<String>,
-    #[cfg(not(feature = "multitenant"))]
     pub apns_team_id: Option<String>,
 
     // FCM
-    #[cfg(not(feature = "multitenant"))]
     pub fcm_api_key: Option<String>,
-    #[cfg(not(feature = "multitenant"))]
     pub fcm_v1_credentials: Option<String>,
+    /// How often the single-tenant credentials are reloaded from the config
+    /// file and `*_FILE` secrets, `0` disables reloading
+    #[serde(default = "default_credentials_reload_interval_secs")]
+    pub credentials_reload_interval_secs: u64,
+    /// Pushes to tenants that only have a legacy FCM API key fail with
+    /// `legacy_fcm_deprecated` instead of being sent and suspending the tenant
+    #[serde(default)]
+    pub legacy_fcm_fail_fast: bool,
 
     // Multi-tenancy
+    /// `single` (default) or `multi`
+    #[serde(default)]
+    pub tenancy_mode: TenancyMode,
     pub tenant_database_url: String,
-    #[cfg(feature = "multitenant")]
+    /// Required in multi-tenant mode
+    #[serde(default)]
     pub jwt_secret: String,
+    /// X25519 private key used to decrypt the credentials of imported tenant
+    /// archives, either PEM or the base64 encoded raw key
+    pub tenant_archive_private_key: Option<String>,
+    /// Overrides the service account's OAuth token endpoint when validating
+    /// uploaded FCM v1 credentials
+    pub fcm_v1_token_endpoint: Option<String>,
+    /// Overrides the FCM endpoint used for the `validate_only` send when
+    /// validating uploaded FCM v1 credentials
+    pub fcm_v1_endpoint: Option<String>,
+    /// Tenants are warned when their APNs certificate expires within this
+    /// many days
+    #[serde(default = "default_apns_certificate_expiry_warning_days")]
+    pub apns_certificate_expiry_warning_days: u32,
+    /// Rejects new legacy FCM API keys, tenants have to upload FCM v1
+    /// credentials instead
+    #[serde(default = "default_legacy_fcm_deprecated")]
+    pub legacy_fcm_deprecated: bool,
+    /// Delivers the queued webhooks from this instance, at least one instance
+    /// sharing the tenant database has to
+    #[serde(default = "default_webhook_dispatcher_enabled")]
+    pub webhook_dispatcher_enabled: bool,
 
     // Analytics
     #[cfg(any(feature = "analytics", feature = "geoblock"))]
@@ -78,8 +198,17 @@ pub struct Config {
     #[cfg(any(feature = "analytics", feature = "geoblock"))]
     pub geoip_db_key: Option<String>,
 
+    /// `s3` (default) or `local`
+    #[cfg(feature = "analytics")]
+    #[serde(default)]
+    pub analytics_export_backend: AnalyticsExportBackend,
+    /// Required by the `s3` backend
     #[cfg(feature = "analytics")]
+    #[serde(default)]
     pub analytics_export_bucket: String,
+    /// Required by the `local` backend
+    #[cfg(feature = "analytics")]
+    pub analytics_export_dir: Option<String>,
 
     #[cfg(feature = "geoblock")]
     pub blocked_countries: Vec<String>,
@@ -88,34 +217,79 @@ pub struct Config {
 impl Config {
     /// Run validations against config and throw error
     pub fn is_valid(&self) -> error::Result<()> {
-        #[cfg(feature = "multitenant")]
-        {
+        let problems = self.validate();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidConfiguration(problems.join(", ")))
+        }
+    }
+
+    /// Run validations against config and return every problem found
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.is_multitenant() {
             if self.tenant_database_url == self.database_url {
-                return Err(InvalidConfiguration(
+                problems.push(
                     "`TENANT_DATABASE_URL` is equal to `DATABASE_URL`, this is not allowed"
                         .to_string(),
-                ));
+                );
+            }
+
+            if self.jwt_secret.is_empty() {
+                problems
+                    .push("`JWT_SECRET` is required when `TENANCY_MODE` is `multi`".to_string());
             }
         }
 
         // Check that APNS config is valid when it has been configured
         match self.get_apns_type() {
-            Ok(_) => Ok(()),
-            Err(NoApnsConfigured) => Ok(()),
-            Err(e) => Err(e),
-        }?;
+            Ok(_) | Err(NoApnsConfigured) => {}
+            Err(InvalidConfiguration(problem)) => problems.push(problem),
+            Err(e) => problems.push(e.to_string()),
+        }
 
         // Empty Relay public key is not allowed
         if self.relay_public_key.is_empty() {
-            return Err(InvalidConfiguration(
-                "`RELAY_PUBLIC_KEY` cannot be empty".to_string(),
-            ));
+            problems.push("`RELAY_PUBLIC_KEY` cannot be empty".to_string());
+        }
+
+        #[cfg(feature = "analytics")]
+        match self.analytics_export_backend {
+            AnalyticsExportBackend::S3 if self.analytics_export_bucket.is_empty() => problems.push(
+                "`ANALYTICS_EXPORT_BUCKET` is required when `ANALYTICS_EXPORT_BACKEND` is `s3`"
+                    .to_string(),
+            ),
+            AnalyticsExportBackend::Local
+                if self
+                    .analytics_export_dir
+                    .as_deref()
+                    .unwrap_or_default()
+                    .is_empty() =>
+            {
+                problems.push(
+                    "`ANALYTICS_EXPORT_DIR` is required when `ANALYTICS_EXPORT_BACKEND` is `local`"
+                        .to_string(),
+                )
+            }
+            _ => {}
         }
 
-        Ok(())
+        problems
+    }
+
+    pub fn fcm_v1_endpoints(&self) -> FcmV1Endpoints {
+        let mut endpoints = FcmV1Endpoints {
+            token: self.fcm_v1_token_endpoint.clone(),
+            ..Default::default()
+        };
+        if let Some(fcm) = &self.fcm_v1_endpoint {
+            endpoints.fcm.clone_from(fcm);
+        }
+        endpoints
     }
 
-    #[cfg(not(feature = "multitenant"))]
     pub fn single_tenant_supported_providers(&self) -> Vec<ProviderKind> {
         let mut supported = vec![];
 
@@ -136,39 +310,35 @@ impl Config {
     }
 
     pub fn get_apns_type(&self) -> Result<ApnsType, Error> {
-        #[cfg(not(feature = "multitenant"))]
-        if let Some(apns_type) = &self.apns_type {
-            // Check if APNS config is correct
-            let _ = match apns_type {
-                ApnsType::Certificate => match (
-                    &self.apns_topic,
-                    &self.apns_certificate,
-                    &self.apns_certificate_password,
-                ) {
-                    (Some(_), Some(_), Some(_)) => Ok(ApnsType::Certificate),
-                    _ => Err(InvalidConfiguration(
-                        "APNS_TYPE of Certificate requires specific variables, please check the \
-                         documentation."
-                            .to_string(),
-                    )),
-                },
-                ApnsType::Token => match (
-                    &self.apns_topic,
-                    &self.apns_pkcs8_pem,
-                    &self.apns_key_id,
-                    &self.apns_team_id,
-                ) {
-                    (Some(_), Some(_), Some(_), Some(_)) => Ok(ApnsType::Token),
-                    _ => Err(InvalidConfiguration(
-                        "APNS_TYPE of Certificate requires specific variables, please check the \
-                         documentation."
-                            .to_string(),
-                    )),
-                },
-            }?;
+        // Check if APNS config is correct
+        match self.apns_type {
+            Some(ApnsType::Certificate) => match (
+                &self.apns_topic,
+                &self.apns_certificate,
+                &self.apns_certificate_password,
+            ) {
+                (Some(_), Some(_), Some(_)) => Ok(ApnsType::Certificate),
+                _ => Err(InvalidConfiguration(
+                    "APNS_TYPE of Certificate requires specific variables, please check the \
+                     documentation."
+                        .to_string(),
+                )),
+            },
+            Some(ApnsType::Token) => match (
+                &self.apns_topic,
+                &self.apns_pkcs8_pem,
+                &self.apns_key_id,
+                &self.apns_team_id,
+            ) {
+                (Some(_), Some(_), Some(_), Some(_)) => Ok(ApnsType::Token),
+                _ => Err(InvalidConfiguration(
+                    "APNS_TYPE of Token requires specific variables, please check the \
+                     documentation."
+                        .to_string(),
+                )),
+            },
+            None => Err(NoApnsConfigured),
         }
-
-        Err(NoApnsConfigured)
     }
 }
 
@@ -192,6 +362,14 @@ fn default_validate_signatures() -> bool {
     true
 }
 
+fn default_shutdown_grace_period_secs() -> u64 {
+    10
+}
+
+fn default_shutdown_drain_timeout_secs() -> u64 {
+    30
+}
+
 fn default_is_test() -> bool {
     false
 }
@@ -200,7 +378,131 @@ fn default_cors_allowed_origins() -> Vec<String> {
     vec!["*".to_string()]
 }
 
+fn default_credentials_reload_interval_secs() -> u64 {
+    30
+}
+
+fn default_apns_certificate_expiry_warning_days() -> u32 {
+    30
+}
+
+fn default_legacy_fcm_deprecated() -> bool {
+    true
+}
+
+fn default_webhook_dispatcher_enabled() -> bool {
+    true
+}
+
 pub fn get_config() -> error::Result<Config> {
-    let config = envy::from_env::<Config>()?;
-    Ok(config)
+    load_config(std::env::vars()).map_err(|problems| InvalidConfiguration(problems.join(", ")))
+}
+
+/// Loads the config from the TOML file at `CONFIG_FILE`, if set, overridden
+/// by `vars`. Returns every problem found rather than stopping at the first
+pub fn load_config(
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<Config, Vec<String>> {
+    let mut problems = vec![];
+
+    let mut env = vars.into_iter().collect::<BTreeMap<_, _>>();
+    resolve_secret_files(&mut env, "environment", &mut problems);
+
+    let mut vars = match env.get(CONFIG_FILE_VAR) {
+        Some(path) if !path.is_empty() => read_config_file(Path::new(path), &mut problems),
+        _ => BTreeMap::new(),
+    };
+    vars.extend(env);
+
+    for key in REQUIRED_VARS {
+        if !vars.contains_key(*key) {
+            problems.push(format!("`{key}` is required"));
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+
+    let config = envy::from_iter::<_, Config>(vars).map_err(|e| vec![e.to_string()])?;
+    let problems = config.validate();
+    if problems.is_empty() {
+        Ok(config)
+    } else {
+        Err(problems)
+    }
+}
+
+/// Reads the top level keys of the TOML config file as env vars, arrays are
+/// joined with commas
+fn read_config_file(path: &Path, problems: &mut Vec<String>) -> BTreeMap<String, String> {
+    let source = path.display().to_string();
+    let table = match std::fs::read_to_string(path) {
+        Ok(contents) => match contents.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                problems.push(format!("{source}: {e}"));
+                return BTreeMap::new();
+            }
+        },
+        Err(e) => {
+            problems.push(format!("{source}: {e}"));
+            return BTreeMap::new();
+        }
+    };
+
+    let mut vars = BTreeMap::new();
+    for (key, value) in table {
+        let value = match value {
+            toml::Value::String(value) => value,
+            toml::Value::Array(values) => values
+                .into_iter()
+                .map(|value| match value {
+                    toml::Value::String(value) => value,
+                    value => value.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            toml::Value::Table(_) => {
+                problems.push(format!("{source}: `{key}` cannot be a table"));
+                continue;
+            }
+            value => value.to_string(),
+        };
+        vars.insert(key.to_uppercase(), value);
+    }
+
+    resolve_secret_files(&mut vars, &source, problems);
+    vars
+}
+
+/// Replaces `<SECRET>_FILE` with the contents of the file it points to
+fn resolve_secret_files(
+    vars: &mut BTreeMap<String, String>,
+    source: &str,
+    problems: &mut Vec<String>,
+) {
+    for key in SECRET_VARS {
+        let file_key = format!("{key}_FILE");
+        let Some(path) = vars.remove(&file_key) else {
+            continue;
+        };
+
+        if vars.contains_key(*key) {
+            problems.push(format!(
+                "{source}: only one of `{key}` and `{file_key}` can be set"
+            ));
+            continue;
+        }
+
+        match std::fs::read_to_string(&path) {
+            Ok(secret) => {
+                vars.insert(
+                    key.to_string(),
+                    secret.trim_end_matches(['\r', '\n']).to_string(),
+                );
+            }
+            Err(e) => problems.push(format!("{source}: `{file_key}` {path}: {e}")),
+        }
+    }
 }
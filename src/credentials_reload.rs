@@ -0,0 +1,114 @@
+//! Hot reload of the single-tenant provider credentials.
+//!
+//! The config file and `*_FILE` secrets are re-read periodically. Changed
+//! credentials are validated the same way as credentials uploaded by tenants
+//! before they replace the current ones, so a bad reload keeps the previous
+//! credentials in place.
+use {
+    crate::{
+        config,
+        error::{Error::InvalidConfiguration, Result},
+        handlers::{
+            update_apns::{apns_certificate_not_after, validate_apns_auth},
+            update_fcm_v1::validate_fcm_v1_credentials,
+        },
+        log::prelude::*,
+        state::AppState,
+        stores::tenant::{
+            ApnsType, DefaultTenantStore, Tenant, TenantApnsUpdateAuth, TenantStore,
+            DEFAULT_TENANT_ID,
+        },
+    },
+    std::{sync::Arc, time::Duration},
+};
+
+/// Periodically reloads the credentials, does nothing when
+/// `CREDENTIALS_RELOAD_INTERVAL_SECS` is `0`
+pub async fn run_reloader(state: Arc<AppState>, store: Arc<DefaultTenantStore>) {
+    if state.config.credentials_reload_interval_secs == 0 {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        state.config.credentials_reload_interval_secs,
+    ));
+    // The first tick completes immediately, the credentials were just loaded
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+
+        let outcome = match reload_credentials(&state, &store, std::env::vars()).await {
+            Ok(false) => continue,
+            Ok(true) => {
+                info!("reloaded single-tenant credentials");
+                "success"
+            }
+            Err(e) => {
+                warn!("failed to reload single-tenant credentials, keeping the previous ones: {e}");
+                "failure"
+            }
+        };
+
+        if let Some(metrics) = &state.metrics {
+            metrics.credential_reload(outcome);
+        }
+    }
+}
+
+/// Loads the config from `vars` and swaps in its credentials when they changed
+/// and are valid, the provider cache is flushed so that new pushes use them.
+/// Returns whether the credentials were replaced
+pub async fn reload_credentials(
+    state: &AppState,
+    store: &DefaultTenantStore,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<bool> {
+    let config =
+        config::load_config(vars).map_err(|problems| InvalidConfiguration(problems.join(", ")))?;
+    let reloaded = DefaultTenantStore::tenant_from_config(&config);
+    let current = store.get_tenant(DEFAULT_TENANT_ID).await?;
+    if reloaded == current {
+        return Ok(false);
+    }
+
+    let reloaded_apns_auth = apns_auth(&reloaded);
+    if reloaded_apns_auth != apns_auth(&current) {
+        if let Some(auth) = &reloaded_apns_auth {
+            validate_apns_auth(auth)?;
+            apns_certificate_not_after(auth)?;
+        }
+    }
+
+    if reloaded.fcm_v1_credentials != current.fcm_v1_credentials {
+        if let Some(credentials) = &reloaded.fcm_v1_credentials {
+            validate_fcm_v1_credentials(
+                &state.http_client,
+                &state.config.fcm_v1_endpoints(),
+                credentials,
+            )
+            .await?;
+        }
+    }
+
+    if !store.replace_tenant(reloaded).await {
+        return Ok(false);
+    }
+    state.provider_cache.invalidate_all();
+
+    Ok(true)
+}
+
+/// The APNs credentials in the shape tenants upload them
+fn apns_auth(tenant: &Tenant) -> Option<TenantApnsUpdateAuth> {
+    match tenant.apns_type? {
+        ApnsType::Certificate => Some(TenantApnsUpdateAuth::Certificate {
+            apns_certificate: tenant.apns_certificate.clone()?,
+            apns_certificate_password: tenant.apns_certificate_password.clone()?,
+        }),
+        ApnsType::Token => Some(TenantApnsUpdateAuth::Token {
+            apns_pkcs8_pem: tenant.apns_pkcs8_pem.clone()?,
+            apns_key_id: tenant.apns_key_id.clone()?,
+            apns_team_id: tenant.apns_team_id.clone()?,
+        }),
+    }
+}
@@ -3,7 +3,8 @@ use {
         handlers::{ErrorField, ErrorLocation, ResponseError},
         log::prelude::*,
         middleware::validate_signature::{SIGNATURE_HEADER_NAME, TIMESTAMP_HEADER_NAME},
-        stores::StoreError,
+        providers::fcm_v1_validation::FcmV1ValidationStep,
+        stores::{tenant::CredentialsKind, StoreError},
     },
     axum::response::{IntoResponse, Response},
     hyper::StatusCode,
@@ -16,8 +17,9 @@ pub enum Error {
     #[error(transparent)]
     Envy(#[from] envy::Error),
 
+    /// The provider's reason, never the token itself
     #[error("Bad device token error: {0}")]
-    BadDeviceToken(String),
+    BadDeviceToken(&'static str),
 
     #[error(transparent)]
     Apns(#[from] a2::Error),
@@ -25,15 +27,20 @@ pub enum Error {
     #[error("APNS Responded with error, {0}")]
     ApnsResponse(a2::ErrorReason),
 
+    #[cfg(feature = "legacy_fcm")]
     #[error(transparent)]
     Fcm(#[from] fcm::FcmError),
 
     #[error(transparent)]
     FcmV1(#[from] fcm_v1::SendError),
 
+    #[cfg(feature = "legacy_fcm")]
     #[error("FCM Responded with an error")]
     FcmResponse(fcm::ErrorReason),
 
+    #[error("the legacy FCM HTTP API has been shut down")]
+    LegacyFcmDeprecated,
+
     #[error("FCM v1 Responded with an error")]
     FcmV1Response(fcm_v1::ErrorReason),
 
@@ -85,6 +92,9 @@ pub enum Error {
     #[error("{0} is an invalid push provider as it has not been enabled")]
     ProviderNotAvailable(String),
 
+    #[error("FCM v1 credential validation failed during {0}: {1}")]
+    FcmV1CredentialValidation(FcmV1ValidationStep, String),
+
     #[error("the `{0}` field must not be empty")]
     EmptyField(String),
 
@@ -118,9 +128,27 @@ pub enum Error {
     #[error("invalid tenant id: {0}")]
     InvalidTenantId(String),
 
+    #[error("tenant {0} is pending deletion")]
+    TenantPendingDeletion(String),
+
     #[error("invalid options provided for {0}")]
     InvalidOptionsProvided(String),
 
+    #[error("invalid APNs topic {0}")]
+    InvalidApnsTopic(String),
+
+    #[error("invalid FCM v1 slot {0}")]
+    InvalidFcmV1Slot(String),
+
+    #[error("FCM v1 slot {0} not found")]
+    FcmV1SlotNotFound(String),
+
+    #[error("invalid webhook: {0}")]
+    InvalidWebhook(String),
+
+    #[error("webhook {0} not found")]
+    WebhookNotFound(String),
+
     #[error(transparent)]
     FromUtf8Error(#[from] std::string::FromUtf8Error),
 
@@ -130,6 +158,27 @@ pub enum Error {
     #[error("The provided multi-part body did not satisfy the requirements")]
     InvalidMultipartBody,
 
+    #[error("The provided APNs update was invalid")]
+    InvalidApnsUpdateBody(Vec<crate::handlers::ErrorField>),
+
+    #[error("The provided bulk registration body was invalid: {0}")]
+    InvalidBulkBody(String),
+
+    #[error("bulk registration contained {0} items, the maximum is {1}")]
+    BulkLimitExceeded(usize, usize),
+
+    #[error("invalid tenant archive: {0}")]
+    InvalidTenantArchive(String),
+
+    #[error("tenant archive version {0} is not supported")]
+    UnsupportedArchiveVersion(u32),
+
+    #[error("invalid tenant archive key: {0}")]
+    InvalidArchiveKey(String),
+
+    #[error(transparent)]
+    Openssl(#[from] openssl::error::ErrorStack),
+
     #[error("invalid apns type: {0}")]
     InvalidApnsType(String),
 
@@ -160,6 +209,9 @@ pub enum Error {
     #[error("BatchCollector Error: {0}")]
     BatchCollector(String),
 
+    #[error(transparent)]
+    Tracing(#[from] opentelemetry::trace::TraceError),
+
     #[error("Invalid Project ID: {0}")]
     InvalidProjectId(String),
 
@@ -187,8 +239,94 @@ pub enum Error {
     #[error("client deleted due to invalid device token")]
     ClientDeleted,
 
-    #[error("tenant suspended due to invalid configuration")]
-    TenantSuspended,
+    #[error("tenant's {0} credentials are suspended due to invalid configuration")]
+    ProviderSuspended(CredentialsKind),
+}
+
+impl Error {
+    /// Name of the variant, e.g. `BadDeviceToken`
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            Self::Envy(..) => "Envy",
+            Self::BadDeviceToken(..) => "BadDeviceToken",
+            Self::Apns(..) => "Apns",
+            Self::ApnsResponse(..) => "ApnsResponse",
+            #[cfg(feature = "legacy_fcm")]
+            Self::Fcm(..) => "Fcm",
+            Self::FcmV1(..) => "FcmV1",
+            #[cfg(feature = "legacy_fcm")]
+            Self::FcmResponse(..) => "FcmResponse",
+            Self::LegacyFcmDeprecated => "LegacyFcmDeprecated",
+            Self::FcmV1Response(..) => "FcmV1Response",
+            Self::Io(..) => "Io",
+            Self::Database(..) => "Database",
+            Self::Hex(..) => "Hex",
+            Self::Ed25519(..) => "Ed25519",
+            Self::HttpRequest(..) => "HttpRequest",
+            Self::Base64Decode(..) => "Base64Decode",
+            Self::DecryptedNotificationDecode(..) => "DecryptedNotificationDecode",
+            Self::DecryptedNotificationParse(..) => "DecryptedNotificationParse",
+            Self::FcmV1InvalidServiceAccountKey(..) => "FcmV1InvalidServiceAccountKey",
+            Self::InternalFcmV1InvalidServiceAccountKey(..) => {
+                "InternalFcmV1InvalidServiceAccountKey"
+            }
+            Self::InternalSerializationError(..) => "InternalSerializationError",
+            Self::Store(..) => "Store",
+            Self::ToStr(..) => "ToStr",
+            Self::DatabaseMigration(..) => "DatabaseMigration",
+            Self::ProviderNotFound(..) => "ProviderNotFound",
+            Self::ProviderNotAvailable(..) => "ProviderNotAvailable",
+            Self::FcmV1CredentialValidation(..) => "FcmV1CredentialValidation",
+            Self::EmptyField(..) => "EmptyField",
+            Self::RequiredEnvNotFound => "RequiredEnvNotFound",
+            Self::MissingTimestampHeader => "MissingTimestampHeader",
+            Self::MissingSignatureHeader => "MissingSignatureHeader",
+            Self::FromRequestError => "FromRequestError",
+            Self::ToBytesError => "ToBytesError",
+            Self::MissingAllSignatureHeader => "MissingAllSignatureHeader",
+            Self::MissingTenantId => "MissingTenantId",
+            Self::IncludedTenantIdWhenNotNeeded => "IncludedTenantIdWhenNotNeeded",
+            Self::InvalidConfiguration(..) => "InvalidConfiguration",
+            Self::InvalidTenantId(..) => "InvalidTenantId",
+            Self::TenantPendingDeletion(..) => "TenantPendingDeletion",
+            Self::InvalidOptionsProvided(..) => "InvalidOptionsProvided",
+            Self::InvalidApnsTopic(..) => "InvalidApnsTopic",
+            Self::InvalidFcmV1Slot(..) => "InvalidFcmV1Slot",
+            Self::FcmV1SlotNotFound(..) => "FcmV1SlotNotFound",
+            Self::InvalidWebhook(..) => "InvalidWebhook",
+            Self::WebhookNotFound(..) => "WebhookNotFound",
+            Self::FromUtf8Error(..) => "FromUtf8Error",
+            Self::MultipartError(..) => "MultipartError",
+            Self::InvalidMultipartBody => "InvalidMultipartBody",
+            Self::InvalidApnsUpdateBody(..) => "InvalidApnsUpdateBody",
+            Self::InvalidBulkBody(..) => "InvalidBulkBody",
+            Self::BulkLimitExceeded(..) => "BulkLimitExceeded",
+            Self::InvalidTenantArchive(..) => "InvalidTenantArchive",
+            Self::UnsupportedArchiveVersion(..) => "UnsupportedArchiveVersion",
+            Self::InvalidArchiveKey(..) => "InvalidArchiveKey",
+            Self::Openssl(..) => "Openssl",
+            Self::InvalidApnsType(..) => "InvalidApnsType",
+            Self::NoApnsConfigured => "NoApnsConfigured",
+            Self::MissingTopic => "MissingTopic",
+            Self::ClientNotFound => "ClientNotFound",
+            Self::InternalServerError => "InternalServerError",
+            Self::JwtError(..) => "JwtError",
+            Self::InvalidAuthentication => "InvalidAuthentication",
+            Self::GeoIpReader(..) => "GeoIpReader",
+            Self::BatchCollector(..) => "BatchCollector",
+            Self::Tracing(..) => "Tracing",
+            Self::InvalidProjectId(..) => "InvalidProjectId",
+            Self::JWT(..) => "JWT",
+            Self::GeoIpS3Failed => "GeoIpS3Failed",
+            Self::MissmatchedTenantId => "MissmatchedTenantId",
+            Self::BadFcmApiKey => "BadFcmApiKey",
+            Self::BadFcmV1Credentials => "BadFcmV1Credentials",
+            Self::BadApnsCredentials => "BadApnsCredentials",
+            Self::ApnsCertificateExpired => "ApnsCertificateExpired",
+            Self::ClientDeleted => "ClientDeleted",
+            Self::ProviderSuspended(..) => "ProviderSuspended",
+        }
+    }
 }
 
 impl IntoResponse for Error {
@@ -218,24 +356,38 @@ impl IntoResponse for Error {
                     message: e.to_string(),
                 }
             ], vec![]),
+            Error::ApnsCertificateExpired => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
+                ResponseError {
+                    name: "apns_certificate_expired".to_string(),
+                    message: "The provided APNs certificate has expired".to_string(),
+                }
+            ], vec![]),
             Error::BadApnsCredentials => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
                 ResponseError {
                     name: "bad_apns_credentials".to_string(),
                     message: "Failed to validate the provided Certificate or Token".to_string(),
                 }
             ], vec![]),
+            #[cfg(feature = "legacy_fcm")]
             Error::Fcm(e) => crate::handlers::Response::new_failure(StatusCode::INTERNAL_SERVER_ERROR, vec![
                 ResponseError {
                     name: "fcm".to_string(),
                     message: e.to_string(),
                 }
             ], vec![]),
+            #[cfg(feature = "legacy_fcm")]
             Error::FcmResponse(e) => crate::handlers::Response::new_failure(StatusCode::INTERNAL_SERVER_ERROR, vec![
                 ResponseError {
                     name: "fcm_response".to_string(),
                     message: format!("{:?}", e)
                 }
             ], vec![]),
+            Error::LegacyFcmDeprecated => crate::handlers::Response::new_failure(StatusCode::GONE, vec![
+                ResponseError {
+                    name: "legacy_fcm_deprecated".to_string(),
+                    message: "The legacy FCM HTTP API has been shut down, upload FCM v1 credentials instead".to_string(),
+                }
+            ], vec![]),
             Error::BadFcmApiKey => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
                 ResponseError {
                     name: "bad_fcm_api_key".to_string(),
@@ -260,6 +412,18 @@ impl IntoResponse for Error {
                     location: ErrorLocation::Body,
                 }
             ]),
+            Error::FcmV1CredentialValidation(step, reason) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
+                ResponseError {
+                    name: format!("fcm_v1_{step}_failed"),
+                    message: format!("The provided credentials failed the FCM v1 {step} check: {reason}"),
+                }
+            ], vec![
+                ErrorField {
+                    field: "fcm_v1_credentials".to_string(),
+                    description: "FCM V1 credentials".to_string(),
+                    location: ErrorLocation::Body,
+                }
+            ]),
             Error::BadFcmV1Credentials => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
                 ResponseError {
                     name: "bad_fcm_v1_credentials".to_string(),
@@ -320,6 +484,20 @@ impl IntoResponse for Error {
                         location: ErrorLocation::Body,
                     }],
                 ),
+                StoreError::AlwaysRawMismatch(id) => crate::handlers::Response::new_failure(
+                    StatusCode::CONFLICT,
+                    vec![ResponseError {
+                        name: "always_raw_mismatch".to_string(),
+                        message: e.to_string(),
+                    }],
+                    vec![ErrorField {
+                        field: "always_raw".to_string(),
+                        description: format!(
+                            "Must match the other devices registered for client {id}"
+                        ),
+                        location: ErrorLocation::Body,
+                    }],
+                ),
             },
             Error::ProviderNotFound(p) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
                 ResponseError {
@@ -398,6 +576,12 @@ impl IntoResponse for Error {
                     location: ErrorLocation::Path,
                 }
             ]),
+            Error::TenantPendingDeletion(id) => crate::handlers::Response::new_failure(StatusCode::CONFLICT, vec![
+                ResponseError {
+                    name: "tenant_pending_deletion".to_string(),
+                    message: format!("The tenant {id} is being deleted, please retry once the deletion has finished"),
+                }
+            ], vec![]),
             Error::MissingTenantId => crate::handlers::Response::new_failure(
                 StatusCode::BAD_REQUEST,
                 vec![ResponseError {
@@ -525,6 +709,54 @@ impl IntoResponse for Error {
                     message: "multipart body did not conform to specification".to_string(),
                 },
             ], vec![]),
+            Error::InvalidApnsUpdateBody(fields) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
+                ResponseError {
+                    name: "invalid_apns_update".to_string(),
+                    message: "The provided fields are not a valid APNs update".to_string(),
+                },
+            ], fields),
+            Error::InvalidBulkBody(e) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
+                ResponseError {
+                    name: "body".to_string(),
+                    message: format!("bulk body did not conform to specification: {e}"),
+                },
+            ], vec![]),
+            Error::BulkLimitExceeded(count, max) => crate::handlers::Response::new_failure(StatusCode::PAYLOAD_TOO_LARGE, vec![
+                ResponseError {
+                    name: "bulk_limit_exceeded".to_string(),
+                    message: format!("{count} clients were provided, at most {max} can be registered per request"),
+                },
+            ], vec![]),
+            Error::InvalidTenantArchive(e) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
+                ResponseError {
+                    name: "invalid_tenant_archive".to_string(),
+                    message: e.to_string(),
+                },
+            ], vec![]),
+            Error::UnsupportedArchiveVersion(v) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
+                ResponseError {
+                    name: "unsupported_archive_version".to_string(),
+                    message: format!("tenant archive version {v} is not supported"),
+                },
+            ], vec![
+                ErrorField {
+                    field: "version".to_string(),
+                    description: "Unsupported archive version".to_string(),
+                    location: ErrorLocation::Body,
+                }
+            ]),
+            Error::InvalidArchiveKey(e) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
+                ResponseError {
+                    name: "invalid_archive_key".to_string(),
+                    message: format!("expected a PEM or base64 encoded X25519 key: {e}"),
+                },
+            ], vec![]),
+            Error::Openssl(e) => crate::handlers::Response::new_failure(StatusCode::INTERNAL_SERVER_ERROR, vec![
+                ResponseError {
+                    name: "openssl".to_string(),
+                    message: e.to_string(),
+                },
+            ], vec![]),
             Error::InvalidApnsType(t) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
                 ResponseError {
                     name: "decoding_error".to_string(),
@@ -543,7 +775,7 @@ impl IntoResponse for Error {
                     message: "This error should not have occurred. Please file an issue at: https://github.com/walletconnect/echo-server".to_string(),
                 },
             ], vec![]),
-            Error::GeoIpReader(_) | Error::BatchCollector(_) => crate::handlers::Response::new_failure(StatusCode::INTERNAL_SERVER_ERROR, vec![
+            Error::GeoIpReader(_) | Error::BatchCollector(_) | Error::Tracing(_) => crate::handlers::Response::new_failure(StatusCode::INTERNAL_SERVER_ERROR, vec![
                 ResponseError {
                     name: "o11y".to_string(),
                     message: "Internal error monitoring the request".to_string(),
@@ -555,6 +787,48 @@ impl IntoResponse for Error {
                     message: "JWT Authentication Failed".to_string(),
                 },
             ], vec![]),
+            Error::InvalidApnsTopic(topic) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
+                ResponseError {
+                    name: "invalid_apns_topic".to_string(),
+                    message: format!("The APNs topic {topic} can't be used for this client"),
+                },
+            ], vec![
+                ErrorField {
+                    field: "apns_topic".to_string(),
+                    description: "Only allowed for APNs clients and must be one of the tenant's APNs topics".to_string(),
+                    location: ErrorLocation::Body,
+                },
+            ]),
+            Error::InvalidFcmV1Slot(slot) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
+                ResponseError {
+                    name: "invalid_fcm_v1_slot".to_string(),
+                    message: format!("The FCM v1 slot {slot} can't be used"),
+                },
+            ], vec![
+                ErrorField {
+                    field: "fcm_v1_slot".to_string(),
+                    description: "Only allowed for FCM clients and must be one of the tenant's FCM v1 slots, names are limited to 64 alphanumerics, `-` and `_`".to_string(),
+                    location: ErrorLocation::Body,
+                },
+            ]),
+            Error::FcmV1SlotNotFound(slot) => crate::handlers::Response::new_failure(StatusCode::NOT_FOUND, vec![
+                ResponseError {
+                    name: "fcm_v1_slot_not_found".to_string(),
+                    message: format!("The FCM v1 slot {slot} does not exist for this tenant"),
+                },
+            ], vec![]),
+            Error::InvalidWebhook(e) => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
+                ResponseError {
+                    name: "invalid_webhook".to_string(),
+                    message: e.to_string(),
+                },
+            ], vec![]),
+            Error::WebhookNotFound(id) => crate::handlers::Response::new_failure(StatusCode::NOT_FOUND, vec![
+                ResponseError {
+                    name: "webhook_not_found".to_string(),
+                    message: format!("The webhook {id} does not exist for this tenant"),
+                },
+            ], vec![]),
             Error::MissmatchedTenantId => crate::handlers::Response::new_failure(StatusCode::BAD_REQUEST, vec![
                 ResponseError {
                     name: "missmatched_identifiers".to_string(),
@@ -578,12 +852,18 @@ impl IntoResponse for Error {
                     message: "Request Accepted, client deleted due to invalid token".to_string(),
                 },
             ], vec![]),
-            Error::TenantSuspended => crate::handlers::Response::new_failure(StatusCode::ACCEPTED, vec![
+            Error::ProviderSuspended(credentials) => crate::handlers::Response::new_failure(StatusCode::ACCEPTED, vec![
                 ResponseError {
                     name: "tenant_suspended".to_string(),
-                    message: "Request Accepted, tenant suspended due to invalid configuration".to_string(),
+                    message: format!("Request Accepted, tenant's {credentials} credentials suspended due to invalid configuration"),
                 },
-            ], vec![]),
+            ], vec![
+                ErrorField {
+                    field: "credentials".to_string(),
+                    description: credentials.to_string(),
+                    location: ErrorLocation::Unknown,
+                },
+            ]),
             e => {
                 warn!("Error does not have response clause, {:?}", e);
 
@@ -0,0 +1,94 @@
+use {
+    crate::{
+        error::{Error, Error::InvalidWebhook},
+        handlers::validate_tenant_request,
+        state::AppState,
+        webhooks::{self, WebhookEvent},
+    },
+    axum::{
+        extract::{Path, State},
+        http::HeaderMap,
+        Json,
+    },
+    chrono::{DateTime, Utc},
+    serde::{Deserialize, Serialize},
+    std::sync::Arc,
+    tracing::{error, info, instrument},
+};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateWebhookBody {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateWebhookResponse {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    /// Used to verify the signature of deliveries, only returned on creation
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[instrument(skip_all, name = "create_webhook_handler")]
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<CreateWebhookBody>,
+) -> Result<Json<CreateWebhookResponse>, Error> {
+    // JWT token verification
+    #[cfg(feature = "cloud")]
+    let jwt_verification_result =
+        validate_tenant_request(&state.jwt_validation_client, &headers, &id).await;
+
+    #[cfg(not(feature = "cloud"))]
+    let jwt_verification_result = validate_tenant_request(&state.jwt_validation_client, &headers);
+
+    if let Err(e) = jwt_verification_result {
+        error!(
+            tenant_id = %id,
+            err = ?e,
+            "JWT verification failed"
+        );
+        return Err(e);
+    }
+
+    let webhooks = state
+        .webhooks
+        .as_ref()
+        .ok_or_else(|| InvalidWebhook("webhooks are not enabled".to_string()))?;
+
+    webhooks::validate_url(&body.url).await?;
+    if body.events.is_empty() {
+        return Err(Error::EmptyField("events".to_string()));
+    }
+    let mut events = body
+        .events
+        .iter()
+        .map(|event| event.as_str().to_string())
+        .collect::<Vec<_>>();
+    events.sort();
+    events.dedup();
+
+    // Ensure tenant real
+    let _existing_tenant = state.tenant_store.get_tenant(&id).await?;
+
+    let secret = webhooks::generate_secret()?;
+    let webhook = webhooks
+        .store()
+        .create_webhook(&id, &body.url, &secret, &events)
+        .await?;
+
+    info!(tenant_id = %id, webhook_id = %webhook.id, "created webhook");
+
+    Ok(Json(CreateWebhookResponse {
+        id: webhook.id,
+        url: webhook.url,
+        events: webhook.events,
+        secret,
+        created_at: webhook.created_at,
+    }))
+}
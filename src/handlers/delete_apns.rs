@@ -1,9 +1,10 @@
 use {
     crate::{
         error::Error::{self},
-        handlers::validate_tenant_request,
+        handlers::{emit_credentials_updated, validate_tenant_request},
         increment_counter,
         state::AppState,
+        stores::tenant::CredentialsKind,
     },
     axum::{
         extract::{Path, State},
@@ -42,12 +43,17 @@ pub async fn handler(
 
     let new_tenant = state.tenant_store.update_tenant_delete_apns(&id).await?;
 
-    if new_tenant.suspended {
-        // If suspended, it can be restored now because valid credentials have been
-        // provided
-        state.tenant_store.unsuspend_tenant(&new_tenant.id).await?;
+    let unsuspended = new_tenant.suspended_reason(CredentialsKind::Apns).is_some();
+    if unsuspended {
+        // The suspension no longer applies as the credentials have been removed
+        state
+            .tenant_store
+            .unsuspend_tenant_credentials(&new_tenant.id, CredentialsKind::Apns, None)
+            .await?;
     }
 
+    emit_credentials_updated(&state, &id, CredentialsKind::Apns, true, unsuspended).await;
+
     increment_counter!(state.metrics, tenant_apns_updates);
 
     Ok(StatusCode::NO_CONTENT)
@@ -1,3 +1,5 @@
+#[cfg(feature = "analytics")]
+use crate::analytics::client_deleted_info::{ClientDeletedInfo, CLIENT_DELETED_REQUESTED};
 use {
     crate::{
         error::{Error::InvalidAuthentication, Result},
@@ -6,17 +8,25 @@ use {
         state::AppState,
     },
     axum::{
-        extract::{Path, State as StateExtractor},
+        extract::{Path, Query, State as StateExtractor},
         http::HeaderMap,
     },
     relay_rpc::domain::ClientId,
+    serde::Deserialize,
     std::sync::Arc,
     tracing::instrument,
 };
 
+#[derive(Deserialize)]
+pub struct DeleteClientQuery {
+    /// Only delete this device of the client, all devices are deleted if omitted
+    pub device_id: Option<String>,
+}
+
 #[instrument(skip_all, name = "delete_client_handler")]
 pub async fn handler(
     Path((tenant_id, id)): Path<(String, String)>,
+    Query(query): Query<DeleteClientQuery>,
     StateExtractor(state): StateExtractor<Arc<AppState>>,
     headers: HeaderMap,
 ) -> Result<Response> {
@@ -53,8 +63,22 @@ pub async fn handler(
         return Err(InvalidAuthentication);
     }
 
-    state.client_store.delete_client(&tenant_id, &id).await?;
-    debug!("client ({}) deleted for tenant ({})", id, tenant_id);
+    match &query.device_id {
+        Some(device_id) => {
+            state
+                .client_store
+                .delete_client_device(&tenant_id, &id, device_id)
+                .await?;
+            debug!(
+                "client ({}) device ({}) deleted for tenant ({})",
+                id, device_id, tenant_id
+            );
+        }
+        None => {
+            state.client_store.delete_client(&tenant_id, &id).await?;
+            debug!("client ({}) deleted for tenant ({})", id, tenant_id);
+        }
+    }
 
     debug!(
         %tenant_id,
@@ -62,5 +86,17 @@ pub async fn handler(
         "deleted client"
     );
 
+    #[cfg(feature = "analytics")]
+    if let Some(analytics) = &state.analytics {
+        analytics.client_deleted(ClientDeletedInfo {
+            project_id: tenant_id.into(),
+            client_id: id.into(),
+            device_id: query.device_id.map(Into::into),
+            push_provider: None,
+            reason: CLIENT_DELETED_REQUESTED.into(),
+            deleted_at: wc::analytics::time::now(),
+        });
+    }
+
     Ok(Response::default())
 }
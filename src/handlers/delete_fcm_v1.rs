@@ -1,9 +1,10 @@
 use {
     crate::{
         error::Error::{self},
-        handlers::validate_tenant_request,
+        handlers::{emit_credentials_updated, update_fcm_v1::FcmV1Path, validate_tenant_request},
         increment_counter,
         state::AppState,
+        stores::tenant::CredentialsKind,
     },
     axum::{
         extract::{Path, State},
@@ -17,16 +18,19 @@ use {
 #[instrument(skip_all, name = "delete_fcm_v1_handler")]
 pub async fn handler(
     State(state): State<Arc<AppState>>,
-    Path(id): Path<String>,
+    Path(path): Path<FcmV1Path>,
     headers: HeaderMap,
 ) -> Result<StatusCode, Error> {
+    let id = path.id.as_str();
+    let slot = path.named_slot()?;
+
     // JWT token verification
     #[cfg(feature = "cloud")]
     let jwt_verification_result =
-        validate_tenant_request(&state.jwt_validation_client, &headers, &id).await;
+        validate_tenant_request(&state.jwt_validation_client, &headers, id).await;
 
     // -- check if tenant is real
-    let _existing_tenant = state.tenant_store.get_tenant(&id).await?;
+    let _existing_tenant = state.tenant_store.get_tenant(id).await?;
 
     #[cfg(not(feature = "cloud"))]
     let jwt_verification_result = validate_tenant_request(&state.jwt_validation_client, &headers);
@@ -40,14 +44,29 @@ pub async fn handler(
         return Err(e);
     }
 
-    let new_tenant = state.tenant_store.update_tenant_delete_fcm_v1(&id).await?;
+    let new_tenant = match slot {
+        Some(slot) => {
+            state
+                .tenant_store
+                .update_tenant_delete_fcm_v1_slot(id, slot)
+                .await?
+        }
+        None => state.tenant_store.update_tenant_delete_fcm_v1(id).await?,
+    };
 
-    if new_tenant.suspended {
-        // If suspended, it can be restored now because valid credentials have been
-        // provided
-        state.tenant_store.unsuspend_tenant(&new_tenant.id).await?;
+    let unsuspended = new_tenant
+        .slot_suspended_reason(CredentialsKind::FcmV1, slot)
+        .is_some();
+    if unsuspended {
+        // The suspension no longer applies as the credentials have been removed
+        state
+            .tenant_store
+            .unsuspend_tenant_credentials(&new_tenant.id, CredentialsKind::FcmV1, slot)
+            .await?;
     }
 
+    emit_credentials_updated(&state, id, CredentialsKind::FcmV1, true, unsuspended).await;
+
     increment_counter!(state.metrics, tenant_fcm_v1_updates);
 
     Ok(StatusCode::NO_CONTENT)
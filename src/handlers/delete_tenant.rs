@@ -1,5 +1,8 @@
 use {
-    crate::{error::Error, handlers::validate_tenant_request, log::prelude::*, state::AppState},
+    crate::{
+        error::Error, handlers::validate_tenant_request, log::prelude::*, state::AppState,
+        tenant_deletion,
+    },
     axum::{
         extract::{Path, State},
         http::HeaderMap,
@@ -37,11 +40,13 @@ pub async fn handler(
         return Err(e);
     }
 
-    state.tenant_store.delete_tenant(&id).await?;
+    // Clients and notifications are deleted in the background, the tenant can't
+    // be fetched from now on
+    tenant_deletion::delete_tenant(state.clone(), &id).await?;
 
     debug!(
         tenant_id = %id,
-        "deleted tenant"
+        "marked tenant for deletion"
     );
 
     Ok(Json(DeleteTenantResponse { success: true }))
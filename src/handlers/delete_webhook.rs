@@ -0,0 +1,50 @@
+use {
+    crate::{
+        error::{Error, Error::InvalidWebhook},
+        handlers::validate_tenant_request,
+        state::AppState,
+    },
+    axum::{
+        extract::{Path, State},
+        http::HeaderMap,
+    },
+    hyper::StatusCode,
+    std::sync::Arc,
+    tracing::{error, info, instrument},
+};
+
+#[instrument(skip_all, name = "delete_webhook_handler")]
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Path((id, webhook_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<StatusCode, Error> {
+    // JWT token verification
+    #[cfg(feature = "cloud")]
+    let jwt_verification_result =
+        validate_tenant_request(&state.jwt_validation_client, &headers, &id).await;
+
+    #[cfg(not(feature = "cloud"))]
+    let jwt_verification_result = validate_tenant_request(&state.jwt_validation_client, &headers);
+
+    if let Err(e) = jwt_verification_result {
+        error!(
+            tenant_id = %id,
+            err = ?e,
+            "JWT verification failed"
+        );
+        return Err(e);
+    }
+
+    let webhooks = state
+        .webhooks
+        .as_ref()
+        .ok_or_else(|| InvalidWebhook("webhooks are not enabled".to_string()))?;
+
+    // Pending deliveries are deleted along with the webhook
+    webhooks.store().delete_webhook(&id, &webhook_id).await?;
+
+    info!(tenant_id = %id, %webhook_id, "deleted webhook");
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,59 @@
+use {
+    crate::{
+        error::Error,
+        handlers::validate_tenant_request,
+        log::prelude::*,
+        state::AppState,
+        tenant_archive::{self, TenantArchive},
+    },
+    axum::{
+        extract::{Path, Query, State},
+        http::HeaderMap,
+        Json,
+    },
+    serde::Deserialize,
+    std::sync::Arc,
+    tracing::instrument,
+};
+
+#[derive(Deserialize)]
+pub struct ExportTenantQuery {
+    /// X25519 public key of the deployment the tenant is moving to, credentials
+    /// are exported in plain text when not provided
+    pub recipient_public_key: Option<String>,
+}
+
+#[instrument(skip_all, name = "export_tenant_handler")]
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<ExportTenantQuery>,
+    headers: HeaderMap,
+) -> Result<Json<TenantArchive>, Error> {
+    // JWT token verification
+    #[cfg(feature = "cloud")]
+    let jwt_verification_result =
+        validate_tenant_request(&state.jwt_validation_client, &headers, &id).await;
+
+    #[cfg(not(feature = "cloud"))]
+    let jwt_verification_result = validate_tenant_request(&state.jwt_validation_client, &headers);
+
+    if let Err(e) = jwt_verification_result {
+        error!(
+            tenant_id = %id,
+            err = ?e,
+            "JWT verification failed"
+        );
+        return Err(e);
+    }
+
+    let archive = tenant_archive::export_tenant(
+        state.tenant_store.as_ref(),
+        state.client_store.as_ref(),
+        &id,
+        query.recipient_public_key.as_deref(),
+    )
+    .await?;
+
+    Ok(Json(archive))
+}
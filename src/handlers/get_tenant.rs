@@ -5,13 +5,14 @@ use {
         log::prelude::*,
         providers::{ProviderKind, PROVIDER_FCM_V1},
         state::AppState,
-        stores::tenant::ApnsType,
+        stores::tenant::{ApnsType, CredentialsKind, Tenant},
     },
     axum::{
         extract::{Path, State},
         http::HeaderMap,
         Json,
     },
+    chrono::{DateTime, Utc},
     serde::{Deserialize, Serialize},
     std::sync::Arc,
     tracing::instrument,
@@ -23,8 +24,113 @@ pub struct GetTenantResponse {
     pub enabled_providers: Vec<String>,
     pub apns_topic: Option<String>,
     pub apns_type: Option<ApnsType>,
+    /// Expiry of the APNs certificate, not set for token auth
+    pub apns_certificate_expires_at: Option<DateTime<Utc>>,
+    /// Additional APNs topics clients can register with
+    #[serde(default)]
+    pub apns_topics: Vec<String>,
+    /// Named FCM v1 credentials slots clients can register with
+    #[serde(default)]
+    pub fcm_v1_slots: Vec<String>,
+    /// Set while FCM clients can only be sent to with the legacy FCM HTTP API,
+    /// the tenant has to upload FCM v1 credentials to keep receiving pushes
+    #[serde(default)]
+    pub legacy_fcm_only: bool,
+    /// Set while any of the providers is suspended
     pub suspended: bool,
+    /// Reason of the first suspended provider
     pub suspended_reason: Option<String>,
+    pub providers: Vec<GetTenantProviderStatus>,
+    /// Status of the named FCM v1 slots, which are suspended separately from
+    /// the default slot reported in `providers`
+    #[serde(default)]
+    pub fcm_v1_slot_status: Vec<GetTenantFcmV1SlotStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetTenantProviderStatus {
+    pub provider: String,
+    /// Credentials the provider sends with, which are suspended together. For
+    /// FCM v1 this is the status of the default slot
+    pub credentials: Option<String>,
+    pub suspended: bool,
+    pub suspended_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetTenantFcmV1SlotStatus {
+    pub slot: String,
+    pub suspended: bool,
+    pub suspended_reason: Option<String>,
+}
+
+impl GetTenantResponse {
+    pub fn new(tenant: Tenant, public_url: &str) -> Self {
+        let providers = tenant.providers();
+
+        let mut res = GetTenantResponse {
+            url: format!("{}/{}", public_url, tenant.id),
+            enabled_providers: providers
+                .iter()
+                .map(|provider| provider.kind.into())
+                // Special case on fcm_v1 for credentials because providers() is also used for token management (of which FCM and FCM V1 tokens are the same)
+                .chain(if tenant.fcm_v1_credentials.is_some() || !tenant.fcm_v1_slots.is_empty() {
+                    vec![PROVIDER_FCM_V1.to_string()]
+                } else {
+                    vec![]
+                })
+                .collect(),
+            apns_topic: None,
+            apns_type: None,
+            apns_certificate_expires_at: None,
+            apns_topics: vec![],
+            fcm_v1_slots: tenant.fcm_v1_slots.keys().cloned().collect(),
+            legacy_fcm_only: tenant.is_legacy_fcm_only(),
+            suspended: false,
+            suspended_reason: None,
+            providers: providers
+                .iter()
+                .map(|provider| GetTenantProviderStatus {
+                    provider: provider.kind.into(),
+                    credentials: provider
+                        .credentials
+                        .map(|credentials| credentials.to_string()),
+                    suspended: provider.is_suspended(),
+                    suspended_reason: provider.suspended_reason.clone(),
+                })
+                .collect(),
+            fcm_v1_slot_status: tenant
+                .fcm_v1_slots
+                .keys()
+                .map(|slot| {
+                    let suspended_reason =
+                        tenant.slot_suspended_reason(CredentialsKind::FcmV1, Some(slot));
+                    GetTenantFcmV1SlotStatus {
+                        slot: slot.clone(),
+                        suspended: suspended_reason.is_some(),
+                        suspended_reason: suspended_reason.map(ToString::to_string),
+                    }
+                })
+                .collect(),
+        };
+
+        let suspended_provider = res.providers.iter().find(|provider| provider.suspended);
+        res.suspended = suspended_provider.is_some();
+        res.suspended_reason =
+            suspended_provider.and_then(|provider| provider.suspended_reason.clone());
+
+        if providers
+            .iter()
+            .any(|provider| provider.kind == ProviderKind::Apns)
+        {
+            res.apns_topic = tenant.apns_topic;
+            res.apns_type = tenant.apns_type;
+            res.apns_certificate_expires_at = tenant.apns_certificate_not_after;
+            res.apns_topics = tenant.apns_topics;
+        }
+
+        res
+    }
 }
 
 #[instrument(skip_all, name = "get_tenant_handler")]
@@ -50,32 +156,7 @@ pub async fn handler(
     }
 
     let tenant = state.tenant_store.get_tenant(&id).await?;
-
-    let providers = tenant.providers();
-
-    let mut res = GetTenantResponse {
-        url: format!("{}/{}", state.config.public_url, tenant.id),
-        enabled_providers: tenant
-            .providers()
-            .iter()
-            .map(Into::into)
-            // Special case on fcm_v1 for credentials because providers() is also used for token management (of which FCM and FCM V1 tokens are the same)
-            .chain(if tenant.fcm_v1_credentials.is_some() {
-                vec![PROVIDER_FCM_V1.to_string()]
-            } else {
-                vec![]
-            })
-            .collect(),
-        apns_topic: None,
-        apns_type: None,
-        suspended: tenant.suspended,
-        suspended_reason: tenant.suspended_reason,
-    };
-
-    if providers.contains(&ProviderKind::Apns) {
-        res.apns_topic = tenant.apns_topic;
-        res.apns_type = tenant.apns_type;
-    }
+    let res = GetTenantResponse::new(tenant, &state.config.public_url);
 
     debug!(
         tenant_id = %id,
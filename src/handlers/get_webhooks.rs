@@ -0,0 +1,57 @@
+use {
+    crate::{
+        error::{Error, Error::InvalidWebhook},
+        handlers::validate_tenant_request,
+        state::AppState,
+        stores::webhook::Webhook,
+    },
+    axum::{
+        extract::{Path, State},
+        http::HeaderMap,
+        Json,
+    },
+    serde::{Deserialize, Serialize},
+    std::sync::Arc,
+    tracing::{error, instrument},
+};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetWebhooksResponse {
+    pub webhooks: Vec<Webhook>,
+}
+
+#[instrument(skip_all, name = "get_webhooks_handler")]
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<GetWebhooksResponse>, Error> {
+    // JWT token verification
+    #[cfg(feature = "cloud")]
+    let jwt_verification_result =
+        validate_tenant_request(&state.jwt_validation_client, &headers, &id).await;
+
+    #[cfg(not(feature = "cloud"))]
+    let jwt_verification_result = validate_tenant_request(&state.jwt_validation_client, &headers);
+
+    if let Err(e) = jwt_verification_result {
+        error!(
+            tenant_id = %id,
+            err = ?e,
+            "JWT verification failed"
+        );
+        return Err(e);
+    }
+
+    let webhooks = state
+        .webhooks
+        .as_ref()
+        .ok_or_else(|| InvalidWebhook("webhooks are not enabled".to_string()))?;
+
+    // Ensure tenant real
+    let _existing_tenant = state.tenant_store.get_tenant(&id).await?;
+
+    let webhooks = webhooks.store().get_webhooks(&id).await?;
+
+    Ok(Json(GetWebhooksResponse { webhooks }))
+}
@@ -1,9 +1,17 @@
 use {
-    crate::state::AppState,
-    axum::{extract::State as ExtractState, http::StatusCode, response::IntoResponse},
-    std::sync::Arc,
+    crate::{log::prelude::*, state::AppState},
+    axum::{extract::State as ExtractState, http::StatusCode, response::IntoResponse, Json},
+    serde::Serialize,
+    sqlx::PgPool,
+    std::{
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    },
 };
 
+/// How long a database gets to answer the readiness check
+const DATABASE_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
 pub async fn handler(ExtractState(state): ExtractState<Arc<AppState>>) -> impl IntoResponse {
     let build_commit = match state.build_info.version_control.clone() {
         Some(v) => v.git().unwrap().commit_short_id.clone(),
@@ -21,3 +29,144 @@ pub async fn handler(ExtractState(state): ExtractState<Arc<AppState>>) -> impl I
         ),
     )
 }
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Unavailable,
+    Draining,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LiveResponse {
+    pub status: HealthStatus,
+    pub version: String,
+    pub instance_id: String,
+    pub uptime_secs: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DatabaseCheck {
+    pub ok: bool,
+    /// Latest applied migration
+    pub migration_version: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AnalyticsCheck {
+    pub enabled: bool,
+    /// Records collected but not exported yet
+    pub pending_records: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReadyResponse {
+    pub status: HealthStatus,
+    pub database: DatabaseCheck,
+    /// Only checked in multi-tenant mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant_database: Option<DatabaseCheck>,
+    pub provider_cache_size: u64,
+    pub analytics: AnalyticsCheck,
+}
+
+/// The process is running, nothing else is checked
+pub async fn live_handler(ExtractState(state): ExtractState<Arc<AppState>>) -> Json<LiveResponse> {
+    Json(LiveResponse {
+        status: HealthStatus::Ok,
+        version: state.build_info.crate_info.version.to_string(),
+        instance_id: state.instance_id.to_string(),
+        uptime_secs: state.uptime.elapsed().as_secs(),
+    })
+}
+
+/// Checks the databases can be queried, responds with 503 when they can't or
+/// from the moment a shutdown signal is received
+pub async fn ready_handler(
+    ExtractState(state): ExtractState<Arc<AppState>>,
+) -> (StatusCode, Json<ReadyResponse>) {
+    let database = check_database(state.database.as_ref()).await;
+    let tenant_database = match &state.tenant_database {
+        Some(pool) => Some(check_database(Some(pool)).await),
+        None => None,
+    };
+
+    let status = if state.draining.load(Ordering::Relaxed) {
+        HealthStatus::Draining
+    } else if !database.ok || tenant_database.as_ref().is_some_and(|check| !check.ok) {
+        HealthStatus::Unavailable
+    } else {
+        HealthStatus::Ok
+    };
+    let status_code = match status {
+        HealthStatus::Ok => StatusCode::OK,
+        _ => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (
+        status_code,
+        Json(ReadyResponse {
+            status,
+            database,
+            tenant_database,
+            provider_cache_size: state.provider_cache.entry_count(),
+            analytics: analytics_check(&state),
+        }),
+    )
+}
+
+async fn check_database(pool: Option<&PgPool>) -> DatabaseCheck {
+    let Some(pool) = pool else {
+        return DatabaseCheck {
+            ok: false,
+            migration_version: None,
+            error: Some("not connected".to_string()),
+        };
+    };
+
+    let query = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT MAX(version) FROM _sqlx_migrations WHERE success",
+    )
+    .fetch_one(pool);
+    let error = match tokio::time::timeout(DATABASE_CHECK_TIMEOUT, query).await {
+        Ok(Ok(migration_version)) => {
+            return DatabaseCheck {
+                ok: true,
+                migration_version,
+                error: None,
+            }
+        }
+        Ok(Err(e)) => e.to_string(),
+        Err(_) => "timed out".to_string(),
+    };
+
+    warn!("readiness check failed to query database: {error}");
+    DatabaseCheck {
+        ok: false,
+        migration_version: None,
+        error: Some(error),
+    }
+}
+
+#[cfg(feature = "analytics")]
+fn analytics_check(state: &AppState) -> AnalyticsCheck {
+    AnalyticsCheck {
+        enabled: state.analytics.is_some(),
+        pending_records: state
+            .analytics
+            .as_ref()
+            .map(|analytics| analytics.pending_records())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(not(feature = "analytics"))]
+fn analytics_check(_state: &AppState) -> AnalyticsCheck {
+    AnalyticsCheck {
+        enabled: false,
+        pending_records: 0,
+    }
+}
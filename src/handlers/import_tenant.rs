@@ -0,0 +1,47 @@
+use {
+    crate::{
+        error::Error,
+        handlers::validate_tenant_request,
+        log::prelude::*,
+        state::AppState,
+        tenant_archive::{self, ImportSummary, TenantArchive},
+    },
+    axum::{extract::State, http::HeaderMap, Json},
+    std::sync::Arc,
+    tracing::instrument,
+};
+
+#[instrument(skip_all, name = "import_tenant_handler")]
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(archive): Json<TenantArchive>,
+) -> Result<Json<ImportSummary>, Error> {
+    // JWT token verification
+    #[cfg(feature = "cloud")]
+    let jwt_verification_result =
+        validate_tenant_request(&state.jwt_validation_client, &headers, &archive.tenant.id).await;
+
+    #[cfg(not(feature = "cloud"))]
+    let jwt_verification_result = validate_tenant_request(&state.jwt_validation_client, &headers);
+
+    if let Err(e) = jwt_verification_result {
+        error!(
+            tenant_id = %archive.tenant.id,
+            err = ?e,
+            "JWT verification failed"
+        );
+        return Err(e);
+    }
+
+    let summary = tenant_archive::import_tenant(
+        state.tenant_store.as_ref(),
+        state.client_store.as_ref(),
+        archive,
+        state.config.tenant_archive_private_key.as_deref(),
+        state.metrics.as_ref(),
+    )
+    .await?;
+
+    Ok(Json(summary))
+}
@@ -1,7 +1,15 @@
+#[cfg(feature = "analytics")]
+use crate::analytics::{
+    tenant_credentials_updated_info::TenantCredentialsUpdatedInfo,
+    tenant_suspended_info::TenantSuspendedInfo,
+};
 use {
     crate::{
         error::{Error::InvalidAuthentication, Result},
         jwt_validation::{Claims, JwtValidationClient},
+        state::AppState,
+        stores::tenant::CredentialsKind,
+        webhooks::WebhookEvent,
     },
     axum::{
         http::{header::AUTHORIZATION, HeaderMap},
@@ -24,28 +32,26 @@ pub mod delete_client;
 pub mod metrics;
 pub mod push_message;
 pub mod register_client;
-#[cfg(not(feature = "multitenant"))]
+pub mod register_clients_bulk;
 pub mod single_tenant_wrappers;
 // Tenant Management
-#[cfg(feature = "multitenant")]
 pub mod create_tenant;
-#[cfg(feature = "multitenant")]
+pub mod create_webhook;
 pub mod delete_apns;
-#[cfg(feature = "multitenant")]
 pub mod delete_fcm;
-#[cfg(feature = "multitenant")]
 pub mod delete_fcm_v1;
-#[cfg(feature = "multitenant")]
 pub mod delete_tenant;
-#[cfg(feature = "multitenant")]
+pub mod delete_webhook;
+pub mod export_tenant;
 pub mod get_tenant;
+pub mod get_webhooks;
 pub mod health;
+pub mod import_tenant;
 pub mod rate_limit_test;
-#[cfg(feature = "multitenant")]
+pub mod test_tenant;
 pub mod update_apns;
-#[cfg(feature = "multitenant")]
+pub mod update_apns_topics;
 pub mod update_fcm;
-#[cfg(feature = "multitenant")]
 pub mod update_fcm_v1;
 
 pub const DECENTRALIZED_IDENTIFIER_PREFIX: &str = "did:key:";
@@ -78,7 +84,7 @@ where
     };
 }
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum ErrorLocation {
     Body,
@@ -96,7 +102,7 @@ pub enum ResponseStatus {
     Failure,
 }
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, Debug)]
 pub struct ErrorField {
     pub field: String,
     pub description: String,
@@ -204,3 +210,62 @@ pub fn validate_tenant_request(
 ) -> Result<()> {
     validate_jwt(jwt_validation_client, headers).map(|_| ())
 }
+
+/// Notifies the tenant's webhooks and analytics that credentials were updated
+/// or removed
+pub async fn emit_credentials_updated(
+    state: &AppState,
+    tenant_id: &str,
+    credentials: CredentialsKind,
+    removed: bool,
+    unsuspended: bool,
+) {
+    if let Some(webhooks) = &state.webhooks {
+        webhooks
+            .emit_credentials_updated(tenant_id, credentials, removed, unsuspended)
+            .await;
+    }
+
+    #[cfg(feature = "analytics")]
+    if let Some(analytics) = &state.analytics {
+        analytics.tenant_credentials_updated(TenantCredentialsUpdatedInfo {
+            project_id: tenant_id.into(),
+            credentials: credentials.as_str().into(),
+            removed,
+            unsuspended,
+            updated_at: wc::analytics::time::now(),
+        });
+    }
+}
+
+/// Notifies the tenant's webhooks and analytics that credentials were
+/// suspended
+pub async fn emit_tenant_suspended(
+    state: &AppState,
+    tenant_id: &str,
+    credentials: CredentialsKind,
+    reason: &str,
+) {
+    if let Some(webhooks) = &state.webhooks {
+        webhooks
+            .emit(
+                tenant_id,
+                WebhookEvent::TenantSuspended,
+                json!({
+                    "credentials": credentials,
+                    "reason": reason,
+                }),
+            )
+            .await;
+    }
+
+    #[cfg(feature = "analytics")]
+    if let Some(analytics) = &state.analytics {
+        analytics.tenant_suspended(TenantSuspendedInfo {
+            project_id: tenant_id.into(),
+            credentials: credentials.as_str().into(),
+            reason: reason.into(),
+            suspended_at: wc::analytics::time::now(),
+        });
+    }
+}
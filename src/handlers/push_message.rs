@@ -1,5 +1,8 @@
 #[cfg(feature = "analytics")]
-use axum_client_ip::SecureClientIp;
+use {
+    crate::analytics::client_deleted_info::{ClientDeletedInfo, CLIENT_DELETED_BAD_TOKEN},
+    axum_client_ip::SecureClientIp,
+};
 use {
     crate::{
         analytics::message_info::MessageInfo,
@@ -7,13 +10,17 @@ use {
             Error,
             Error::{ClientNotFound, Store},
         },
-        handlers::DECENTRALIZED_IDENTIFIER_PREFIX,
+        handlers::{emit_tenant_suspended, DECENTRALIZED_IDENTIFIER_PREFIX},
         increment_counter,
         log::prelude::*,
         middleware::validate_signature::RequireValidSignature,
         providers::{LegacyPushMessage, Provider, PushMessage, PushProvider, RawPushMessage},
         state::AppState,
-        stores::StoreError,
+        stores::{
+            tenant::{CredentialsKind, DEFAULT_FCM_V1_SLOT},
+            StoreError,
+        },
+        webhooks::WebhookEvent,
     },
     axum::{
         extract::{Json, Path, State as StateExtractor},
@@ -21,11 +28,15 @@ use {
         response::IntoResponse,
     },
     serde::{Deserialize, Serialize},
-    std::sync::Arc,
+    serde_json::json,
+    std::{sync::Arc, time::Instant},
     tap::TapFallible,
     tracing::instrument,
 };
 
+/// Reason the APNs credentials are suspended with when the certificate expired
+pub const APNS_CERTIFICATE_EXPIRED_REASON: &str = "APNs certificate expired";
+
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct PushMessageBody {
     #[serde(flatten)]
@@ -43,17 +54,26 @@ pub async fn handler(
     StateExtractor(state): StateExtractor<Arc<AppState>>,
     RequireValidSignature(Json(body)): RequireValidSignature<Json<PushMessageBody>>,
 ) -> Result<axum::response::Response, Error> {
+    let start = Instant::now();
+    let mut tenant_loaded = false;
     let res = handler_internal(
         Path((tenant_id.clone(), client_id.clone())),
         StateExtractor(state.clone()),
         RequireValidSignature(Json(body.clone())),
+        &mut tenant_loaded,
     )
     .await;
+    // Metrics are only labelled with tenants that exist, not with any id
+    // requested
+    let metrics_tenant = tenant_loaded.then_some(tenant_id.as_str());
 
     let inner_packed = match res {
         Ok((res, analytics_options_inner)) => (res.status().as_u16(), res, analytics_options_inner),
         Err((error, analytics_option_inner)) => {
             warn!("error handling push message: {error:?}");
+            if let Some(metrics) = &state.metrics {
+                metrics.push_failure(&error, metrics_tenant);
+            }
 
             #[cfg(feature = "analytics")]
             let error_str = format!("{:?}", &error);
@@ -80,6 +100,10 @@ pub async fn handler(
         }
     };
 
+    if let Some(metrics) = &state.metrics {
+        metrics.push_handler(inner_packed.0, metrics_tenant, start);
+    }
+
     #[cfg(feature = "analytics")]
     let (status, response, analytics_option) = inner_packed;
 
@@ -90,7 +114,7 @@ pub async fn handler(
     if let Some(mut message_info) = analytics_option {
         message_info.status = status;
 
-        tokio::spawn(async move {
+        state.tasks.clone().spawn(async move {
             if let Some(analytics) = &state.analytics {
                 let (country, continent, region) = analytics
                     .lookup_geo_data(client_ip)
@@ -117,14 +141,20 @@ pub async fn handler(
     Ok(response)
 }
 
-#[instrument(name = "push_message_internal", skip_all, fields(tenant_id = tenant_id, client_id = client_id))]
+#[instrument(name = "push_message_internal", skip_all, fields(tenant_id = tenant_id, client_id = client_id, notification_id = tracing::field::Empty))]
+/// `tenant_loaded` is set once the tenant has been loaded
 pub async fn handler_internal(
     Path((tenant_id, client_id)): Path<(String, String)>,
     StateExtractor(state): StateExtractor<Arc<AppState>>,
     RequireValidSignature(Json(body)): RequireValidSignature<Json<PushMessageBody>>,
+    tenant_loaded: &mut bool,
 ) -> Result<(axum::response::Response, Option<MessageInfo>), (Error, Option<MessageInfo>)> {
-    let client = match state.client_store.get_client(&tenant_id, &client_id).await {
-        Ok(c) => Ok(c),
+    let devices = match state
+        .client_store
+        .get_client_devices(&tenant_id, &client_id)
+        .await
+    {
+        Ok(devices) => Ok(devices),
         Err(StoreError::NotFound(_, _)) => Err(ClientNotFound),
         Err(e) => Err(Store(e)),
     }
@@ -168,29 +198,17 @@ pub async fn handler_internal(
         )
     })?;
 
+    // Devices are ordered by registration, the first one determines the message id
+    // used for deduplication and is reported in analytics. All devices of a client
+    // share `always_raw`, registering a device with another one is rejected
+    let client = devices[0].clone();
+
     let cloned_body = body.clone();
-    let push_message = if client.always_raw {
-        if let Some(body) = body.raw {
-            PushMessage::RawPushMessage(body)
-        } else {
-            return Err((
-                Error::EmptyField("missing topic, tag, or message field".to_string()),
-                None,
-            ));
-        }
-    } else {
-        #[allow(clippy::collapsible_else_if)]
-        if let Some(body) = body.legacy {
-            PushMessage::LegacyPushMessage(body)
-        } else {
-            return Err((
-                Error::EmptyField("missing id or payload field".to_string()),
-                None,
-            ));
-        }
-    };
+    let push_message =
+        build_push_message(&body, client.always_raw).map_err(|error| (error, None))?;
 
     let message_id = push_message.message_id();
+    tracing::Span::current().record("notification_id", &*message_id);
 
     #[cfg(feature = "analytics")]
     let mut analytics = Some(MessageInfo {
@@ -235,34 +253,13 @@ pub async fn handler_internal(
             "client tenant id does not match request tenant id"
         );
 
-        #[cfg(feature = "multitenant")]
-        {
-            if client.tenant_id == "0000-0000-0000-0000" {
-                warn!(
-                    %tenant_id,
-                    client_id = %client_id,
-                    "client tenant id has not been set, allowing request to continue"
-                );
-            } else {
-                #[cfg(feature = "analytics")]
-                {
-                    analytics = Some(MessageInfo {
-                        response_message: Some(
-                            "Client tenant id does not match request tenant id".into(),
-                        ),
-                        ..analytics.unwrap()
-                    });
-
-                    return Err((Error::MissmatchedTenantId, analytics));
-                }
-
-                #[cfg(not(feature = "analytics"))]
-                return Err((Error::MissmatchedTenantId, None));
-            }
-        }
-
-        #[cfg(not(feature = "multitenant"))]
-        {
+        if state.config.is_multitenant() && client.tenant_id == "0000-0000-0000-0000" {
+            warn!(
+                %tenant_id,
+                client_id = %client_id,
+                "client tenant id has not been set, allowing request to continue"
+            );
+        } else {
             #[cfg(feature = "analytics")]
             {
                 analytics = Some(MessageInfo {
@@ -352,6 +349,7 @@ pub async fn handler_internal(
         .await
         .tap_err(|e| warn!("error fetching tenant: {e:?}"))
         .map_err(|e| (e, analytics.clone()))?;
+    *tenant_loaded = true;
     debug!(
         %tenant_id,
         client_id = %client_id,
@@ -359,119 +357,220 @@ pub async fn handler_internal(
         "fetched tenant"
     );
 
-    if tenant.suspended {
-        warn!("tenant suspended");
-        return Err((Error::TenantSuspended, analytics.clone()));
-    }
+    let mut delivered = false;
+    let mut send_error = None;
+    // Credentials and FCM v1 slots suspended while sending to earlier devices
+    let mut suspended_credentials: Vec<(CredentialsKind, Option<String>)> = vec![];
+    for device in devices {
+        // Devices of other providers are still sent to when one provider's
+        // credentials are suspended
+        let credentials = tenant.credentials_kind(&device.push_type);
+        // Each FCM v1 slot is suspended separately
+        let slot = match credentials {
+            Some(CredentialsKind::FcmV1) => device
+                .fcm_v1_slot
+                .clone()
+                .filter(|slot| slot != DEFAULT_FCM_V1_SLOT),
+            _ => None,
+        };
+        if let Some(credentials) = credentials {
+            if tenant
+                .slot_suspended_reason(credentials, slot.as_deref())
+                .is_some()
+                || suspended_credentials.contains(&(credentials, slot.clone()))
+            {
+                debug!(
+                    %tenant_id,
+                    client_id = %client_id,
+                    device_id = %device.device_id,
+                    credentials = credentials.as_str(),
+                    "tenant's credentials are suspended"
+                );
+                send_error.get_or_insert(Error::ProviderSuspended(credentials));
+                continue;
+            }
 
-    let provider = tenant
-        .provider(
-            &client.push_type,
-            state.http_client.clone(),
-            &state.provider_cache,
-        )
-        .await
-        .tap_err(|e| warn!("error fetching provider: {e:?}"))
-        .map_err(|e| (e, analytics.clone()))?;
-    debug!(
-        %tenant_id,
-        client_id = %client_id,
-        notification_id = %notification.id,
-        push_type = client.push_type.as_str(),
-        "fetched provider"
-    );
+            // Sending would fail and suspend the tenant's FCM credentials
+            if credentials == CredentialsKind::Fcm && state.config.legacy_fcm_fail_fast {
+                debug!(
+                    %tenant_id,
+                    client_id = %client_id,
+                    device_id = %device.device_id,
+                    "tenant only has legacy FCM credentials"
+                );
+                send_error.get_or_insert(Error::LegacyFcmDeprecated);
+                continue;
+            }
+        }
 
-    match provider.send_notification(client.token, push_message).await {
-        Ok(()) => Ok(()),
-        Err(error) => {
-            warn!("error sending notification: {error:?}");
-            match error {
-                Error::BadDeviceToken(_) => {
-                    state
-                        .client_store
-                        .delete_client(&tenant_id, &client_id)
-                        .await
-                        .map_err(|e| (Error::Store(e), analytics.clone()))?;
-                    increment_counter!(state.metrics, client_suspensions);
-                    warn!(
-                        %tenant_id,
-                        client_id = %client_id,
-                        notification_id = %notification.id,
-                        push_type = client.push_type.as_str(),
-                        "client has been deleted due to a bad device token"
-                    );
-                    Err(Error::ClientDeleted)
-                }
-                Error::BadApnsCredentials => {
-                    state
-                        .tenant_store
-                        .suspend_tenant(&tenant_id, "Invalid APNS Credentials")
-                        .await
-                        .map_err(|e| (e, analytics.clone()))?;
-                    increment_counter!(state.metrics, tenant_suspensions);
-                    warn!(
-                        %tenant_id,
-                        client_id = %client_id,
-                        notification_id = %notification.id,
-                        push_type = client.push_type.as_str(),
-                        "tenant has been suspended due to invalid provider credentials"
-                    );
-                    Err(Error::TenantSuspended)
-                }
-                Error::ApnsCertificateExpired => {
-                    let reason = "APNs certificate expired";
-                    state
-                        .tenant_store
-                        .suspend_tenant(&tenant_id, reason)
-                        .await
-                        .map_err(|e| (e, analytics.clone()))?;
-                    increment_counter!(state.metrics, tenant_suspensions);
-                    warn!(
-                        %tenant_id,
-                        client_id = %client_id,
-                        notification_id = %notification.id,
-                        push_type = client.push_type.as_str(),
-                        "tenant has been suspended due to: {reason}"
-                    );
-                    Err(Error::TenantSuspended)
+        let device_message = match build_push_message(&cloned_body, device.always_raw) {
+            Ok(message) => message,
+            Err(error) => {
+                warn!(
+                    %tenant_id,
+                    client_id = %client_id,
+                    device_id = %device.device_id,
+                    "error building push message for device: {error:?}"
+                );
+                send_error.get_or_insert(error);
+                continue;
+            }
+        };
+
+        let provider = match tenant
+            .provider(
+                &device.push_type,
+                device.fcm_v1_slot.as_deref(),
+                state.http_client.clone(),
+                &state.provider_cache,
+            )
+            .await
+        {
+            Ok(provider) => provider.with_apns_topic(device.apns_topic.as_deref()),
+            Err(error) => {
+                warn!("error fetching provider: {error:?}");
+                send_error.get_or_insert(error);
+                continue;
+            }
+        };
+        debug!(
+            %tenant_id,
+            client_id = %client_id,
+            device_id = %device.device_id,
+            notification_id = %notification.id,
+            push_type = device.push_type.as_str(),
+            "fetched provider"
+        );
+
+        let send_start = Instant::now();
+        let send_result = provider
+            .send_notification(device.token.clone(), device_message)
+            .await;
+        if let Some(metrics) = &state.metrics {
+            metrics.provider_send(
+                provider.name(),
+                send_outcome(&send_result),
+                Some(&tenant_id),
+                send_start,
+            );
+        }
+
+        let result = match send_result {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                warn!("error sending notification: {error:?}");
+                match error {
+                    Error::BadDeviceToken(_) => {
+                        state
+                            .client_store
+                            .delete_client_device(&tenant_id, &client_id, &device.device_id)
+                            .await
+                            .map_err(|e| (Error::Store(e), analytics.clone()))?;
+                        increment_counter!(state.metrics, client_suspensions);
+                        warn!(
+                            %tenant_id,
+                            client_id = %client_id,
+                            device_id = %device.device_id,
+                            notification_id = %notification.id,
+                            push_type = device.push_type.as_str(),
+                            "client device has been deleted due to a bad device token"
+                        );
+                        #[cfg(feature = "analytics")]
+                        if let Some(analytics) = &state.analytics {
+                            analytics.client_deleted(ClientDeletedInfo {
+                                project_id: tenant_id.clone().into(),
+                                client_id: client_id.clone().into(),
+                                device_id: Some(device.device_id.clone().into()),
+                                push_provider: Some(device.push_type.as_str().into()),
+                                reason: CLIENT_DELETED_BAD_TOKEN.into(),
+                                deleted_at: wc::analytics::time::now(),
+                            });
+                        }
+                        if let Some(webhooks) = &state.webhooks {
+                            webhooks
+                                .emit(
+                                    &tenant_id,
+                                    WebhookEvent::ClientDeletedBadToken,
+                                    json!({
+                                        "client_id": client_id,
+                                        "device_id": device.device_id,
+                                        "type": device.push_type.as_str(),
+                                    }),
+                                )
+                                .await;
+                        }
+                        Err(Error::ClientDeleted)
+                    }
+                    error => match (credentials, suspension_reason(&error)) {
+                        (Some(credentials), Some(reason)) => {
+                            state
+                                .tenant_store
+                                .suspend_tenant_credentials(
+                                    &tenant_id,
+                                    credentials,
+                                    slot.as_deref(),
+                                    reason,
+                                )
+                                .await
+                                .map_err(|e| (e, analytics.clone()))?;
+                            suspended_credentials.push((credentials, slot.clone()));
+                            increment_counter!(state.metrics, tenant_suspensions);
+                            warn!(
+                                %tenant_id,
+                                client_id = %client_id,
+                                notification_id = %notification.id,
+                                push_type = device.push_type.as_str(),
+                                credentials = credentials.as_str(),
+                                fcm_v1_slot = ?slot,
+                                "tenant's credentials have been suspended due to: {reason}"
+                            );
+                            emit_tenant_suspended(&state, &tenant_id, credentials, reason).await;
+                            Err(Error::ProviderSuspended(credentials))
+                        }
+                        _ => Err(error),
+                    },
                 }
-                Error::BadFcmApiKey => {
-                    state
-                        .tenant_store
-                        .suspend_tenant(&tenant_id, "Invalid FCM Credentials")
-                        .await
-                        .map_err(|e| (e, analytics.clone()))?;
-                    increment_counter!(state.metrics, tenant_suspensions);
-                    warn!(
-                        %tenant_id,
-                        client_id = %client_id,
-                        notification_id = %notification.id,
-                        push_type = client.push_type.as_str(),
-                        "tenant has been suspended due to invalid provider credentials"
-                    );
-                    Err(Error::TenantSuspended)
+            }
+        };
+
+        match result {
+            Ok(()) => {
+                delivered = true;
+                debug!(
+                    %tenant_id,
+                    client_id = %client_id,
+                    device_id = %device.device_id,
+                    notification_id = %notification.id,
+                    push_type = device.push_type.as_str(),
+                    "sent notification"
+                );
+
+                // Provider specific metrics
+                match provider {
+                    #[cfg(feature = "legacy_fcm")]
+                    Provider::Fcm(_) => increment_counter!(state.metrics, sent_fcm_notifications),
+                    Provider::FcmV1(_) => {
+                        increment_counter!(state.metrics, sent_fcm_v1_notifications)
+                    }
+                    Provider::Apns(_) => increment_counter!(state.metrics, sent_apns_notifications),
+                    #[cfg(any(debug_assertions, test))]
+                    Provider::Noop(_) => {}
                 }
-                e => Err(e),
+            }
+            // Only the failing device was deleted, the others are still tried
+            Err(Error::ClientDeleted) => {}
+            Err(error) => {
+                send_error.get_or_insert(error);
             }
         }
     }
-    .map_err(|e| (e, analytics.clone()))?;
 
-    debug!(
-        %tenant_id,
-        client_id = %client_id,
-        notification_id = %notification.id,
-        push_type = client.push_type.as_str(),
-        "sent notification"
-    );
-
-    // Provider specific metrics
-    match provider {
-        Provider::Fcm(_) => increment_counter!(state.metrics, sent_fcm_notifications),
-        Provider::FcmV1(_) => increment_counter!(state.metrics, sent_fcm_v1_notifications),
-        Provider::Apns(_) => increment_counter!(state.metrics, sent_apns_notifications),
-        #[cfg(any(debug_assertions, test))]
-        Provider::Noop(_) => {}
+    if !delivered {
+        // If no other error occurred then every device was deleted for a bad token
+        return Err((
+            send_error.unwrap_or(Error::ClientDeleted),
+            analytics.clone(),
+        ));
     }
 
     #[cfg(feature = "analytics")]
@@ -487,3 +586,42 @@ pub async fn handler_internal(
     #[cfg(not(feature = "analytics"))]
     Ok(((StatusCode::ACCEPTED).into_response(), None))
 }
+
+/// Reason to suspend the credentials the error was returned for, `None` when
+/// the error isn't caused by the credentials
+fn suspension_reason(error: &Error) -> Option<&'static str> {
+    match error {
+        Error::BadApnsCredentials => Some("Invalid APNS Credentials"),
+        Error::ApnsCertificateExpired => Some(APNS_CERTIFICATE_EXPIRED_REASON),
+        Error::BadFcmApiKey => Some("Invalid FCM Credentials"),
+        Error::BadFcmV1Credentials => Some("Invalid FCM V1 Credentials"),
+        _ => None,
+    }
+}
+
+/// Outcome label of the provider send latency metric
+fn send_outcome<T>(result: &Result<T, Error>) -> &'static str {
+    match result {
+        Ok(_) => "ok",
+        Err(Error::BadDeviceToken(_)) => "bad_token",
+        Err(error) if suspension_reason(error).is_some() => "bad_credentials",
+        Err(Error::Apns(_) | Error::FcmV1(_) | Error::HttpRequest(_)) => "transient",
+        #[cfg(feature = "legacy_fcm")]
+        Err(Error::Fcm(_)) => "transient",
+        Err(_) => "other",
+    }
+}
+
+fn build_push_message(body: &PushMessageBody, always_raw: bool) -> Result<PushMessage, Error> {
+    if always_raw {
+        body.raw
+            .clone()
+            .map(PushMessage::RawPushMessage)
+            .ok_or_else(|| Error::EmptyField("missing topic, tag, or message field".to_string()))
+    } else {
+        body.legacy
+            .clone()
+            .map(PushMessage::LegacyPushMessage)
+            .ok_or_else(|| Error::EmptyField("missing id or payload field".to_string()))
+    }
+}
@@ -3,14 +3,21 @@ use {crate::analytics::client_info::ClientInfo, axum_client_ip::SecureClientIp};
 use {
     crate::{
         error::{
-            Error::{EmptyField, InvalidAuthentication, ProviderNotAvailable},
+            Error::{
+                EmptyField, InvalidApnsTopic, InvalidAuthentication, InvalidFcmV1Slot,
+                ProviderNotAvailable,
+            },
             Result,
         },
         handlers::{authenticate_client, Response, DECENTRALIZED_IDENTIFIER_PREFIX},
         increment_counter,
         log::prelude::*,
+        providers::ProviderKind,
         state::AppState,
-        stores::client::Client,
+        stores::{
+            client::{Client, DEFAULT_DEVICE_ID},
+            tenant::{Tenant, DEFAULT_FCM_V1_SLOT},
+        },
     },
     axum::{
         extract::{Json, Path, State as StateExtractor},
@@ -29,6 +36,87 @@ pub struct RegisterBody {
     pub push_type: String,
     pub token: String,
     pub always_raw: Option<bool>,
+    /// Identifies the device when the same client id is registered on multiple
+    /// devices, defaults to [`DEFAULT_DEVICE_ID`]
+    pub device_id: Option<String>,
+    /// APNs topic (bundle id) of the app the token belongs to, defaults to the
+    /// tenant's topic
+    #[serde(alias = "bundle_id")]
+    pub apns_topic: Option<String>,
+    /// FCM v1 credentials slot of the app the token belongs to, defaults to
+    /// the tenant's FCM v1 credentials
+    pub fcm_v1_slot: Option<String>,
+}
+
+impl RegisterBody {
+    /// Validates the registration against the tenant's supported providers,
+    /// APNs topics and FCM v1 slots and returns the client id along with the client to be stored
+    pub fn into_client(
+        self,
+        tenant: &Tenant,
+        supported_providers: &[ProviderKind],
+    ) -> Result<(String, Client)> {
+        let push_type = self.push_type.as_str().try_into()?;
+        if !supported_providers.contains(&push_type) {
+            return Err(ProviderNotAvailable(push_type.into()));
+        }
+
+        if self.token.is_empty() {
+            return Err(EmptyField("token".to_string()));
+        }
+
+        let device_id = match self.device_id {
+            Some(device_id) if device_id.is_empty() => {
+                return Err(EmptyField("device_id".to_string()));
+            }
+            Some(device_id) => device_id,
+            None => DEFAULT_DEVICE_ID.to_string(),
+        };
+
+        let apns_topic = match self.apns_topic {
+            Some(topic) if topic.is_empty() => {
+                return Err(EmptyField("apns_topic".to_string()));
+            }
+            Some(topic)
+                if !matches!(push_type, ProviderKind::Apns | ProviderKind::ApnsSandbox)
+                    || !tenant.has_apns_topic(&topic) =>
+            {
+                return Err(InvalidApnsTopic(topic));
+            }
+            topic => topic,
+        };
+
+        let fcm_v1_slot = match self.fcm_v1_slot {
+            Some(slot) if slot.is_empty() => {
+                return Err(EmptyField("fcm_v1_slot".to_string()));
+            }
+            Some(slot) if push_type != ProviderKind::Fcm || !tenant.has_fcm_v1_slot(&slot) => {
+                return Err(InvalidFcmV1Slot(slot));
+            }
+            // Clients of the default slot are stored without one
+            Some(slot) if slot == DEFAULT_FCM_V1_SLOT => None,
+            slot => slot,
+        };
+
+        let client_id = self
+            .client_id
+            .as_ref()
+            .trim_start_matches(DECENTRALIZED_IDENTIFIER_PREFIX)
+            .to_owned();
+
+        Ok((
+            client_id,
+            Client {
+                tenant_id: tenant.id.clone(),
+                push_type,
+                token: self.token,
+                always_raw: self.always_raw.unwrap_or(false),
+                device_id,
+                apns_topic,
+                fcm_v1_slot,
+            },
+        ))
+    }
 }
 
 #[instrument(skip_all, name = "register_client_handler")]
@@ -67,48 +155,26 @@ pub async fn handler(
         return Err(InvalidAuthentication);
     }
 
-    let push_type = body.push_type.as_str().try_into()?;
     let tenant = state.tenant_store.get_tenant(&tenant_id).await?;
-    let supported_providers = tenant.providers();
-    if !supported_providers.contains(&push_type) {
-        return Err(ProviderNotAvailable(push_type.into()));
-    }
-
-    if body.token.is_empty() {
-        return Err(EmptyField("token".to_string()));
-    }
-
-    let client_id = body
-        .client_id
-        .as_ref()
-        .trim_start_matches(DECENTRALIZED_IDENTIFIER_PREFIX)
-        .to_owned();
+    let (client_id, client) = body.into_client(&tenant, &tenant.provider_kinds())?;
 
-    let always_raw = body.always_raw.unwrap_or(false);
+    let push_type = client.push_type;
+    let always_raw = client.always_raw;
+    let device_id = client.device_id.clone();
     state
         .client_store
-        .create_client(
-            &tenant_id,
-            &client_id,
-            Client {
-                tenant_id: tenant_id.clone(),
-                push_type,
-                token: body.token,
-                always_raw,
-            },
-            state.metrics.as_ref(),
-        )
+        .create_client(&tenant_id, &client_id, client, state.metrics.as_ref())
         .await?;
 
     debug!(
-        %tenant_id, %client_id, %push_type, "registered client"
+        %tenant_id, %client_id, %device_id, %push_type, "registered client"
     );
 
     increment_counter!(state.metrics, registered_clients);
 
     // Analytics
     #[cfg(feature = "analytics")]
-    tokio::spawn(async move {
+    state.tasks.clone().spawn(async move {
         if let Some(analytics) = &state.analytics {
             let (country, continent, region) = analytics
                 .lookup_geo_data(client_ip)
@@ -129,7 +195,7 @@ pub async fn handler(
                 continent,
                 project_id: tenant_id.into(),
                 client_id: client_id.into(),
-                push_provider: body.push_type.as_str().into(),
+                push_provider: push_type.as_str().into(),
                 always_raw,
                 registered_at: wc::analytics::time::now(),
             };
@@ -0,0 +1,159 @@
+use {
+    crate::{
+        error::Error,
+        handlers::{register_client::RegisterBody, validate_tenant_request},
+        log::prelude::*,
+        state::AppState,
+    },
+    axum::{
+        body::Bytes,
+        extract::{Path, State},
+        http::{header::CONTENT_TYPE, HeaderMap},
+        Json,
+    },
+    serde::{Deserialize, Serialize},
+    std::sync::Arc,
+    tracing::instrument,
+};
+
+/// Maximum number of clients accepted in a single bulk registration
+pub const MAX_BULK_CLIENTS: usize = 10_000;
+
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BulkRegisterItemResult {
+    /// Position of the item in the request body
+    pub index: usize,
+    pub client_id: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BulkRegisterResponse {
+    pub registered: usize,
+    pub failed: usize,
+    pub results: Vec<BulkRegisterItemResult>,
+}
+
+#[instrument(skip_all, name = "register_clients_bulk_handler")]
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Path(tenant_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<BulkRegisterResponse>, Error> {
+    // JWT token verification
+    #[cfg(feature = "cloud")]
+    let jwt_verification_result =
+        validate_tenant_request(&state.jwt_validation_client, &headers, &tenant_id).await;
+
+    #[cfg(not(feature = "cloud"))]
+    let jwt_verification_result = validate_tenant_request(&state.jwt_validation_client, &headers);
+
+    if let Err(e) = jwt_verification_result {
+        error!(
+            %tenant_id,
+            err = ?e,
+            "JWT verification failed"
+        );
+        return Err(e);
+    }
+
+    let items = parse_items(&headers, &body)?;
+    if items.len() > MAX_BULK_CLIENTS {
+        return Err(Error::BulkLimitExceeded(items.len(), MAX_BULK_CLIENTS));
+    }
+
+    let tenant = state.tenant_store.get_tenant(&tenant_id).await?;
+    let supported_providers = tenant.provider_kinds();
+
+    let mut results = Vec::with_capacity(items.len());
+    // Index into `results` for every client handed to the store
+    let mut pending = vec![];
+    let mut clients = vec![];
+    for (index, item) in items.into_iter().enumerate() {
+        let validated = item.and_then(|body| {
+            body.into_client(&tenant, &supported_providers)
+                .map_err(|e| e.to_string())
+        });
+
+        match validated {
+            Ok((client_id, client)) => {
+                results.push(BulkRegisterItemResult {
+                    index,
+                    client_id: Some(client_id.clone()),
+                    success: false,
+                    error: None,
+                });
+                pending.push(index);
+                clients.push((client_id, client));
+            }
+            Err(error) => results.push(BulkRegisterItemResult {
+                index,
+                client_id: None,
+                success: false,
+                error: Some(error),
+            }),
+        }
+    }
+
+    let outcomes = state
+        .client_store
+        .create_clients(&tenant_id, clients, state.metrics.as_ref())
+        .await?;
+    for (index, outcome) in pending.into_iter().zip(outcomes) {
+        match outcome {
+            Ok(()) => results[index].success = true,
+            Err(e) => results[index].error = Some(e.to_string()),
+        }
+    }
+
+    let registered = results.iter().filter(|result| result.success).count();
+    let failed = results.len() - registered;
+
+    debug!(%tenant_id, %registered, %failed, "bulk registered clients");
+
+    // Analytics are not emitted for bulk registrations as the request comes from
+    // the tenant's backend so the client IP and geo data would be meaningless
+    if let Some(metrics) = &state.metrics {
+        metrics.registered_clients.add(registered as u64, &[]);
+    }
+
+    Ok(Json(BulkRegisterResponse {
+        registered,
+        failed,
+        results,
+    }))
+}
+
+/// Parses the body as NDJSON when requested by the `Content-Type` header and
+/// as a JSON array otherwise. Items that fail to deserialize are returned as
+/// per-item errors rather than failing the whole request
+fn parse_items(
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Vec<Result<RegisterBody, String>>, Error> {
+    let is_ndjson = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with(NDJSON_CONTENT_TYPE))
+        .unwrap_or(false);
+
+    if is_ndjson {
+        let body = std::str::from_utf8(body).map_err(|e| Error::InvalidBulkBody(e.to_string()))?;
+        return Ok(body
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| e.to_string()))
+            .collect());
+    }
+
+    let values: Vec<serde_json::Value> =
+        serde_json::from_slice(body).map_err(|e| Error::InvalidBulkBody(e.to_string()))?;
+    Ok(values
+        .into_iter()
+        .map(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+        .collect())
+}
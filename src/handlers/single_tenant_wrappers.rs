@@ -3,33 +3,31 @@ use axum_client_ip::SecureClientIp;
 use {
     crate::{
         error::Result,
-        handlers::{push_message::PushMessageBody, register_client::RegisterBody, Response},
+        handlers::{
+            delete_client::DeleteClientQuery, push_message::PushMessageBody,
+            register_client::RegisterBody, Response,
+        },
         middleware::validate_signature::RequireValidSignature,
         state::AppState,
         stores::tenant::DEFAULT_TENANT_ID,
     },
     axum::{
-        extract::{Path, State as StateExtractor},
+        extract::{Path, Query, State as StateExtractor},
         Json,
     },
     hyper::HeaderMap,
     std::sync::Arc,
 };
 
-#[cfg(feature = "multitenant")]
-use crate::error::Error::MissingTenantId;
-
 pub async fn delete_handler(
     Path(id): Path<String>,
+    query: Query<DeleteClientQuery>,
     state: StateExtractor<Arc<AppState>>,
     headers: HeaderMap,
 ) -> Result<Response> {
-    #[cfg(feature = "multitenant")]
-    return Err(MissingTenantId);
-
-    #[cfg(not(feature = "multitenant"))]
     crate::handlers::delete_client::handler(
         Path((DEFAULT_TENANT_ID.to_string(), id)),
+        query,
         state,
         headers,
     )
@@ -42,20 +40,16 @@ pub async fn push_handler(
     state: StateExtractor<Arc<AppState>>,
     valid_sig: RequireValidSignature<Json<PushMessageBody>>,
 ) -> Result<axum::response::Response> {
-    #[cfg(feature = "multitenant")]
-    return Err(MissingTenantId);
-
-    #[cfg(all(not(feature = "multitenant"), feature = "analytics"))]
+    #[cfg(feature = "analytics")]
     return crate::handlers::push_message::handler(
         SecureClientIp(client_ip),
         Path((DEFAULT_TENANT_ID.to_string(), id)),
         state,
-        headers,
         valid_sig,
     )
     .await;
 
-    #[cfg(all(not(feature = "multitenant"), not(feature = "analytics")))]
+    #[cfg(not(feature = "analytics"))]
     return crate::handlers::push_message::handler(
         Path((DEFAULT_TENANT_ID.to_string(), id)),
         state,
@@ -70,10 +64,7 @@ pub async fn register_handler(
     headers: HeaderMap,
     body: Json<RegisterBody>,
 ) -> Result<Response> {
-    #[cfg(feature = "multitenant")]
-    return Err(MissingTenantId);
-
-    #[cfg(all(not(feature = "multitenant"), feature = "analytics"))]
+    #[cfg(feature = "analytics")]
     return crate::handlers::register_client::handler(
         SecureClientIp(client_ip),
         Path(DEFAULT_TENANT_ID.to_string()),
@@ -83,7 +74,7 @@ pub async fn register_handler(
     )
     .await;
 
-    #[cfg(all(not(feature = "multitenant"), not(feature = "analytics")))]
+    #[cfg(not(feature = "analytics"))]
     return crate::handlers::register_client::handler(
         Path(DEFAULT_TENANT_ID.to_string()),
         state,
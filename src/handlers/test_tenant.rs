@@ -0,0 +1,180 @@
+use {
+    crate::{
+        error::Error,
+        handlers::{validate_tenant_request, DECENTRALIZED_IDENTIFIER_PREFIX},
+        log::prelude::*,
+        providers::{ProviderKind, ProviderResponse, PushMessage, PushProvider},
+        state::AppState,
+        stores::StoreError,
+    },
+    axum::{
+        extract::{Path, State},
+        http::HeaderMap,
+        Json,
+    },
+    serde::{Deserialize, Serialize},
+    std::sync::Arc,
+    tracing::instrument,
+};
+
+const DEFAULT_TITLE: &str = "Test notification";
+const DEFAULT_BODY: &str = "Your push credentials are working";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TestNotificationBody {
+    /// Sends to every device registered for the client
+    pub client_id: Option<String>,
+    /// Sends to a device token that doesn't need to be registered, requires
+    /// `type` to be set
+    pub token: Option<String>,
+    #[serde(rename = "type")]
+    pub push_type: Option<String>,
+    pub title: Option<String>,
+    pub body: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TestNotificationResult {
+    pub device_id: Option<String>,
+    #[serde(rename = "type")]
+    pub push_type: String,
+    /// Whether the provider accepted the notification
+    pub ok: bool,
+    pub response: Option<ProviderResponse>,
+    /// Why the notification couldn't be sent, the other devices are still
+    /// sent to
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TestNotificationResponse {
+    pub results: Vec<TestNotificationResult>,
+}
+
+/// Sends a plain notification through the tenant's credentials. Unlike
+/// `push_message` this doesn't record the notification for deduplication and
+/// doesn't delete clients or suspend the tenant when the provider rejects it,
+/// the provider's error is reported in the device's result instead
+#[instrument(skip_all, name = "test_tenant_handler")]
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<TestNotificationBody>,
+) -> Result<Json<TestNotificationResponse>, Error> {
+    // JWT token verification
+    #[cfg(feature = "cloud")]
+    let jwt_verification_result =
+        validate_tenant_request(&state.jwt_validation_client, &headers, &id).await;
+
+    #[cfg(not(feature = "cloud"))]
+    let jwt_verification_result = validate_tenant_request(&state.jwt_validation_client, &headers);
+
+    if let Err(e) = jwt_verification_result {
+        error!(
+            tenant_id = %id,
+            err = ?e,
+            "JWT verification failed"
+        );
+        return Err(e);
+    }
+
+    // (device id, push type, token, APNs topic, FCM v1 slot)
+    type Target = (
+        Option<String>,
+        ProviderKind,
+        String,
+        Option<String>,
+        Option<String>,
+    );
+    let targets: Vec<Target> = match (body.client_id, body.token) {
+        (Some(client_id), _) => {
+            let client_id = client_id
+                .trim_start_matches(DECENTRALIZED_IDENTIFIER_PREFIX)
+                .to_string();
+            match state.client_store.get_client_devices(&id, &client_id).await {
+                Ok(devices) => devices
+                    .into_iter()
+                    .map(|device| {
+                        (
+                            Some(device.device_id),
+                            device.push_type,
+                            device.token,
+                            device.apns_topic,
+                            device.fcm_v1_slot,
+                        )
+                    })
+                    .collect(),
+                Err(StoreError::NotFound(_, _)) => return Err(Error::ClientNotFound),
+                Err(e) => return Err(Error::Store(e)),
+            }
+        }
+        (None, Some(token)) => {
+            if token.is_empty() {
+                return Err(Error::EmptyField("token".to_string()));
+            }
+            let push_type = body
+                .push_type
+                .ok_or_else(|| Error::EmptyField("type".to_string()))?;
+            vec![(None, push_type.as_str().try_into()?, token, None, None)]
+        }
+        (None, None) => return Err(Error::EmptyField("client_id".to_string())),
+    };
+
+    let tenant = state.tenant_store.get_tenant(&id).await?;
+    let title = body.title.as_deref().unwrap_or(DEFAULT_TITLE);
+    let message_body = body.body.as_deref().unwrap_or(DEFAULT_BODY);
+
+    let mut results = Vec::with_capacity(targets.len());
+    for (device_id, push_type, token, apns_topic, fcm_v1_slot) in targets {
+        let send_res = async {
+            let provider = tenant
+                .provider(
+                    &push_type,
+                    fcm_v1_slot.as_deref(),
+                    state.http_client.clone(),
+                    &state.provider_cache,
+                )
+                .await?
+                .with_apns_topic(apns_topic.as_deref());
+            provider
+                .send_notification(token, PushMessage::plain(title, message_body)?)
+                .await
+        }
+        .await;
+
+        let result = match send_res {
+            Ok(response) => {
+                info!(
+                    tenant_id = %id,
+                    push_type = push_type.as_str(),
+                    "sent test notification"
+                );
+                TestNotificationResult {
+                    device_id,
+                    push_type: push_type.into(),
+                    ok: true,
+                    response: Some(response),
+                    error: None,
+                }
+            }
+            Err(e) => {
+                warn!(
+                    tenant_id = %id,
+                    push_type = push_type.as_str(),
+                    "test notification failed: {e:?}"
+                );
+                TestNotificationResult {
+                    device_id,
+                    push_type: push_type.into(),
+                    ok: false,
+                    response: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+        results.push(result);
+    }
+
+    Ok(Json(TestNotificationResponse { results }))
+}
@@ -1,24 +1,33 @@
 use {
     crate::{
-        error::{Error, Error::InvalidMultipartBody},
-        handlers::validate_tenant_request,
+        error::{
+            Error,
+            Error::{InvalidApnsUpdateBody, InvalidMultipartBody},
+        },
+        handlers::{emit_credentials_updated, validate_tenant_request, ErrorField, ErrorLocation},
         increment_counter,
         state::AppState,
-        stores::tenant::{TenantApnsUpdateAuth, TenantApnsUpdateParams},
+        stores::tenant::{CredentialsKind, TenantApnsUpdateAuth, TenantApnsUpdateParams},
     },
     a2::ClientConfig,
     axum::{
-        extract::{Multipart, Path, State},
-        http::HeaderMap,
+        extract::{FromRequest, Multipart, Path, Request, State},
+        http::{header::CONTENT_TYPE, HeaderMap},
         Json,
     },
-    base64::Engine,
+    base64::{engine::general_purpose::STANDARD, Engine},
+    chrono::{DateTime, Utc},
+    openssl::{asn1::Asn1Time, pkcs12::Pkcs12},
     serde::{Deserialize, Serialize},
     std::sync::Arc,
     tracing::{error, instrument, warn},
 };
 
-#[derive(Deserialize)]
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Body of an APNs update, either `multipart/form-data` with the certificate
+/// or key as file parts, or JSON with them base64 encoded
+#[derive(Deserialize, Default, Debug)]
 pub struct ApnsUpdateBody {
     pub apns_topic: Option<String>,
 
@@ -38,13 +47,48 @@ pub struct ApnsSqlUpdate {
 }
 
 impl ApnsUpdateBody {
+    /// Reads the body from the form, file parts are base64 encoded
+    pub async fn from_multipart(mut form_body: Multipart) -> Result<Self, Error> {
+        let mut body = ApnsUpdateBody::default();
+        while let Some(field) = form_body.next_field().await? {
+            let name = field.name().unwrap_or("unknown").to_string();
+
+            // Check the lowercase name against list of known names for struct
+            match name.to_lowercase().as_str() {
+                "apns_topic" => {
+                    body.apns_topic = Some(field.text().await?);
+                }
+                "apns_certificate" => {
+                    let data = field.bytes().await?;
+                    body.apns_certificate = Some(STANDARD.encode(&data));
+                }
+                "apns_certificate_password" => {
+                    body.apns_certificate_password = Some(field.text().await?);
+                }
+                "apns_pkcs8_pem" => {
+                    let data = field.bytes().await?;
+                    body.apns_pkcs8_pem = Some(STANDARD.encode(&data));
+                }
+                "apns_key_id" => {
+                    body.apns_key_id = Some(field.text().await?);
+                }
+                "apns_team_id" => {
+                    body.apns_team_id = Some(field.text().await?);
+                }
+                _ => {
+                    // Unknown field, ignored
+                }
+            };
+        }
+
+        Ok(body)
+    }
+
     pub fn validate(&self) -> Result<ApnsSqlUpdate, Error> {
-        // Match cases when the input is not valid and return false.
         // Input is valid if certificate and certificate_password is included for
         // updates. topic is required for new tenants
-
-        match (
-            &self.apns_topic,
+        let topic = self.apns_topic.clone();
+        let auth = match (
             &self.apns_certificate,
             &self.apns_certificate_password,
             &self.apns_pkcs8_pem,
@@ -52,66 +96,179 @@ impl ApnsUpdateBody {
             &self.apns_team_id,
         ) {
             // Update Topic
-            (Some(topic), None, None, None, None, None) => Ok(ApnsSqlUpdate {
-                topic: Some(topic.clone()),
-                auth: None,
-            }),
+            (None, None, None, None, None) if topic.is_some() => None,
             // Update Certificate
-            (Some(topic), Some(certificate), Some(password), None, None, None) => {
-                Ok(ApnsSqlUpdate {
-                    topic: Some(topic.clone()),
-                    auth: Some(TenantApnsUpdateAuth::Certificate {
-                        apns_certificate: certificate.clone(),
-                        apns_certificate_password: password.clone(),
-                    }),
-                })
-            }
-            (Some(topic), Some(certificate), None, None, None, None) => Ok(ApnsSqlUpdate {
-                topic: Some(topic.clone()),
-                auth: Some(TenantApnsUpdateAuth::Certificate {
-                    apns_certificate: certificate.clone(),
-                    apns_certificate_password: String::new(),
-                }),
-            }),
-            (None, Some(certificate), Some(password), None, None, None) => Ok(ApnsSqlUpdate {
-                topic: None,
-                auth: Some(TenantApnsUpdateAuth::Certificate {
-                    apns_certificate: certificate.clone(),
-                    apns_certificate_password: password.clone(),
-                }),
-            }),
-            (None, Some(certificate), None, None, None, None) => Ok(ApnsSqlUpdate {
-                topic: None,
-                auth: Some(TenantApnsUpdateAuth::Certificate {
+            (Some(certificate), password, None, None, None) => {
+                Some(TenantApnsUpdateAuth::Certificate {
                     apns_certificate: certificate.clone(),
-                    apns_certificate_password: String::new(),
-                }),
-            }),
-            // Update Token
-            (Some(topic), None, None, Some(pkcs8_pem), Some(key_id), Some(team_id)) => {
-                Ok(ApnsSqlUpdate {
-                    topic: Some(topic.clone()),
-                    auth: Some(TenantApnsUpdateAuth::Token {
-                        apns_pkcs8_pem: pkcs8_pem.clone(),
-                        apns_key_id: key_id.clone(),
-                        apns_team_id: team_id.clone(),
-                    }),
+                    apns_certificate_password: password.clone().unwrap_or_default(),
                 })
             }
-            (None, None, None, Some(pkcs8_pem), Some(key_id), Some(team_id)) => Ok(ApnsSqlUpdate {
-                topic: None,
-                auth: Some(TenantApnsUpdateAuth::Token {
+            // Update Token
+            (None, None, Some(pkcs8_pem), Some(key_id), Some(team_id)) => {
+                Some(TenantApnsUpdateAuth::Token {
                     apns_pkcs8_pem: pkcs8_pem.clone(),
                     apns_key_id: key_id.clone(),
                     apns_team_id: team_id.clone(),
-                }),
-            }),
+                })
+            }
             // All other cases are invalid
-            _ => Err(InvalidMultipartBody),
+            _ => return Err(InvalidApnsUpdateBody(self.invalid_fields())),
+        };
+
+        let mut invalid_fields = vec![];
+        if matches!(&self.apns_topic, Some(topic) if topic.is_empty()) {
+            invalid_fields.push(body_field("apns_topic", "Must not be empty"));
+        }
+        for (field, value) in [
+            ("apns_certificate", &self.apns_certificate),
+            ("apns_pkcs8_pem", &self.apns_pkcs8_pem),
+        ] {
+            if matches!(value, Some(value) if STANDARD.decode(value).is_err()) {
+                invalid_fields.push(body_field(field, "Must be base64 encoded"));
+            }
+        }
+        if !invalid_fields.is_empty() {
+            return Err(InvalidApnsUpdateBody(invalid_fields));
+        }
+
+        Ok(ApnsSqlUpdate { topic, auth })
+    }
+
+    /// Explains why the combination of fields isn't a valid update
+    fn invalid_fields(&self) -> Vec<ErrorField> {
+        let has_certificate =
+            self.apns_certificate.is_some() || self.apns_certificate_password.is_some();
+        let token_fields = [
+            ("apns_pkcs8_pem", &self.apns_pkcs8_pem),
+            ("apns_key_id", &self.apns_key_id),
+            ("apns_team_id", &self.apns_team_id),
+        ];
+        let has_token = token_fields.iter().any(|(_, value)| value.is_some());
+
+        if has_certificate && has_token {
+            return token_fields
+                .iter()
+                .filter(|(_, value)| value.is_some())
+                .map(|(field, _)| body_field(field, "Can't be combined with apns_certificate"))
+                .collect();
+        }
+
+        if has_certificate {
+            // Only the password was given
+            return vec![body_field(
+                "apns_certificate",
+                "Required with apns_certificate_password",
+            )];
+        }
+
+        if has_token {
+            return token_fields
+                .iter()
+                .filter(|(_, value)| value.is_none())
+                .map(|(field, _)| body_field(field, "Required for token authentication"))
+                .collect();
         }
+
+        vec![body_field(
+            "apns_topic",
+            "Required when neither a certificate nor a token is provided",
+        )]
+    }
+}
+
+fn body_field(field: &str, description: &str) -> ErrorField {
+    ErrorField {
+        field: field.to_string(),
+        description: description.to_string(),
+        location: ErrorLocation::Body,
     }
 }
 
+/// Checks the certificate or token can be used to build an APNs client
+pub fn validate_apns_auth(auth: &TenantApnsUpdateAuth) -> Result<(), Error> {
+    match auth {
+        TenantApnsUpdateAuth::Certificate {
+            apns_certificate,
+            apns_certificate_password,
+        } => {
+            let decoded = STANDARD.decode(apns_certificate)?;
+            match a2::Client::certificate(
+                &mut std::io::Cursor::new(decoded),
+                apns_certificate_password,
+                ClientConfig::new(a2::Endpoint::Sandbox),
+            ) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    warn!("Error validating APNS certificate on update: {:?}", e);
+                    Err(Error::BadApnsCredentials)
+                }
+            }
+        }
+        TenantApnsUpdateAuth::Token {
+            apns_pkcs8_pem,
+            apns_key_id,
+            apns_team_id,
+        } => {
+            let decoded = STANDARD.decode(apns_pkcs8_pem)?;
+            match a2::Client::token(
+                &mut std::io::Cursor::new(decoded),
+                apns_key_id.clone(),
+                apns_team_id.clone(),
+                ClientConfig::new(a2::Endpoint::Sandbox),
+            ) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    warn!("Error validating APNS token on update: {:?}", e);
+                    Err(Error::BadApnsCredentials)
+                }
+            }
+        }
+    }
+}
+
+/// Expiry of the certificate in the p12, `None` for token auth. Certificates
+/// that have already expired are rejected
+pub fn apns_certificate_not_after(
+    auth: &TenantApnsUpdateAuth,
+) -> Result<Option<DateTime<Utc>>, Error> {
+    let TenantApnsUpdateAuth::Certificate {
+        apns_certificate,
+        apns_certificate_password,
+    } = auth
+    else {
+        return Ok(None);
+    };
+
+    let not_after = p12_not_after(apns_certificate, apns_certificate_password)?;
+    if not_after <= Utc::now() {
+        warn!(%not_after, "Rejected expired APNS certificate on update");
+        return Err(Error::ApnsCertificateExpired);
+    }
+
+    Ok(Some(not_after))
+}
+
+/// Expiry of the certificate in the base64 encoded p12
+pub fn p12_not_after(
+    apns_certificate: &str,
+    apns_certificate_password: &str,
+) -> Result<DateTime<Utc>, Error> {
+    let decoded = STANDARD.decode(apns_certificate)?;
+    let certificate = Pkcs12::from_der(&decoded)?
+        .parse2(apns_certificate_password)?
+        .cert
+        .ok_or(Error::BadApnsCredentials)?;
+
+    // `Asn1Time` can only be converted by diffing it against another time
+    let since_epoch = Asn1Time::from_unix(0)?.diff(certificate.not_after())?;
+    DateTime::from_timestamp(
+        i64::from(since_epoch.days) * 24 * 60 * 60 + i64::from(since_epoch.secs),
+        0,
+    )
+    .ok_or(Error::BadApnsCredentials)
+}
+
 #[derive(Serialize)]
 pub struct UpdateTenantApnsResponse {
     success: bool,
@@ -122,7 +279,7 @@ pub async fn handler(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
     headers: HeaderMap,
-    mut form_body: Multipart,
+    request: Request,
 ) -> Result<Json<UpdateTenantApnsResponse>, Error> {
     // JWT verification
     #[cfg(feature = "cloud")]
@@ -144,50 +301,23 @@ pub async fn handler(
     // Ensure tenant real
     let _existing_tenant = state.tenant_store.get_tenant(&id).await?;
 
-    // ---- retrieve body from form
-    let mut body = ApnsUpdateBody {
-        apns_topic: None,
-
-        apns_certificate: None,
-        apns_certificate_password: None,
-
-        apns_pkcs8_pem: None,
-        apns_key_id: None,
-        apns_team_id: None,
+    // ---- retrieve body, JSON or form depending on the content type
+    let is_json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with(JSON_CONTENT_TYPE))
+        .unwrap_or(false);
+    let body = if is_json {
+        let Json(body) = Json::<ApnsUpdateBody>::from_request(request, &state)
+            .await
+            .map_err(|e| InvalidApnsUpdateBody(vec![body_field("body", &e.body_text())]))?;
+        body
+    } else {
+        let form_body = Multipart::from_request(request, &state)
+            .await
+            .map_err(|_| InvalidMultipartBody)?;
+        ApnsUpdateBody::from_multipart(form_body).await?
     };
-    while let Some(field) = form_body.next_field().await? {
-        let name = field.name().unwrap_or("unknown").to_string();
-
-        // Check the lowercase name against list of known names for struct
-        match name.to_lowercase().as_str() {
-            "apns_topic" => {
-                body.apns_topic = Some(field.text().await?);
-            }
-            "apns_certificate" => {
-                let data = field.bytes().await?;
-                let encoded_certificate = base64::engine::general_purpose::STANDARD.encode(&data);
-                body.apns_certificate = Some(encoded_certificate);
-            }
-            "apns_certificate_password" => {
-                body.apns_certificate_password = Some(field.text().await?);
-            }
-            "apns_pkcs8_pem" => {
-                let data = field.bytes().await?;
-                let encoded_p8_certificate =
-                    base64::engine::general_purpose::STANDARD.encode(&data);
-                body.apns_pkcs8_pem = Some(encoded_p8_certificate);
-            }
-            "apns_key_id" => {
-                body.apns_key_id = Some(field.text().await?);
-            }
-            "apns_team_id" => {
-                body.apns_team_id = Some(field.text().await?);
-            }
-            _ => {
-                // Unknown field, ignored
-            }
-        };
-    }
 
     let apns_updates = body.validate()?;
 
@@ -205,69 +335,46 @@ pub async fn handler(
 
             increment_counter!(state.metrics, tenant_apns_updates);
 
+            emit_credentials_updated(&state, &id, CredentialsKind::Apns, false, false).await;
+
             return Ok(Json(UpdateTenantApnsResponse { success: true }));
         }
     }
 
     // ---- Checks
-    if let Some(auth_change) = apns_updates.auth.clone() {
-        match auth_change {
-            TenantApnsUpdateAuth::Certificate {
-                apns_certificate,
-                apns_certificate_password,
-            } => {
-                let decoded = base64::engine::general_purpose::STANDARD
-                    .decode(apns_certificate.into_bytes())?;
-                match a2::Client::certificate(
-                    &mut std::io::Cursor::new(decoded),
-                    &apns_certificate_password,
-                    ClientConfig::new(a2::Endpoint::Sandbox),
-                ) {
-                    Ok(_) => Ok(()),
-                    Err(e) => {
-                        warn!("Error validating APNS certificate on update: {:?}", e);
-                        Err(Error::BadApnsCredentials)
-                    }
-                }
-            }
-            TenantApnsUpdateAuth::Token {
-                apns_pkcs8_pem,
-                apns_key_id,
-                apns_team_id,
-            } => {
-                let decoded = base64::engine::general_purpose::STANDARD
-                    .decode(apns_pkcs8_pem.into_bytes())?;
-                match a2::Client::token(
-                    &mut std::io::Cursor::new(decoded),
-                    apns_key_id,
-                    apns_team_id,
-                    ClientConfig::new(a2::Endpoint::Sandbox),
-                ) {
-                    Ok(_) => Ok(()),
-                    Err(e) => {
-                        warn!("Error validating APNS token on update: {:?}", e);
-                        Err(Error::BadApnsCredentials)
-                    }
-                }
-            }
-        }?;
+    let mut certificate_not_after = None;
+    if let Some(auth_change) = &apns_updates.auth {
+        validate_apns_auth(auth_change)?;
+        certificate_not_after = apns_certificate_not_after(auth_change)?;
     }
 
     // ---- handler
     if let Some(auth) = apns_updates.auth {
-        let new_tenant = state
+        let mut new_tenant = state
             .tenant_store
             .update_tenant_apns_auth(&id, auth)
             .await?;
+        if let Some(not_after) = certificate_not_after {
+            new_tenant = state
+                .tenant_store
+                .update_tenant_apns_certificate_expiry(&id, not_after)
+                .await?;
+        }
 
         increment_counter!(state.metrics, tenant_apns_updates);
 
-        if new_tenant.suspended {
+        let unsuspended = new_tenant.suspended_reason(CredentialsKind::Apns).is_some();
+        if unsuspended {
             // If suspended, it can be restored now because valid credentials have been
             // provided
-            state.tenant_store.unsuspend_tenant(&new_tenant.id).await?;
+            state
+                .tenant_store
+                .unsuspend_tenant_credentials(&new_tenant.id, CredentialsKind::Apns, None)
+                .await?;
         }
 
+        emit_credentials_updated(&state, &id, CredentialsKind::Apns, false, unsuspended).await;
+
         return Ok(Json(UpdateTenantApnsResponse { success: true }));
     }
 
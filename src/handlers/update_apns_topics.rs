@@ -0,0 +1,77 @@
+use {
+    crate::{
+        error::Error::{self, EmptyField},
+        handlers::{emit_credentials_updated, validate_tenant_request},
+        increment_counter,
+        state::AppState,
+        stores::tenant::CredentialsKind,
+    },
+    axum::{
+        extract::{Path, State},
+        http::HeaderMap,
+        Json,
+    },
+    serde::{Deserialize, Serialize},
+    std::sync::Arc,
+    tracing::{error, instrument},
+};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateApnsTopicsBody {
+    /// Topics (bundle ids) clients can register with in addition to the
+    /// tenant's APNs topic, replaces the current list
+    pub topics: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateApnsTopicsResponse {
+    pub topics: Vec<String>,
+}
+
+#[instrument(skip_all, name = "update_apns_topics_handler")]
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<UpdateApnsTopicsBody>,
+) -> Result<Json<UpdateApnsTopicsResponse>, Error> {
+    // JWT verification
+    #[cfg(feature = "cloud")]
+    let jwt_verification_result =
+        validate_tenant_request(&state.jwt_validation_client, &headers, &id).await;
+
+    #[cfg(not(feature = "cloud"))]
+    let jwt_verification_result = validate_tenant_request(&state.jwt_validation_client, &headers);
+
+    if let Err(e) = jwt_verification_result {
+        error!(
+            tenant_id = %id,
+            err = ?e,
+            "JWT verification failed"
+        );
+        return Err(e);
+    }
+
+    let mut topics = body.topics;
+    if topics.iter().any(|topic| topic.is_empty()) {
+        return Err(EmptyField("topics".to_string()));
+    }
+    topics.sort();
+    topics.dedup();
+
+    // Ensure tenant real
+    let _existing_tenant = state.tenant_store.get_tenant(&id).await?;
+
+    let new_tenant = state
+        .tenant_store
+        .update_tenant_apns_topics(&id, topics)
+        .await?;
+
+    increment_counter!(state.metrics, tenant_apns_updates);
+
+    emit_credentials_updated(&state, &id, CredentialsKind::Apns, false, false).await;
+
+    Ok(Json(UpdateApnsTopicsResponse {
+        topics: new_tenant.apns_topics,
+    }))
+}
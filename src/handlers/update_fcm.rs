@@ -2,22 +2,21 @@ use {
     crate::{
         error::{
             Error,
-            Error::{BadFcmApiKey, InvalidMultipartBody},
+            Error::{InvalidMultipartBody, LegacyFcmDeprecated},
         },
-        handlers::validate_tenant_request,
+        handlers::{emit_credentials_updated, validate_tenant_request},
         increment_counter,
         state::AppState,
-        stores::tenant::TenantFcmUpdateParams,
+        stores::tenant::{CredentialsKind, TenantFcmUpdateParams},
     },
     axum::{
         extract::{Multipart, Path, State},
         http::HeaderMap,
         Json,
     },
-    fcm::FcmError,
     serde::Serialize,
     std::sync::Arc,
-    tracing::{error, instrument},
+    tracing::{error, instrument, warn},
 };
 
 pub struct FcmUpdateBody {
@@ -31,6 +30,24 @@ pub struct UpdateTenantFcmResponse {
     success: bool,
 }
 
+/// Sends a dry run message to check the API key is accepted
+#[cfg(feature = "legacy_fcm")]
+pub async fn validate_fcm_api_key(fcm_api_key: &str) -> Result<(), Error> {
+    let mut test_message_builder = fcm::MessageBuilder::new(fcm_api_key, "wc-notification-test");
+    test_message_builder.dry_run(true);
+    let test_message = test_message_builder.finalize();
+    match fcm::Client::new().send(test_message).await {
+        Err(fcm::FcmError::Unauthorized) => Err(Error::BadFcmApiKey),
+        _ => Ok(()),
+    }
+}
+
+/// API keys can't be validated without the legacy FCM client
+#[cfg(not(feature = "legacy_fcm"))]
+pub async fn validate_fcm_api_key(_fcm_api_key: &str) -> Result<(), Error> {
+    Err(LegacyFcmDeprecated)
+}
+
 #[instrument(skip_all, name = "update_fcm_handler")]
 pub async fn handler(
     State(state): State<Arc<AppState>>,
@@ -55,6 +72,11 @@ pub async fn handler(
         return Err(e);
     }
 
+    if state.config.legacy_fcm_deprecated {
+        warn!(tenant_id = %id, "rejected legacy FCM API key upload");
+        return Err(LegacyFcmDeprecated);
+    }
+
     // -- check if tenant is real
     let _existing_tenant = state.tenant_store.get_tenant(&id).await?;
 
@@ -77,18 +99,7 @@ pub async fn handler(
     }
 
     // ---- checks
-    let fcm_api_key = body.api_key.clone();
-    let mut test_message_builder = fcm::MessageBuilder::new(&fcm_api_key, "wc-notification-test");
-    test_message_builder.dry_run(true);
-    let test_message = test_message_builder.finalize();
-    let test_notification = fcm::Client::new().send(test_message).await;
-    match test_notification {
-        Err(e) => match e {
-            FcmError::Unauthorized => Err(BadFcmApiKey),
-            _ => Ok(()),
-        },
-        Ok(_) => Ok(()),
-    }?;
+    validate_fcm_api_key(&body.api_key).await?;
 
     // ---- handler
     let update_body = TenantFcmUpdateParams {
@@ -100,12 +111,18 @@ pub async fn handler(
         .update_tenant_fcm(&id, update_body)
         .await?;
 
-    if new_tenant.suspended {
+    let unsuspended = new_tenant.suspended_reason(CredentialsKind::Fcm).is_some();
+    if unsuspended {
         // If suspended, it can be restored now because valid credentials have been
         // provided
-        state.tenant_store.unsuspend_tenant(&new_tenant.id).await?;
+        state
+            .tenant_store
+            .unsuspend_tenant_credentials(&new_tenant.id, CredentialsKind::Fcm, None)
+            .await?;
     }
 
+    emit_credentials_updated(&state, &id, CredentialsKind::Fcm, false, unsuspended).await;
+
     increment_counter!(state.metrics, tenant_fcm_updates);
 
     Ok(Json(UpdateTenantFcmResponse { success: true }))
@@ -1,17 +1,20 @@
 use {
     crate::{
         error::{Error, Error::InvalidMultipartBody},
-        handlers::validate_tenant_request,
+        handlers::{emit_credentials_updated, validate_tenant_request},
         increment_counter,
+        providers::fcm_v1_validation::{self, FcmV1Endpoints},
         state::AppState,
-        stores::tenant::TenantFcmV1UpdateParams,
+        stores::tenant::{
+            validate_fcm_v1_slot, CredentialsKind, TenantFcmV1UpdateParams, DEFAULT_FCM_V1_SLOT,
+        },
     },
     axum::{
         extract::{Multipart, Path, State},
         http::HeaderMap,
         Json,
     },
-    serde::Serialize,
+    serde::{Deserialize, Serialize},
     std::sync::Arc,
     tracing::{debug, error, instrument},
 };
@@ -22,25 +25,64 @@ pub struct FcmV1UpdateBody {
     value_changed_: bool,
 }
 
+/// Path of `/:id/fcm_v1` and `/:id/fcm_v1/:slot`, the former manages the
+/// default slot
+#[derive(Deserialize)]
+pub struct FcmV1Path {
+    pub id: String,
+    pub slot: Option<String>,
+}
+
+impl FcmV1Path {
+    /// Name of the slot, `None` for the default slot
+    pub fn named_slot(&self) -> Result<Option<&str>, Error> {
+        match self.slot.as_deref() {
+            None | Some(DEFAULT_FCM_V1_SLOT) => Ok(None),
+            Some(slot) => {
+                validate_fcm_v1_slot(slot)?;
+                Ok(Some(slot))
+            }
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct UpdateTenantFcmV1Response {
     success: bool,
 }
 
+/// Checks the service account key can get an access token and that FCM
+/// accepts a `validate_only` message sent with it
+pub async fn validate_fcm_v1_credentials(
+    http_client: &reqwest::Client,
+    endpoints: &FcmV1Endpoints,
+    credentials: &str,
+) -> Result<(), Error> {
+    fcm_v1_validation::validate_credentials(http_client, endpoints, credentials)
+        .await
+        .map_err(|e| {
+            debug!("Failed credential validation: {e}");
+            e
+        })
+}
+
 #[instrument(skip_all, name = "update_fcm_v1_handler")]
 pub async fn handler(
     State(state): State<Arc<AppState>>,
-    Path(id): Path<String>,
+    Path(path): Path<FcmV1Path>,
     headers: HeaderMap,
     mut form_body: Multipart,
 ) -> Result<Json<UpdateTenantFcmV1Response>, Error> {
+    let id = path.id.as_str();
+    let slot = path.named_slot()?;
+
     // JWT token verification
     #[cfg(feature = "cloud")]
     let jwt_verification_result =
-        validate_tenant_request(&state.jwt_validation_client, &headers, &id).await;
+        validate_tenant_request(&state.jwt_validation_client, &headers, id).await;
 
     // -- check if tenant is real
-    let _existing_tenant = state.tenant_store.get_tenant(&id).await?;
+    let _existing_tenant = state.tenant_store.get_tenant(id).await?;
 
     #[cfg(not(feature = "cloud"))]
     let jwt_verification_result = validate_tenant_request(&state.jwt_validation_client, &headers);
@@ -72,32 +114,48 @@ pub async fn handler(
         return Err(InvalidMultipartBody);
     }
 
-    // Client will validate the key on startup
-    fcm_v1::Client::from_key(
-        serde_json::from_str(&body.credentials).map_err(Error::FcmV1InvalidServiceAccountKey)?,
+    validate_fcm_v1_credentials(
+        &state.http_client,
+        &state.config.fcm_v1_endpoints(),
+        &body.credentials,
     )
-    .await
-    .map_err(|e| {
-        debug!("Failed credential validation: {e}");
-        Error::BadFcmV1Credentials
-    })?;
+    .await?;
 
     // ---- handler
     let update_body = TenantFcmV1UpdateParams {
         fcm_v1_credentials: body.credentials,
     };
 
-    let new_tenant = state
-        .tenant_store
-        .update_tenant_fcm_v1(&id, update_body)
-        .await?;
+    let new_tenant = match slot {
+        Some(slot) => {
+            state
+                .tenant_store
+                .update_tenant_fcm_v1_slot(id, slot, update_body)
+                .await?
+        }
+        None => {
+            state
+                .tenant_store
+                .update_tenant_fcm_v1(id, update_body)
+                .await?
+        }
+    };
 
-    if new_tenant.suspended {
+    // Only the slot's status is lifted, other slots keep their own
+    let unsuspended = new_tenant
+        .slot_suspended_reason(CredentialsKind::FcmV1, slot)
+        .is_some();
+    if unsuspended {
         // If suspended, it can be restored now because valid credentials have been
         // provided
-        state.tenant_store.unsuspend_tenant(&new_tenant.id).await?;
+        state
+            .tenant_store
+            .unsuspend_tenant_credentials(&new_tenant.id, CredentialsKind::FcmV1, slot)
+            .await?;
     }
 
+    emit_credentials_updated(&state, id, CredentialsKind::FcmV1, false, unsuspended).await;
+
     increment_counter!(state.metrics, tenant_fcm_v1_updates);
 
     Ok(Json(UpdateTenantFcmV1Response { success: true }))
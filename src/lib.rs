@@ -8,10 +8,10 @@ use {
     wc::geoip::MaxMindResolver,
 };
 use {
-    crate::{log::prelude::*, state::TenantStoreArc},
+    crate::{log::prelude::*, state::TenantStoreArc, stores::tenant::DefaultTenantStore},
     axum::{
         extract::Request,
-        routing::{delete, get, post},
+        routing::{delete, get, post, put},
         Router,
     },
     axum_client_ip::SecureClientIpSource,
@@ -20,10 +20,16 @@ use {
     middleware::rate_limit::rate_limit_middleware,
     sqlx::{
         postgres::{PgConnectOptions, PgPoolOptions},
-        ConnectOptions,
+        ConnectOptions, PgPool,
     },
-    std::{future::IntoFuture, net::SocketAddr, str::FromStr, sync::Arc, time::Duration},
-    tokio::{net::TcpListener, select, sync::broadcast},
+    std::{
+        net::SocketAddr,
+        str::FromStr,
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    },
+    tokio::{net::TcpListener, select, sync::broadcast, task::JoinSet},
+    tokio_util::sync::CancellationToken,
     tower::ServiceBuilder,
     tower_http::{
         catch_panic::CatchPanicLayer,
@@ -35,11 +41,10 @@ use {
     tracing::{info, log::LevelFilter},
 };
 
-#[cfg(not(feature = "multitenant"))]
-use crate::stores::tenant::DefaultTenantStore;
-
+pub mod admin;
 #[cfg(feature = "analytics")]
 pub mod analytics;
+pub mod apns_certificate_expiry;
 
 #[cfg(not(feature = "analytics"))]
 pub mod analytics {
@@ -50,7 +55,9 @@ pub mod analytics {
 }
 
 pub mod blob;
+pub mod cli;
 pub mod config;
+pub mod credentials_reload;
 pub mod error;
 pub mod handlers;
 pub mod jwt_validation;
@@ -63,41 +70,43 @@ pub mod providers;
 pub mod relay;
 pub mod state;
 pub mod stores;
+pub mod tenant_archive;
+pub mod tenant_deletion;
+pub mod webhooks;
 
 const PG_CONNECTION_POOL_SIZE: u32 = 100;
-
-pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) -> error::Result<()> {
-    // Check config is valid and then throw the error if its not
-    config.is_valid()?;
-
-    let pg_options = PgConnectOptions::from_str(&config.database_url)?
+/// Request body limit for bulk client registrations
+const BULK_BODY_LIMIT: usize = 16 * 1024 * 1024;
+/// Request body limit for tenant archive imports
+const ARCHIVE_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Opens a connection pool to the database, migrations are not run
+pub async fn connect_database(database_url: &str) -> error::Result<PgPool> {
+    let pg_options = PgConnectOptions::from_str(database_url)?
         .log_statements(LevelFilter::Trace)
         .log_slow_statements(LevelFilter::Info, Duration::from_millis(250))
         .clone();
 
-    let store = PgPoolOptions::new()
+    let pool = PgPoolOptions::new()
         .max_connections(PG_CONNECTION_POOL_SIZE)
         .connect_with(pg_options)
         .await?;
 
+    Ok(pool)
+}
+
+pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) -> error::Result<()> {
+    // Check config is valid and then throw the error if its not
+    config.is_valid()?;
+
+    let store = connect_database(&config.database_url).await?;
+
     // Run database migrations. `./migrations` is the path to migrations, relative
     // to the root dir (the directory containing `Cargo.toml`).
     sqlx::migrate!("./migrations").run(&store).await?;
 
-    #[cfg(not(feature = "multitenant"))]
-    let tenant_store: TenantStoreArc = Arc::new(DefaultTenantStore::new(Arc::new(config.clone()))?);
-
-    #[cfg(feature = "multitenant")]
-    let tenant_store: TenantStoreArc = {
-        let tenant_pg_options = PgConnectOptions::from_str(&config.tenant_database_url)?
-            .log_statements(LevelFilter::Trace)
-            .log_slow_statements(LevelFilter::Info, Duration::from_millis(250))
-            .clone();
-
-        let tenant_database = PgPoolOptions::new()
-            .max_connections(PG_CONNECTION_POOL_SIZE)
-            .connect_with(tenant_pg_options)
-            .await?;
+    let tenant_database = if config.is_multitenant() {
+        let tenant_database = connect_database(&config.tenant_database_url).await?;
 
         // Run database migrations. `./tenant_migrations` is the path to migrations,
         // relative to the root dir (the directory containing `Cargo.toml`).
@@ -105,7 +114,17 @@ pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) ->
             .run(&tenant_database)
             .await?;
 
-        Arc::new(tenant_database)
+        Some(tenant_database)
+    } else {
+        None
+    };
+
+    let (tenant_store, default_tenant_store): (TenantStoreArc, _) = match &tenant_database {
+        Some(tenant_database) => (Arc::new(tenant_database.clone()), None),
+        None => {
+            let store = Arc::new(DefaultTenantStore::new(Arc::new(config.clone()))?);
+            (store.clone(), Some(store))
+        }
     };
 
     let mut state = state::new_state(
@@ -115,19 +134,32 @@ pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) ->
         tenant_store,
     )?;
 
+    state.database = Some(store.clone());
+    state.tenant_database.clone_from(&tenant_database);
+    state.webhooks =
+        tenant_database.map(|tenant_database| webhooks::Webhooks::new(Arc::new(tenant_database)));
+
     #[cfg(any(feature = "analytics", feature = "geoblock"))]
     {
-        let s3_client = get_s3_client(&state.config).await;
-        let geoip_resolver = get_geoip_resolver(&state.config, &s3_client).await;
+        // The AWS config is only loaded when something is read from or written to S3
+        let s3_client = if uses_s3(&state.config) {
+            Some(get_s3_client(&state.config).await)
+        } else {
+            None
+        };
+        let geoip_resolver = match &s3_client {
+            Some(s3_client) => get_geoip_resolver(&state.config, s3_client).await,
+            None => None,
+        };
 
         #[cfg(feature = "analytics")]
         {
-            if let Some(ip) = state.public_ip {
-                let analytics =
-                    analytics::initialize(&state.config, s3_client, ip, geoip_resolver.clone())
-                        .await;
-                state.analytics = Some(analytics);
-            }
+            state.analytics = analytics::initialize(
+                &state.config,
+                s3_client,
+                state.public_ip,
+                geoip_resolver.clone(),
+            );
         }
 
         #[cfg(feature = "geoblock")]
@@ -142,20 +174,25 @@ pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) ->
         }
     }
 
-    #[cfg(feature = "multitenant")]
-    let supported_providers_string = "multi-tenant".to_string();
-
-    #[cfg(not(feature = "multitenant"))]
-    let supported_providers_string = state
-        .config
-        .single_tenant_supported_providers()
-        .into_iter()
-        .map(Into::into)
-        .collect::<Vec<&str>>()
-        .join(", ");
+    let supported_providers_string = if state.config.is_multitenant() {
+        "multi-tenant".to_string()
+    } else {
+        state
+            .config
+            .single_tenant_supported_providers()
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<&str>>()
+            .join(", ")
+    };
 
     if state.config.telemetry_prometheus_port.is_some() {
-        state.set_metrics(metrics::Metrics::new());
+        let metrics = if state.config.metrics_tenant_labels.is_empty() {
+            metrics::Metrics::new()
+        } else {
+            metrics::Metrics::new().with_tenant_labels(state.config.metrics_tenant_labels.clone())
+        };
+        state.set_metrics(metrics);
     }
 
     let port = state.config.port;
@@ -186,7 +223,9 @@ pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) ->
                         String::new()
                     }
                 };
-                tracing::info_span!("http-request", "method" = ?request.method(), "request_id" = ?request_id, "uri" = ?request.uri())
+                let span = tracing::info_span!("http-request", "method" = ?request.method(), "request_id" = %request_id, "uri" = ?request.uri());
+                log::set_remote_parent(&span, request.headers());
+                span
             })
         )
         .layer(CatchPanicLayer::new())
@@ -202,10 +241,14 @@ pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) ->
         .layer(SecureClientIpSource::RightmostXForwardedFor.into_extension())
         .propagate_x_request_id();
 
-    #[cfg(feature = "multitenant")]
-    let app = {
+    let app = if state_arc.config.is_multitenant() {
         let tenancy_routes = Router::new()
             .route("/", post(handlers::create_tenant::handler))
+            .route(
+                "/import",
+                post(handlers::import_tenant::handler)
+                    .layer(axum::extract::DefaultBodyLimit::max(ARCHIVE_BODY_LIMIT)),
+            )
             .route(
                 "/:id",
                 get(handlers::get_tenant::handler).delete(handlers::delete_tenant::handler),
@@ -214,8 +257,25 @@ pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) ->
             .route("/:id/fcm", delete(handlers::delete_fcm::handler))
             .route("/:id/fcm_v1", post(handlers::update_fcm_v1::handler))
             .route("/:id/fcm_v1", delete(handlers::delete_fcm_v1::handler))
+            .route("/:id/fcm_v1/:slot", post(handlers::update_fcm_v1::handler))
+            .route(
+                "/:id/fcm_v1/:slot",
+                delete(handlers::delete_fcm_v1::handler),
+            )
             .route("/:id/apns", post(handlers::update_apns::handler))
             .route("/:id/apns", delete(handlers::delete_apns::handler))
+            .route(
+                "/:id/apns/topics",
+                put(handlers::update_apns_topics::handler),
+            )
+            .route("/:id/export", get(handlers::export_tenant::handler))
+            .route("/:id/test", post(handlers::test_tenant::handler))
+            .route("/:id/webhooks", get(handlers::get_webhooks::handler))
+            .route("/:id/webhooks", post(handlers::create_webhook::handler))
+            .route(
+                "/:id/webhooks/:webhook_id",
+                delete(handlers::delete_webhook::handler),
+            )
             .layer(
                 global_middleware.clone().layer(
                     CorsLayer::new()
@@ -234,6 +294,8 @@ pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) ->
 
         Router::new()
             .route("/health", get(handlers::health::handler))
+            .route("/health/live", get(handlers::health::live_handler))
+            .route("/health/ready", get(handlers::health::ready_handler))
             .route("/rate_limit_test", get(handlers::rate_limit_test::handler).layer(
                 axum::middleware::from_fn_with_state(state_arc.clone(), rate_limit_middleware),
             ))
@@ -246,6 +308,15 @@ pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) ->
                     axum::middleware::from_fn_with_state(state_arc.clone(), rate_limit_middleware),
                 ),
             )
+            .route(
+                "/:tenant_id/clients/bulk",
+                post(handlers::register_clients_bulk::handler)
+                    .layer(axum::extract::DefaultBodyLimit::max(BULK_BODY_LIMIT))
+                    .layer(axum::middleware::from_fn_with_state(
+                        state_arc.clone(),
+                        rate_limit_middleware,
+                    )),
+            )
             .route(
                 "/:tenant_id/clients/:id",
                 delete(handlers::delete_client::handler).layer(
@@ -258,32 +329,33 @@ pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) ->
                 post(handlers::push_message::handler),
             )
             .layer(global_middleware)
-    };
-
-    #[cfg(not(feature = "multitenant"))]
-    let app = Router::new()
-        .route("/health", get(handlers::health::handler))
-        .route("/rate_limit_test", get(handlers::rate_limit_test::handler).layer(
-            axum::middleware::from_fn_with_state(state_arc.clone(), rate_limit_middleware),
-        ))
-        .route(
-            "/clients",
-            post(handlers::single_tenant_wrappers::register_handler).layer(
-                axum::middleware::from_fn_with_state(state_arc.clone(), rate_limit_middleware),
-            ),
-        )
-        .route(
-            "/clients/:id",
-            delete(handlers::single_tenant_wrappers::delete_handler).layer(
+    } else {
+        Router::new()
+            .route("/health", get(handlers::health::handler))
+            .route("/health/live", get(handlers::health::live_handler))
+            .route("/health/ready", get(handlers::health::ready_handler))
+            .route("/rate_limit_test", get(handlers::rate_limit_test::handler).layer(
                 axum::middleware::from_fn_with_state(state_arc.clone(), rate_limit_middleware),
-            ),
-        )
-        // Rate limiting middleware is not applying to push_handler because it is used by the relay
-        .route(
-            "/clients/:id",
-            post(handlers::single_tenant_wrappers::push_handler),
-        )
-        .layer(global_middleware);
+            ))
+            .route(
+                "/clients",
+                post(handlers::single_tenant_wrappers::register_handler).layer(
+                    axum::middleware::from_fn_with_state(state_arc.clone(), rate_limit_middleware),
+                ),
+            )
+            .route(
+                "/clients/:id",
+                delete(handlers::single_tenant_wrappers::delete_handler).layer(
+                    axum::middleware::from_fn_with_state(state_arc.clone(), rate_limit_middleware),
+                ),
+            )
+            // Rate limiting middleware is not applying to push_handler because it is used by the relay
+            .route(
+                "/clients/:id",
+                post(handlers::single_tenant_wrappers::push_handler),
+            )
+            .layer(global_middleware)
+    };
 
     // If geoblock is enabled, add the geoblock middleware to the app
     let app = if let Some(geoblock) = state_arc.geoblock.clone() {
@@ -295,7 +367,7 @@ pub async fn bootstap(mut shutdown: broadcast::Receiver<()>, config: Config) ->
     let app = app.with_state(state_arc.clone());
     let private_app = Router::new()
         .route("/metrics", get(handlers::metrics::handler))
-        .with_state(state_arc);
+        .with_state(state_arc.clone());
 
     if show_header {
         let header = format!(
@@ -327,15 +399,143 @@ providers: [{}]
     let private_listener =
         TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], private_port))).await?;
 
-    select! {
-        _ = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).into_future() => info!("Server terminating"),
-        _ = axum::serve(private_listener, private_app.into_make_service()).into_future() => info!("Internal Server terminating"),
-        _ = shutdown.recv() => info!("Shutdown signal received, killing servers"),
+    let mut background_tasks = vec![];
+    if state_arc.config.is_multitenant() {
+        // Finishes tenant deletions interrupted by a restart
+        background_tasks.push(tokio::spawn(tenant_deletion::run_reconciler(
+            state_arc.clone(),
+        )));
+
+        background_tasks.push(tokio::spawn(apns_certificate_expiry::run_expiry_check(
+            state_arc.clone(),
+        )));
+    }
+
+    // Picks up rotated single-tenant credentials
+    if let Some(store) = default_tenant_store {
+        background_tasks.push(tokio::spawn(credentials_reload::run_reloader(
+            state_arc.clone(),
+            store,
+        )));
+    }
+
+    // Delivers webhooks queued by this and other instances
+    if let Some(webhooks) = state_arc.webhooks.clone() {
+        if state_arc.config.webhook_dispatcher_enabled {
+            background_tasks.push(tokio::spawn(webhooks.run_dispatcher()));
+        }
+    }
+
+    // Cancelled once the grace period after a shutdown signal is over
+    let stop_serving = CancellationToken::new();
+    let mut servers = JoinSet::new();
+    let public_stop = stop_serving.clone();
+    servers.spawn(async move {
+        let _ = axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(public_stop.cancelled_owned())
+        .await;
+        info!("Server terminating");
+    });
+    let private_stop = stop_serving.clone();
+    servers.spawn(async move {
+        let _ = axum::serve(private_listener, private_app.into_make_service())
+            .with_graceful_shutdown(private_stop.cancelled_owned())
+            .await;
+        info!("Internal Server terminating");
+    });
+
+    let shutdown_requested = select! {
+        _ = servers.join_next() => false,
+        _ = shutdown.recv() => true,
+    };
+
+    if shutdown_requested {
+        // The readiness probe fails from now on, while the servers keep
+        // serving until the instance has been taken out of rotation
+        state_arc.draining.store(true, Ordering::Relaxed);
+        let grace_period = Duration::from_secs(state_arc.config.shutdown_grace_period_secs);
+        info!(
+            ?grace_period,
+            "Shutdown signal received, failing readiness before draining"
+        );
+        let server_exited = select! {
+            _ = tokio::time::sleep(grace_period) => false,
+            _ = servers.join_next() => true,
+        };
+        if server_exited {
+            warn!("Server terminated during the shutdown grace period");
+        }
+
+        // The servers stop accepting connections and wait for in-flight
+        // requests, e.g. pushes being sent, to finish
+        stop_serving.cancel();
+        let drain_timeout = Duration::from_secs(state_arc.config.shutdown_drain_timeout_secs);
+        info!(?drain_timeout, "Draining in-flight requests");
+        let drained = tokio::time::timeout(drain_timeout, async {
+            while servers.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!(
+                ?drain_timeout,
+                "In-flight requests did not finish in time, dropping them"
+            );
+        }
+    }
+
+    // Stops the remaining server if the other one terminated on its own, or
+    // both when draining timed out
+    servers.shutdown().await;
+
+    for task in background_tasks {
+        task.abort();
+        let _ = task.await;
+    }
+
+    // Detached tasks still hold the state and may collect analytics
+    state_arc.tasks.close();
+    let shutdown_timeout = Duration::from_secs(state_arc.config.shutdown_drain_timeout_secs);
+    if tokio::time::timeout(shutdown_timeout, state_arc.tasks.wait())
+        .await
+        .is_err()
+    {
+        // Interrupted tenant deletions are finished by the reconciler
+        warn!(
+            tasks = state_arc.tasks.len(),
+            "Detached tasks did not finish in time, dropping them"
+        );
+    }
+
+    // The collectors export their pending batches once the last reference to
+    // them is dropped, the servers, background tasks and detached tasks
+    // holding the state are gone by now
+    #[cfg(feature = "analytics")]
+    if let Some(analytics) = state_arc.analytics.clone() {
+        drop(state_arc);
+        analytics.flush().await;
     }
 
     Ok(())
 }
 
+/// Whether the geoip database or the analytics are on S3
+#[cfg(any(feature = "analytics", feature = "geoblock"))]
+fn uses_s3(config: &Config) -> bool {
+    let geoip = config.geoip_db_bucket.is_some() && config.geoip_db_key.is_some();
+    #[cfg(feature = "analytics")]
+    let analytics = matches!(
+        config.analytics_export_backend,
+        crate::config::AnalyticsExportBackend::S3
+    );
+    #[cfg(not(feature = "analytics"))]
+    let analytics = false;
+
+    geoip || analytics
+}
+
 #[cfg(any(feature = "analytics", feature = "geoblock"))]
 async fn get_geoip_resolver(config: &Config, s3_client: &S3Client) -> Option<Arc<MaxMindResolver>> {
     match (&config.geoip_db_bucket, &config.geoip_db_key) {
@@ -371,8 +571,10 @@ async fn get_s3_client(config: &Config) -> S3Client {
         Some(s3_endpoint) => {
             info!(%s3_endpoint, "initializing analytics with custom s3 endpoint");
 
+            // S3 compatible stores generally don't support virtual hosted buckets
             aws_sdk_s3::config::Builder::from(&shared_config)
                 .endpoint_url(s3_endpoint)
+                .force_path_style(true)
                 .build()
         }
         _ => aws_sdk_s3::config::Builder::from(&shared_config).build(),
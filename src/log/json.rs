@@ -0,0 +1,133 @@
+//! Newline delimited JSON output, one object per event.
+//!
+//! Every event carries the fields of the spans it happened in, e.g. the
+//! `request_id` of the HTTP request or the `tenant_id` and `client_id` of a
+//! push, with fields of inner spans taking precedence. Sensitive fields are
+//! redacted.
+use {
+    super::{is_sensitive_field, REDACTED},
+    serde_json::{Map, Value},
+    std::{fmt, io::Write},
+    tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Subscriber,
+    },
+    tracing_subscriber::{fmt::MakeWriter, layer::Context, registry::LookupSpan, Layer},
+};
+
+/// Fields of a span, kept in the span's extensions.
+struct SpanFields(Map<String, Value>);
+
+pub struct JsonLayer<W> {
+    make_writer: W,
+}
+
+impl<W> JsonLayer<W> {
+    pub fn new(make_writer: W) -> Self {
+        Self { make_writer }
+    }
+}
+
+impl<S, W> Layer<S> for JsonLayer<W>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert(
+            "timestamp".to_string(),
+            chrono::Utc::now().to_rfc3339().into(),
+        );
+        object.insert("level".to_string(), metadata.level().as_str().into());
+        object.insert("target".to_string(), metadata.target().into());
+
+        if let Some(scope) = ctx.event_scope(event) {
+            let mut span_name = None;
+            for span in scope.from_root() {
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                    object.extend(fields.clone());
+                }
+                span_name = Some(span.name());
+            }
+            if let Some(span_name) = span_name {
+                object.insert("span".to_string(), span_name.into());
+            }
+        }
+
+        event.record(&mut JsonVisitor(&mut object));
+
+        let Ok(mut line) = serde_json::to_vec(&object) else {
+            return;
+        };
+        line.push(b'\n');
+        // Nowhere to report a failed write
+        let _ = self.make_writer.make_writer_for(metadata).write_all(&line);
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl JsonVisitor<'_> {
+    fn insert(&mut self, field: &Field, value: Value) {
+        let value = if is_sensitive_field(field.name()) {
+            REDACTED.into()
+        } else {
+            value
+        };
+        self.0.insert(field.name().to_string(), value);
+    }
+}
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.into());
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.insert(field, value.to_string().into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{value:?}").into());
+    }
+}
@@ -9,10 +9,41 @@
 //! feature gate. See the [features] section of Cargo.toml for more.
 pub use tracing::{debug, error, info, trace, warn};
 use {
-    tracing_appender::non_blocking::WorkerGuard,
-    tracing_subscriber::{prelude::*, EnvFilter},
+    crate::{
+        config::{Config, LogFormat, LogRotation},
+        error::Error::InvalidConfiguration,
+        state::INSTANCE_ID,
+    },
+    axum::http::HeaderMap,
+    opentelemetry::{
+        global,
+        propagation::Extractor,
+        sdk::{propagation::TraceContextPropagator, trace, Resource},
+        trace::TracerProvider as _,
+        KeyValue,
+    },
+    opentelemetry_otlp::{SpanExporterBuilder, WithExportConfig},
+    std::path::Path,
+    tracing::Subscriber,
+    tracing_appender::{
+        non_blocking::WorkerGuard,
+        rolling::{RollingFileAppender, Rotation},
+    },
+    tracing_opentelemetry::OpenTelemetrySpanExt,
+    tracing_subscriber::{
+        field::MakeExt,
+        fmt::{format::debug_fn, MakeWriter},
+        prelude::*,
+        registry::LookupSpan,
+        EnvFilter, Layer, Registry,
+    },
 };
 
+mod json;
+mod otlp;
+
+pub use json::JsonLayer;
+
 pub mod prelude {
     //! Reexport of the most common macros and traits used for logging.
     //!
@@ -26,33 +57,194 @@ pub mod prelude {
 /// no other can be found.
 const DEFAULT_LOG_LEVEL_STDERR: tracing::Level = tracing::Level::WARN;
 
-/// The environment variable used to control the stderr logger.
+/// The environment variable used to control the stderr and file loggers.
 const ENV_LOG_LEVEL_STDERR: &str = "LOG_LEVEL";
 
+/// The `service.name` of exported spans.
+const SERVICE_NAME: &str = "echo-server";
+
+/// Replaces the value of sensitive fields.
+const REDACTED: &str = "[redacted]";
+
+/// Fields never written to the logs, along with any field ending in `_token`,
+/// `_secret` or `_password`.
+const SENSITIVE_FIELDS: &[&str] = &[
+    "token",
+    "secret",
+    "password",
+    "authorization",
+    "fcm_api_key",
+    "fcm_v1_credentials",
+    "apns_certificate",
+    "apns_pkcs8_pem",
+];
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
 pub struct Logger {
-    _guard: WorkerGuard,
+    _guards: Vec<WorkerGuard>,
+    exporting_traces: bool,
 }
 
 impl Logger {
-    pub fn init() -> crate::error::Result<Self> {
-        let stderr_filter = EnvFilter::try_from_env(ENV_LOG_LEVEL_STDERR)
-            .unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_LEVEL_STDERR.to_string()));
-
+    /// Logs to stderr and `LOG_FILE` in the `LOG_FORMAT`, spans are also
+    /// exported over OTLP when `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+    pub fn init(config: &Config) -> crate::error::Result<Self> {
         let (writer, guard) = tracing_appender::non_blocking(std::io::stderr());
+        let mut guards = vec![guard];
+        let mut loggers = vec![format_layer(
+            config.log_format,
+            writer,
+            atty::is(atty::Stream::Stderr),
+        )];
+
+        if let Some(log_file) = &config.log_file {
+            let (writer, guard) =
+                tracing_appender::non_blocking(file_appender(log_file, config.log_file_rotation)?);
+            guards.push(guard);
+            loggers.push(format_layer(config.log_format, writer, false));
+        }
+
+        let otlp = config
+            .otel_exporter_otlp_endpoint
+            .as_deref()
+            .map(|endpoint| otlp_layer(endpoint, &config.log_level_otel))
+            .transpose()?;
+        let exporting_traces = otlp.is_some();
 
-        let logger = tracing_subscriber::fmt::layer()
+        tracing_subscriber::registry()
+            .with(loggers)
+            .with(otlp)
+            .init();
+
+        Ok(Self {
+            _guards: guards,
+            exporting_traces,
+        })
+    }
+
+    pub fn stop(self) {
+        // Export the spans still batched, consume self to trigger drop.
+        if self.exporting_traces {
+            global::shutdown_tracer_provider();
+        }
+    }
+}
+
+fn log_filter() -> EnvFilter {
+    EnvFilter::try_from_env(ENV_LOG_LEVEL_STDERR)
+        .unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_LEVEL_STDERR.to_string()))
+}
+
+/// Writes the events enabled by `LOG_LEVEL` in the given format.
+fn format_layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
             .with_target(false)
-            .with_ansi(atty::is(atty::Stream::Stderr))
+            .with_ansi(ansi)
+            .fmt_fields(
+                debug_fn(|writer, field, value| match field.name() {
+                    "message" => write!(writer, "{value:?}"),
+                    name if is_sensitive_field(name) => write!(writer, "{name}={REDACTED}"),
+                    name => write!(writer, "{name}={value:?}"),
+                })
+                .delimited(" "),
+            )
             .with_writer(writer)
-            .with_filter(stderr_filter)
-            .boxed();
+            .boxed(),
+        LogFormat::Json => json::JsonLayer::new(writer).boxed(),
+    };
+
+    layer.with_filter(log_filter()).boxed()
+}
+
+/// Appends to `path`, rotated files get the date as a suffix.
+fn file_appender(path: &str, rotation: LogRotation) -> crate::error::Result<RollingFileAppender> {
+    let path = Path::new(path);
+    let Some(file_name) = path.file_name() else {
+        return Err(InvalidConfiguration(format!(
+            "LOG_FILE is not a file: {}",
+            path.display()
+        )));
+    };
+    let directory = path
+        .parent()
+        .filter(|directory| !directory.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let rotation = match rotation {
+        LogRotation::Minutely => Rotation::MINUTELY,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+
+    RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(file_name.to_string_lossy())
+        .build(directory)
+        .map_err(|e| InvalidConfiguration(format!("LOG_FILE can't be written: {e}")))
+}
+
+fn is_sensitive_field(name: &str) -> bool {
+    SENSITIVE_FIELDS.contains(&name)
+        || name.ends_with("_token")
+        || name.ends_with("_secret")
+        || name.ends_with("_password")
+}
+
+/// Exports the spans enabled by `filter` (`LOG_LEVEL_OTEL`) to the OTLP gRPC
+/// `endpoint`, with sensitive fields redacted.
+fn otlp_layer<S>(endpoint: &str, filter: &str) -> crate::error::Result<impl Layer<S>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let exporter = SpanExporterBuilder::from(
+        opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(endpoint),
+    )
+    .build_span_exporter()?;
+    let provider = trace::TracerProvider::builder()
+        .with_span_processor(otlp::RedactingSpanProcessor(
+            trace::BatchSpanProcessor::builder(exporter, opentelemetry::runtime::Tokio).build(),
+        ))
+        .with_config(trace::config().with_resource(Resource::new([
+            KeyValue::new("service.name", SERVICE_NAME),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+            KeyValue::new("service.instance.id", INSTANCE_ID.to_string()),
+        ])))
+        .build();
+    let tracer = provider.tracer(SERVICE_NAME);
+    global::set_tracer_provider(provider);
+
+    Ok(tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(EnvFilter::new(filter)))
+}
 
-        tracing_subscriber::registry().with(logger).init();
+/// Reads the W3C trace context headers of a request.
+struct HeaderExtractor<'a>(&'a HeaderMap);
 
-        Ok(Self { _guard: guard })
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
     }
 
-    pub fn stop(self) {
-        // Consume self to trigger drop.
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
     }
 }
+
+/// Continues the caller's trace when the request has a `traceparent` header,
+/// e.g. pushes from the relay. Does nothing unless traces are exported.
+pub fn set_remote_parent(span: &tracing::Span, headers: &HeaderMap) {
+    let context =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    span.set_parent(context);
+}
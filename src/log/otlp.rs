@@ -0,0 +1,66 @@
+//! Redaction of exported spans.
+//!
+//! Span fields and event fields both end up as attributes of the exported
+//! spans, so sensitive ones are redacted before the spans are batched.
+use {
+    super::{is_sensitive_field, REDACTED},
+    opentelemetry::{
+        sdk::{
+            export::trace::SpanData,
+            trace::{EvictedQueue, Span, SpanProcessor},
+        },
+        trace::TraceResult,
+        Context, KeyValue,
+    },
+};
+
+/// Redacts the sensitive attributes of spans and their events, then hands the
+/// spans to the wrapped processor.
+#[derive(Debug)]
+pub struct RedactingSpanProcessor<P>(pub P);
+
+impl<P: SpanProcessor> SpanProcessor for RedactingSpanProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.0.on_start(span, cx);
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        let sensitive_keys = span
+            .attributes
+            .iter()
+            .filter(|(key, _)| is_sensitive_field(key.as_str()))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in sensitive_keys {
+            span.attributes.insert(KeyValue::new(key, REDACTED));
+        }
+
+        if span.events.iter().any(|event| {
+            event
+                .attributes
+                .iter()
+                .any(|attribute| is_sensitive_field(attribute.key.as_str()))
+        }) {
+            let mut events = EvictedQueue::new(span.events.len() as u32);
+            events.extend(span.events.into_iter().map(|mut event| {
+                for attribute in &mut event.attributes {
+                    if is_sensitive_field(attribute.key.as_str()) {
+                        attribute.value = REDACTED.into();
+                    }
+                }
+                event
+            }));
+            span.events = events;
+        }
+
+        self.0.on_end(span);
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        self.0.force_flush()
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        self.0.shutdown()
+    }
+}
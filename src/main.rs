@@ -1,17 +1,37 @@
 use {
     dotenv::dotenv,
     echo_server::{config, log},
-    tokio::sync::broadcast,
+    tokio::{signal, sync::broadcast},
 };
 
 #[tokio::main]
 async fn main() -> echo_server::error::Result<()> {
-    let logger = log::Logger::init().expect("Failed to start logging");
+    let (signal, shutdown) = broadcast::channel(1);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        let _ = signal.send(());
+    });
 
-    let (_signal, shutdown) = broadcast::channel(1);
     dotenv().ok();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if echo_server::cli::is_check_config(&args) {
+        return echo_server::cli::check_config();
+    }
+    let command = echo_server::cli::Command::parse(&args).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(2);
+    });
+
     let config = config::get_config()
         .expect("Failed to load config, please ensure all env vars are defined.");
+    let logger = log::Logger::init(&config).expect("Failed to start logging");
+
+    if let Some(command) = command {
+        let result = command.run(config).await;
+        logger.stop();
+        return result;
+    }
 
     let result = echo_server::bootstap(shutdown, config).await;
 
@@ -19,3 +39,28 @@ async fn main() -> echo_server::error::Result<()> {
 
     result
 }
+
+/// Resolves on Ctrl+C or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
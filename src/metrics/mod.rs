@@ -1,5 +1,6 @@
 use {
-    std::time::Instant,
+    crate::error::Error,
+    std::{collections::HashSet, sync::Arc, time::Instant},
     wc::metrics::{
         otel::{
             metrics::{Counter, Histogram},
@@ -9,6 +10,25 @@ use {
     },
 };
 
+/// Label shared by the tenants that aren't labelled by their id
+pub const OTHER_TENANT_LABEL: &str = "other";
+
+/// Tenants labelled by their id, the same on every instance so that series
+/// can be aggregated across instances
+#[derive(Clone)]
+struct TenantLabels(Arc<HashSet<String>>);
+
+impl TenantLabels {
+    /// `None` when the tenant wasn't loaded, e.g. for requests with an
+    /// unknown tenant id
+    fn label(&self, tenant_id: Option<&str>) -> String {
+        match tenant_id {
+            Some(tenant_id) if self.0.contains(tenant_id) => tenant_id.to_string(),
+            _ => OTHER_TENANT_LABEL.to_string(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Metrics {
     pub received_notifications: Counter<u64>,
@@ -26,6 +46,16 @@ pub struct Metrics {
     pub tenant_suspensions: Counter<u64>,
     pub client_suspensions: Counter<u64>,
 
+    pub apns_certificate_expiry_warnings: Counter<u64>,
+    pub expired_apns_certificates: Counter<u64>,
+
+    credential_reloads: Counter<u64>,
+
+    provider_send_latency: Histogram<u64>,
+    push_handler_latency: Histogram<u64>,
+    push_failures: Counter<u64>,
+    tenant_labels: Option<TenantLabels>,
+
     postgres_queries: Counter<u64>,
     postgres_query_latency: Histogram<u64>,
 }
@@ -96,6 +126,38 @@ impl Metrics {
             .with_description("The number of clients that have been suspended")
             .init();
 
+        let apns_certificate_expiry_warnings_counter = meter
+            .u64_counter("apns_certificate_expiry_warnings")
+            .with_description(
+                "The number of tenants warned that their APNS certificate is expiring",
+            )
+            .init();
+
+        let expired_apns_certificates_counter = meter
+            .u64_counter("expired_apns_certificates")
+            .with_description("The number of APNS certificates suspended after expiring")
+            .init();
+
+        let credential_reloads: Counter<u64> = meter
+            .u64_counter("credential_reloads")
+            .with_description("The number of single-tenant credential reloads by outcome")
+            .init();
+
+        let provider_send_latency: Histogram<u64> = meter
+            .u64_histogram("provider_send_latency")
+            .with_description("The latency of sending a notification to a provider")
+            .init();
+
+        let push_handler_latency: Histogram<u64> = meter
+            .u64_histogram("push_handler_latency")
+            .with_description("The latency of handling a push request")
+            .init();
+
+        let push_failures: Counter<u64> = meter
+            .u64_counter("push_failures")
+            .with_description("The number of failed push requests by error")
+            .init();
+
         let postgres_queries: Counter<u64> = meter
             .u64_counter("postgres_queries")
             .with_description("The number of Postgres queries executed")
@@ -118,11 +180,83 @@ impl Metrics {
             tenant_fcm_v1_updates: tenant_fcm_v1_updates_counter,
             tenant_suspensions: tenant_suspensions_counter,
             client_suspensions: client_suspensions_counter,
+            apns_certificate_expiry_warnings: apns_certificate_expiry_warnings_counter,
+            expired_apns_certificates: expired_apns_certificates_counter,
+            credential_reloads,
+            provider_send_latency,
+            push_handler_latency,
+            push_failures,
+            tenant_labels: None,
             postgres_queries,
             postgres_query_latency,
         }
     }
 
+    /// Adds a `tenant_id` label to the tenant specific metrics, the given
+    /// tenants are labelled by their id and the others share the `other`
+    /// label
+    pub fn with_tenant_labels(mut self, tenants: impl IntoIterator<Item = String>) -> Self {
+        self.tenant_labels = Some(TenantLabels(Arc::new(tenants.into_iter().collect())));
+        self
+    }
+
+    /// Value of the `tenant_id` label the tenant's metrics are recorded
+    /// with, `None` when the label is left out
+    pub fn tenant_label(&self, tenant_id: Option<&str>) -> Option<String> {
+        self.tenant_labels
+            .as_ref()
+            .map(|tenant_labels| tenant_labels.label(tenant_id))
+    }
+
+    fn with_tenant(&self, mut attributes: Vec<KeyValue>, tenant_id: Option<&str>) -> Vec<KeyValue> {
+        if let Some(label) = self.tenant_label(tenant_id) {
+            attributes.push(KeyValue::new("tenant_id", label));
+        }
+        attributes
+    }
+
+    /// `outcome` is one of `ok`, `bad_token`, `bad_credentials`, `transient`
+    /// or `other`
+    pub fn provider_send(
+        &self,
+        provider: &'static str,
+        outcome: &'static str,
+        tenant_id: Option<&str>,
+        start: Instant,
+    ) {
+        let attributes = self.with_tenant(
+            vec![
+                KeyValue::new("provider", provider),
+                KeyValue::new("outcome", outcome),
+            ],
+            tenant_id,
+        );
+        self.provider_send_latency
+            .record(start.elapsed().as_millis() as u64, &attributes);
+    }
+
+    /// `tenant_id` is `None` when the request failed before the tenant was
+    /// loaded
+    pub fn push_handler(&self, status: u16, tenant_id: Option<&str>, start: Instant) {
+        let attributes =
+            self.with_tenant(vec![KeyValue::new("status", i64::from(status))], tenant_id);
+        self.push_handler_latency
+            .record(start.elapsed().as_millis() as u64, &attributes);
+    }
+
+    pub fn push_failure(&self, error: &Error, tenant_id: Option<&str>) {
+        let attributes = self.with_tenant(
+            vec![KeyValue::new("error", error.variant_name())],
+            tenant_id,
+        );
+        self.push_failures.add(1, &attributes);
+    }
+
+    pub fn credential_reload(&self, outcome: &'static str) {
+        self.credential_reloads
+            .add(1, &[KeyValue::new("outcome", outcome)]);
+    }
+
     pub fn postgres_query(&self, query_name: &'static str, start: Instant) {
         let elapsed = start.elapsed();
 
@@ -30,13 +30,28 @@ pub fn find_public_ip_addr() -> Result<IpAddr, NetworkInterfaceError> {
     }
 }
 
-fn is_public_ip_addr(addr: IpAddr) -> bool {
+/// The first IPv4 address of a non-loopback interface, for machines without a
+/// public IP address.
+pub fn find_local_ip_addr() -> Option<IpAddr> {
+    pnet_datalink::interfaces()
+        .into_iter()
+        .filter(|iface| !iface.is_loopback())
+        .flat_map(|iface| iface.ips)
+        .map(|ip| ip.ip())
+        .find(|ip| ip.is_ipv4())
+}
+
+/// Whether the address is outside of the loopback, private, link-local,
+/// shared (CGNAT) and other reserved ranges. IPv4-mapped IPv6 addresses are
+/// checked as IPv4.
+pub fn is_public_ip_addr(addr: IpAddr) -> bool {
     use once_cell::sync::Lazy;
 
-    static RESERVED_NETWORKS: Lazy<[IpNet; 24]> = Lazy::new(|| {
+    static RESERVED_NETWORKS: Lazy<[IpNet; 35]> = Lazy::new(|| {
         [
             "0.0.0.0/8",
             "0.0.0.0/32",
+            "10.0.0.0/8",
             "100.64.0.0/10",
             "127.0.0.0/8",
             "169.254.0.0/16",
@@ -59,10 +74,27 @@ fn is_public_ip_addr(addr: IpAddr) -> bool {
             "203.0.113.0/24",
             "240.0.0.0/4",
             "255.255.255.255/32",
+            "::/128",
+            "::1/128",
+            "64:ff9b::/96",
+            "100::/64",
+            "2001::/23",
+            "2001:db8::/32",
+            "2002::/16",
+            "fc00::/7",
+            "fe80::/10",
+            "ff00::/8",
         ]
         .map(|net| net.parse().unwrap())
     });
 
+    let addr = match addr {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    };
     RESERVED_NETWORKS.iter().all(|range| !range.contains(&addr))
 }
 
@@ -1,6 +1,10 @@
 use {
     super::{LegacyPushMessage, PushMessage, RawPushMessage},
-    crate::{blob::DecryptedPayloadBlob, error::Error, providers::PushProvider},
+    crate::{
+        blob::DecryptedPayloadBlob,
+        error::Error,
+        providers::{ProviderResponse, PushProvider},
+    },
     a2::{ClientConfig, ErrorReason, NotificationBuilder, NotificationOptions},
     async_trait::async_trait,
     std::io::Read,
@@ -44,16 +48,22 @@ impl ApnsProvider {
             topic,
         })
     }
+
+    /// Sends with the topic instead of the tenant's default topic
+    pub fn with_topic(mut self, topic: String) -> Self {
+        self.topic = topic;
+        self
+    }
 }
 
 #[async_trait]
 impl PushProvider for ApnsProvider {
-    #[instrument(name = "send_apns_notification")]
+    #[instrument(name = "send_apns_notification", skip_all)]
     async fn send_notification(
         &self,
         token: String,
         body: PushMessage,
-    ) -> crate::error::Result<()> {
+    ) -> crate::error::Result<ProviderResponse> {
         let opt = NotificationOptions {
             apns_id: None,
             apns_expiration: None,
@@ -125,7 +135,10 @@ impl PushProvider for ApnsProvider {
                     );
                     Err(Error::Apns(a2::Error::ResponseError(response)))
                 } else {
-                    Ok(())
+                    Ok(ProviderResponse {
+                        message_id: response.apns_id.clone(),
+                        raw: Some(format!("{response:?}")),
+                    })
                 }
             }
             Err(e) => match e {
@@ -133,15 +146,15 @@ impl PushProvider for ApnsProvider {
                     None => Err(Error::Apns(a2::Error::ResponseError(res))),
                     Some(response) => match response.reason {
                         ErrorReason::BadDeviceToken => {
-                            Err(Error::BadDeviceToken("Bad device token".to_string()))
+                            Err(Error::BadDeviceToken("Bad device token"))
                         }
                         // Note: This will have the device deleted because the token was not for the
                         // configured topic
                         ErrorReason::DeviceTokenNotForTopic => Err(Error::BadDeviceToken(
-                            "The device token does not match the specified topic".to_string(),
+                            "The device token does not match the specified topic",
                         )),
                         ErrorReason::Unregistered => Err(Error::BadDeviceToken(
-                            "The device token is inactive for the specified topic".to_string(),
+                            "The device token is inactive for the specified topic",
                         )),
                         ErrorReason::TopicDisallowed => Err(Error::BadApnsCredentials),
                         reason => Err(Error::ApnsResponse(reason)),
@@ -1,6 +1,10 @@
 use {
     super::{LegacyPushMessage, PushMessage},
-    crate::{blob::DecryptedPayloadBlob, error::Error, providers::PushProvider},
+    crate::{
+        blob::DecryptedPayloadBlob,
+        error::Error,
+        providers::{ProviderResponse, PushProvider},
+    },
     async_trait::async_trait,
     fcm::{ErrorReason, FcmError, FcmResponse, MessageBuilder, NotificationBuilder, Priority},
     std::fmt::{Debug, Formatter},
@@ -23,12 +27,12 @@ impl FcmProvider {
 
 #[async_trait]
 impl PushProvider for FcmProvider {
-    #[instrument(name = "send_fcm_notification")]
+    #[instrument(name = "send_fcm_notification", skip_all)]
     async fn send_notification(
         &self,
         token: String,
         body: PushMessage,
-    ) -> crate::error::Result<()> {
+    ) -> crate::error::Result<ProviderResponse> {
         let mut message_builder = MessageBuilder::new(self.api_key.as_str(), token.as_str());
 
         let result = match body {
@@ -72,23 +76,29 @@ impl PushProvider for FcmProvider {
 
         match result {
             Ok(val) => {
-                let FcmResponse { error, .. } = val;
+                let raw = format!("{val:?}");
+                let FcmResponse {
+                    error, message_id, ..
+                } = val;
                 if let Some(error) = error {
                     match error {
-                        ErrorReason::MissingRegistration => Err(Error::BadDeviceToken(
-                            "Missing registration for token".into(),
-                        )),
+                        ErrorReason::MissingRegistration => {
+                            Err(Error::BadDeviceToken("Missing registration for token"))
+                        }
                         ErrorReason::InvalidRegistration => {
-                            Err(Error::BadDeviceToken("Invalid token registration".into()))
+                            Err(Error::BadDeviceToken("Invalid token registration"))
                         }
                         ErrorReason::NotRegistered => {
-                            Err(Error::BadDeviceToken("Token is not registered".into()))
+                            Err(Error::BadDeviceToken("Token is not registered"))
                         }
                         ErrorReason::InvalidApnsCredential => Err(Error::BadApnsCredentials),
                         e => Err(Error::FcmResponse(e)),
                     }
                 } else {
-                    Ok(())
+                    Ok(ProviderResponse {
+                        message_id: message_id.map(|id| id.to_string()),
+                        raw: Some(raw),
+                    })
                 }
             }
             Err(e) => match e {
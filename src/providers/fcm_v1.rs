@@ -1,6 +1,10 @@
 use {
     super::{LegacyPushMessage, PushMessage},
-    crate::{blob::DecryptedPayloadBlob, error::Error, providers::PushProvider},
+    crate::{
+        blob::DecryptedPayloadBlob,
+        error::Error,
+        providers::{ProviderResponse, PushProvider},
+    },
     async_trait::async_trait,
     fcm_v1::{
         gauth::serv_account::ServiceAccountKey, AndroidConfig, AndroidMessagePriority, ApnsConfig,
@@ -37,7 +41,7 @@ impl PushProvider for FcmV1Provider {
         &self,
         token: String,
         body: PushMessage,
-    ) -> crate::error::Result<()> {
+    ) -> crate::error::Result<ProviderResponse> {
         fn make_message(
             token: String,
             notification: Option<Notification>,
@@ -118,10 +122,12 @@ impl PushProvider for FcmV1Provider {
             }
         };
 
-        result.map(|_| ()).map_err(|e| match e {
-            SendError::Unregistered => Error::BadDeviceToken("Token was unregistered".into()),
-            SendError::Forbidden => Error::BadFcmV1Credentials,
-            e => Error::FcmV1(e),
-        })
+        result
+            .map(|_| ProviderResponse::default())
+            .map_err(|e| match e {
+                SendError::Unregistered => Error::BadDeviceToken("Token was unregistered"),
+                SendError::Forbidden => Error::BadFcmV1Credentials,
+                e => Error::FcmV1(e),
+            })
     }
 }
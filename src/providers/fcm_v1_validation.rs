@@ -0,0 +1,184 @@
+//! Checks FCM v1 service account credentials before they are saved, by
+//! exchanging the key for an OAuth access token and sending a `validate_only`
+//! message with it
+use {
+    crate::error::{
+        Error::{FcmV1CredentialValidation, FcmV1InvalidServiceAccountKey},
+        Result,
+    },
+    jsonwebtoken::{Algorithm, EncodingKey, Header},
+    serde::{Deserialize, Serialize},
+    serde_json::json,
+    std::fmt::{Display, Formatter},
+    tracing::{debug, instrument},
+};
+
+pub const DEFAULT_FCM_V1_ENDPOINT: &str = "https://fcm.googleapis.com";
+
+const FCM_V1_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+const TOKEN_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+const TOKEN_LIFETIME_SECS: i64 = 60 * 60;
+/// Topic targeted by the dry-run, FCM accepts any topic name for
+/// `validate_only` messages
+const DRY_RUN_TOPIC: &str = "echo-server-credential-check";
+
+/// Step of the validation that rejected the credentials
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FcmV1ValidationStep {
+    /// The OAuth token exchange for the service account
+    TokenExchange,
+    /// The `validate_only` send to FCM
+    DryRun,
+}
+
+impl FcmV1ValidationStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TokenExchange => "token_exchange",
+            Self::DryRun => "dry_run",
+        }
+    }
+}
+
+impl Display for FcmV1ValidationStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Endpoints used for validation, overridable so that tests can point them at
+/// a local server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FcmV1Endpoints {
+    /// Replaces the `token_uri` of the service account when set
+    pub token: Option<String>,
+    pub fcm: String,
+}
+
+impl Default for FcmV1Endpoints {
+    fn default() -> Self {
+        Self {
+            token: None,
+            fcm: DEFAULT_FCM_V1_ENDPOINT.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ServiceAccount {
+    project_id: String,
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Serialize)]
+struct TokenClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Validates the service account key JSON, returning which step failed
+#[instrument(skip_all)]
+pub async fn validate_credentials(
+    http_client: &reqwest::Client,
+    endpoints: &FcmV1Endpoints,
+    credentials: &str,
+) -> Result<()> {
+    let account: ServiceAccount =
+        serde_json::from_str(credentials).map_err(FcmV1InvalidServiceAccountKey)?;
+
+    let access_token = exchange_token(http_client, endpoints, &account).await?;
+    dry_run(http_client, endpoints, &account, &access_token).await
+}
+
+async fn exchange_token(
+    http_client: &reqwest::Client,
+    endpoints: &FcmV1Endpoints,
+    account: &ServiceAccount,
+) -> Result<String> {
+    let step = FcmV1ValidationStep::TokenExchange;
+    let token_uri = endpoints.token.as_deref().unwrap_or(&account.token_uri);
+
+    let key = EncodingKey::from_rsa_pem(account.private_key.as_bytes())
+        .map_err(|e| FcmV1CredentialValidation(step, format!("invalid private key: {e}")))?;
+    let iat = chrono::Utc::now().timestamp();
+    let assertion = jsonwebtoken::encode(
+        &Header::new(Algorithm::RS256),
+        &TokenClaims {
+            iss: &account.client_email,
+            scope: FCM_V1_SCOPE,
+            aud: &account.token_uri,
+            iat,
+            exp: iat + TOKEN_LIFETIME_SECS,
+        },
+        &key,
+    )
+    .map_err(|e| FcmV1CredentialValidation(step, format!("failed to sign assertion: {e}")))?;
+
+    let response = http_client
+        .post(token_uri)
+        .form(&[("grant_type", TOKEN_GRANT_TYPE), ("assertion", &assertion)])
+        .send()
+        .await
+        .map_err(|e| FcmV1CredentialValidation(step, e.to_string()))?;
+    let response = check_status(step, response).await?;
+
+    let token: TokenResponse = response
+        .json()
+        .await
+        .map_err(|e| FcmV1CredentialValidation(step, format!("unexpected response: {e}")))?;
+
+    Ok(token.access_token)
+}
+
+async fn dry_run(
+    http_client: &reqwest::Client,
+    endpoints: &FcmV1Endpoints,
+    account: &ServiceAccount,
+    access_token: &str,
+) -> Result<()> {
+    let step = FcmV1ValidationStep::DryRun;
+
+    let response = http_client
+        .post(format!(
+            "{}/v1/projects/{}/messages:send",
+            endpoints.fcm.trim_end_matches('/'),
+            account.project_id
+        ))
+        .bearer_auth(access_token)
+        .json(&json!({
+            "validate_only": true,
+            "message": {
+                "topic": DRY_RUN_TOPIC,
+            },
+        }))
+        .send()
+        .await
+        .map_err(|e| FcmV1CredentialValidation(step, e.to_string()))?;
+    check_status(step, response).await?;
+
+    Ok(())
+}
+
+async fn check_status(
+    step: FcmV1ValidationStep,
+    response: reqwest::Response,
+) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    debug!(%step, %status, "FCM v1 credential validation failed: {body}");
+    Err(FcmV1CredentialValidation(step, format!("{status}: {body}")))
+}
@@ -1,17 +1,20 @@
 pub mod apns;
+#[cfg(feature = "legacy_fcm")]
 pub mod fcm;
 pub mod fcm_v1;
+pub mod fcm_v1_validation;
 #[cfg(any(debug_assertions, test))]
 pub mod noop;
 
 use {
     self::fcm_v1::FcmV1Provider,
     crate::{
-        blob::ENCRYPTED_FLAG,
+        blob::{DecryptedPayloadBlob, ENCRYPTED_FLAG},
         error,
-        providers::{apns::ApnsProvider, fcm::FcmProvider},
+        providers::apns::ApnsProvider,
     },
     async_trait::async_trait,
+    base64::Engine as _,
     relay_rpc::rpc::msg_id::get_message_id,
     serde::{Deserialize, Serialize},
     std::{
@@ -21,9 +24,14 @@ use {
     tracing::instrument,
 };
 
+#[cfg(feature = "legacy_fcm")]
+use crate::providers::fcm::FcmProvider;
 #[cfg(any(debug_assertions, test))]
 use crate::providers::noop::NoopProvider;
 
+/// Topic of notifications built by [`PushMessage::plain`]
+const TEST_NOTIFICATION_TOPIC: &str = "echo-server-test";
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum PushMessage {
     LegacyPushMessage(LegacyPushMessage),
@@ -44,6 +52,29 @@ impl PushMessage {
             Self::LegacyPushMessage(msg) => msg.payload.topic.clone(),
         }
     }
+
+    /// Builds an unencrypted notification showing the title and body, used to
+    /// check a tenant's credentials end to end
+    pub fn plain(title: &str, body: &str) -> error::Result<Self> {
+        let blob = serde_json::to_vec(&DecryptedPayloadBlob {
+            title: title.to_string(),
+            body: body.to_string(),
+            image: None,
+            url: None,
+        })
+        .map_err(error::Error::InternalSerializationError)?;
+
+        Ok(Self::LegacyPushMessage(LegacyPushMessage {
+            id: uuid::Uuid::new_v4().to_string().into(),
+            payload: MessagePayload {
+                topic: TEST_NOTIFICATION_TOPIC.into(),
+                flags: 0,
+                blob: base64::engine::general_purpose::STANDARD
+                    .encode(blob)
+                    .into(),
+            },
+        }))
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
@@ -76,9 +107,22 @@ pub struct RawPushMessage {
     pub message: Arc<str>,
 }
 
+/// What the provider returned for a notification it accepted
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProviderResponse {
+    /// Id the provider assigned to the notification, if it returns one
+    pub message_id: Option<String>,
+    /// The provider's response as received, for debugging credentials
+    pub raw: Option<String>,
+}
+
 #[async_trait]
 pub trait PushProvider {
-    async fn send_notification(&self, token: String, body: PushMessage) -> error::Result<()>;
+    async fn send_notification(
+        &self,
+        token: String,
+        body: PushMessage,
+    ) -> error::Result<ProviderResponse>;
 }
 
 pub const PROVIDER_APNS: &str = "apns";
@@ -154,6 +198,7 @@ impl TryFrom<&str> for ProviderKind {
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 pub enum Provider {
+    #[cfg(feature = "legacy_fcm")]
     Fcm(FcmProvider),
     FcmV1(FcmV1Provider),
     Apns(ApnsProvider),
@@ -161,11 +206,39 @@ pub enum Provider {
     Noop(NoopProvider),
 }
 
+impl Provider {
+    /// Provider name used in metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "legacy_fcm")]
+            Provider::Fcm(_) => PROVIDER_FCM,
+            Provider::FcmV1(_) => PROVIDER_FCM_V1,
+            Provider::Apns(_) => PROVIDER_APNS,
+            #[cfg(any(debug_assertions, test))]
+            Provider::Noop(_) => PROVIDER_NOOP,
+        }
+    }
+
+    /// Sends APNs notifications with the client's topic, other providers are
+    /// unaffected
+    pub fn with_apns_topic(self, topic: Option<&str>) -> Self {
+        match (self, topic) {
+            (Provider::Apns(p), Some(topic)) => Provider::Apns(p.with_topic(topic.to_string())),
+            (provider, _) => provider,
+        }
+    }
+}
+
 #[async_trait]
 impl PushProvider for Provider {
-    #[instrument(name = "send_notification")]
-    async fn send_notification(&self, token: String, body: PushMessage) -> error::Result<()> {
+    #[instrument(name = "send_notification", skip_all, fields(provider = self.name()))]
+    async fn send_notification(
+        &self,
+        token: String,
+        body: PushMessage,
+    ) -> error::Result<ProviderResponse> {
         match self {
+            #[cfg(feature = "legacy_fcm")]
             Provider::Fcm(p) => p.send_notification(token, body).await,
             Provider::FcmV1(p) => p.send_notification(token, body).await,
             Provider::Apns(p) => p.send_notification(token, body).await,
@@ -1,6 +1,6 @@
 use {
     super::PushMessage,
-    crate::providers::PushProvider,
+    crate::providers::{ProviderResponse, PushProvider},
     async_trait::async_trait,
     reqwest::Url,
     std::{collections::HashMap, sync::Arc},
@@ -21,12 +21,12 @@ impl NoopProvider {
 
 #[async_trait]
 impl PushProvider for NoopProvider {
-    #[instrument(name = "send_noop_notification")]
+    #[instrument(name = "send_noop_notification", skip_all)]
     async fn send_notification(
         &self,
         token: String,
         body: PushMessage,
-    ) -> crate::error::Result<()> {
+    ) -> crate::error::Result<ProviderResponse> {
         self.bootstrap(token.clone()).await;
 
         let mut lock = self.notifications.write().await;
@@ -37,7 +37,7 @@ impl PushProvider for NoopProvider {
             assert!(reqwest::get(url).await?.status().is_success());
         }
 
-        Ok(())
+        Ok(ProviderResponse::default())
     }
 }
 
@@ -1,24 +1,33 @@
 use {
     crate::{
         config::Config,
+        jwt_validation::JwtValidationClient,
         metrics::Metrics,
         middleware::rate_limit,
         networking,
         providers::Provider,
         relay::RelayClient,
         stores::{client::ClientStore, notification::NotificationStore, tenant::TenantStore},
+        webhooks::Webhooks,
     },
     build_info::BuildInfo,
     moka::future::Cache,
-    std::{net::IpAddr, sync::Arc},
+    once_cell::sync::Lazy,
+    sqlx::PgPool,
+    std::{
+        net::IpAddr,
+        sync::{atomic::AtomicBool, Arc},
+    },
     tokio::time::Duration,
+    tokio_util::task::TaskTracker,
     wc::geoip::{block::middleware::GeoBlockLayer, MaxMindResolver},
 };
 
 #[cfg(feature = "analytics")]
 use crate::analytics::PushAnalytics;
-#[cfg(feature = "multitenant")]
-use crate::jwt_validation::JwtValidationClient;
+
+/// Identifies this process in the health endpoints and exported traces
+pub static INSTANCE_ID: Lazy<uuid::Uuid> = Lazy::new(uuid::Uuid::new_v4);
 
 pub type ClientStoreArc = Arc<dyn ClientStore + Send + Sync + 'static>;
 pub type NotificationStoreArc = Arc<dyn NotificationStore + Send + Sync + 'static>;
@@ -45,9 +54,12 @@ pub struct AppState {
     pub client_store: ClientStoreArc,
     pub notification_store: NotificationStoreArc,
     pub tenant_store: TenantStoreArc,
+    /// Pools checked by the readiness endpoint
+    pub database: Option<PgPool>,
+    pub tenant_database: Option<PgPool>,
     pub relay_client: RelayClient,
-    #[cfg(feature = "multitenant")]
     pub jwt_validation_client: JwtValidationClient,
+    pub webhooks: Option<Webhooks>,
     pub public_ip: Option<IpAddr>,
     is_multitenant: bool,
     pub geoblock: Option<GeoBlockLayer<Arc<MaxMindResolver>>>,
@@ -58,6 +70,13 @@ pub struct AppState {
     pub http_client: reqwest::Client,
     pub provider_cache: Cache<String, Provider>,
     pub rate_limit: rate_limit::RateLimiter,
+    /// Set once a shutdown signal was received and in-flight requests are
+    /// being drained
+    pub draining: Arc<AtomicBool>,
+    /// Tasks outliving the request that spawned them, e.g. collecting
+    /// analytics or purging a deleted tenant. Waited for on shutdown so that
+    /// their analytics are flushed
+    pub tasks: TaskTracker,
 }
 
 build_info::build_info!(fn build_info);
@@ -70,13 +89,7 @@ pub fn new_state(
 ) -> crate::error::Result<AppState> {
     let build_info: &BuildInfo = build_info();
 
-    #[cfg(feature = "multitenant")]
-    let is_multitenant = true;
-
-    #[cfg(not(feature = "multitenant"))]
-    let is_multitenant = false;
-
-    #[cfg(feature = "multitenant")]
+    let is_multitenant = config.is_multitenant();
     let jwt_secret = config.jwt_secret.clone();
 
     let public_ip = match networking::find_public_ip_addr() {
@@ -94,17 +107,21 @@ pub fn new_state(
         client_store,
         notification_store,
         tenant_store,
+        database: None,
+        tenant_database: None,
         relay_client: RelayClient::new(config.relay_public_key)?,
-        #[cfg(feature = "multitenant")]
         jwt_validation_client: JwtValidationClient::new(jwt_secret),
+        webhooks: None,
         public_ip,
         is_multitenant,
         geoblock: None,
-        instance_id: uuid::Uuid::new_v4(),
+        instance_id: *INSTANCE_ID,
         uptime: std::time::Instant::now(),
         http_client: reqwest::Client::new(),
         provider_cache: Cache::new(100),
         rate_limit: rate_limit::RateLimiter::new(100, Duration::from_secs(60)),
+        draining: Arc::new(AtomicBool::new(false)),
+        tasks: TaskTracker::new(),
     })
 }
 
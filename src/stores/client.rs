@@ -2,14 +2,24 @@ use {
     crate::{
         metrics::Metrics,
         providers::ProviderKind,
-        stores::{self, StoreError::NotFound},
+        stores::{
+            self,
+            StoreError::{AlwaysRawMismatch, NotFound},
+        },
     },
     async_trait::async_trait,
-    sqlx::Executor,
+    sqlx::{Connection, Executor, Postgres, Transaction},
     std::time::Instant,
     tracing::{debug, instrument},
 };
 
+/// Device id used for registrations that don't specify one, clients registered
+/// before multi-device support were migrated to this id
+pub const DEFAULT_DEVICE_ID: &str = "default";
+
+/// Number of clients upserted per transaction by [`ClientStore::create_clients`]
+const BULK_CREATE_CHUNK_SIZE: usize = 100;
+
 #[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
 pub struct Client {
     pub tenant_id: String,
@@ -17,8 +27,17 @@ pub struct Client {
     #[sqlx(rename = "device_token")]
     pub token: String,
     pub always_raw: bool,
+    pub device_id: String,
+    /// APNs topic the device registered for, `None` for the tenant's default
+    pub apns_topic: Option<String>,
+    /// FCM v1 credentials slot the device registered for, `None` for the
+    /// tenant's default credentials
+    pub fcm_v1_slot: Option<String>,
 }
 
+/// Notifications have no foreign key to their client since a client id can
+/// have multiple devices, so every path that deletes clients must also delete
+/// the notifications of the client ids that no longer have a device
 #[async_trait]
 pub trait ClientStore {
     async fn create_client(
@@ -28,8 +47,36 @@ pub trait ClientStore {
         client: Client,
         metrics: Option<&Metrics>,
     ) -> stores::Result<()>;
+    /// Creates the clients in chunked transactions with the same conflict
+    /// handling as [`ClientStore::create_client`], returning a result per client
+    async fn create_clients(
+        &self,
+        tenant_id: &str,
+        clients: Vec<(String, Client)>,
+        metrics: Option<&Metrics>,
+    ) -> stores::Result<Vec<stores::Result<()>>>;
     async fn get_client(&self, tenant_id: &str, id: &str) -> stores::Result<Client>;
+    async fn get_client_devices(&self, tenant_id: &str, id: &str) -> stores::Result<Vec<Client>>;
+    /// Returns the client registered with the device token along with its
+    /// client id
+    async fn get_client_by_token(&self, token: &str) -> stores::Result<(String, Client)>;
+    /// Returns every client of the tenant along with its client id
+    async fn get_tenant_clients(&self, tenant_id: &str) -> stores::Result<Vec<(String, Client)>>;
     async fn delete_client(&self, tenant_id: &str, id: &str) -> stores::Result<()>;
+    /// Deletes up to `limit` of the tenant's client devices, returning the
+    /// client id, device id and push type of each. The clients' notifications
+    /// must be deleted separately
+    async fn delete_tenant_clients(
+        &self,
+        tenant_id: &str,
+        limit: i64,
+    ) -> stores::Result<Vec<(String, String, ProviderKind)>>;
+    async fn delete_client_device(
+        &self,
+        tenant_id: &str,
+        id: &str,
+        device_id: &str,
+    ) -> stores::Result<()>;
 }
 
 #[async_trait]
@@ -43,182 +90,81 @@ impl ClientStore for sqlx::PgPool {
         metrics: Option<&Metrics>,
     ) -> stores::Result<()> {
         debug!(
-            "ClientStore::create_client tenant_id={tenant_id} id={id} token={} with locking",
-            client.token
+            "ClientStore::create_client tenant_id={tenant_id} id={id} device_id={} with locking",
+            client.device_id
         );
 
-        #[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
-        pub struct ClientSelect {
-            pub id: String,
-            pub device_token: String,
-            pub tenant_id: String,
-        }
-
         let start = Instant::now();
         let mut transaction = self.begin().await?;
         if let Some(metrics) = metrics {
             metrics.postgres_query("create_client_begin", start);
         }
-        // Lock the records in-case of fast concurrent requests
-        let start = Instant::now();
-        let query = "
-            SELECT
-            pg_advisory_xact_lock(abs(hashtext($1::text))),
-            pg_advisory_xact_lock(abs(hashtext($2::text)))
-        ";
-        sqlx::query(query)
-            .bind(id)
-            .bind(client.token.clone())
-            .execute(&mut transaction)
-            .await?;
-        if let Some(metrics) = metrics {
-            metrics.postgres_query("create_client_pg_advisory_xact_lock", start);
-        }
 
-        let query = "
-            SELECT *
-            FROM public.clients
-            WHERE id = $1
-                  OR device_token = $2
-            FOR UPDATE
-        ";
+        upsert_client(&mut transaction, tenant_id, id, client, metrics).await?;
+
         let start = Instant::now();
-        let existing_client = sqlx::query_as::<sqlx::postgres::Postgres, ClientSelect>(query)
-            .bind(id)
-            .bind(client.token.clone())
-            .fetch_one(&mut transaction)
-            .await
-            .map(Some)
-            .or_else(|e| match e {
-                sqlx::Error::RowNotFound => Ok(None),
-                e => Err(e),
-            })?;
+        transaction.commit().await?;
         if let Some(metrics) = metrics {
-            metrics.postgres_query("create_client_select", start);
+            metrics.postgres_query("create_client_commit", start);
         }
 
-        #[cfg(feature = "functional_tests")]
-        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        Ok(())
+    }
 
-        if let Some(existing_client) = existing_client {
-            if existing_client.id == id && existing_client.device_token != client.token {
-                let query = "
-                    UPDATE public.clients
-                    SET device_token = $2,
-                        push_type = $3,
-                        always_raw = $4,
-                        tenant_id = $5
-                    WHERE id = $1
-                ";
-                let start = Instant::now();
-                sqlx::query(query)
-                    .bind(id)
-                    .bind(client.token)
-                    .bind(client.push_type)
-                    .bind(client.always_raw)
-                    .bind(tenant_id)
-                    .execute(&mut transaction)
-                    .await?;
-                if let Some(metrics) = metrics {
-                    metrics.postgres_query("create_client_update_device_token", start);
-                }
-            } else if existing_client.device_token == client.token && existing_client.id != id {
-                let query = "
-                    DELETE FROM public.notifications
-                    WHERE client_id = $1
-                          AND tenant_id = $2
-                ";
-                let start = Instant::now();
-                sqlx::query(query)
-                    .bind(existing_client.id)
-                    .bind(existing_client.tenant_id)
-                    .execute(&mut transaction)
-                    .await?;
-                if let Some(metrics) = metrics {
-                    metrics.postgres_query("create_client_delete_notifications", start);
-                }
+    #[instrument(skip(self, clients, metrics))]
+    async fn create_clients(
+        &self,
+        tenant_id: &str,
+        clients: Vec<(String, Client)>,
+        metrics: Option<&Metrics>,
+    ) -> stores::Result<Vec<stores::Result<()>>> {
+        debug!(
+            "ClientStore::create_clients tenant_id={tenant_id} count={}",
+            clients.len()
+        );
 
-                let query = "
-                    UPDATE public.clients
-                    SET id = $2,
-                        push_type = $3,
-                        always_raw = $4,
-                        tenant_id = $5
-                    WHERE device_token = $1
-                ";
-                let start = Instant::now();
-                sqlx::query(query)
-                    .bind(client.token)
-                    .bind(id)
-                    .bind(client.push_type)
-                    .bind(client.always_raw)
-                    .bind(tenant_id)
-                    .execute(&mut transaction)
-                    .await?;
-                if let Some(metrics) = metrics {
-                    metrics.postgres_query("create_client_update_id", start);
-                }
-            } else {
-                let query = "
-                    UPDATE public.clients
-                    SET push_type = $2,
-                        always_raw = $3,
-                        tenant_id = $4
-                    WHERE id = $1
-                ";
-                let start = Instant::now();
-                sqlx::query(query)
-                    .bind(id)
-                    .bind(client.push_type)
-                    .bind(client.always_raw)
-                    .bind(tenant_id)
-                    .execute(&mut transaction)
-                    .await?;
-                if let Some(metrics) = metrics {
-                    metrics.postgres_query("create_client_update_id", start);
+        let mut results = Vec::with_capacity(clients.len());
+        let mut clients = clients.into_iter().peekable();
+        while clients.peek().is_some() {
+            let start = Instant::now();
+            let mut transaction = self.begin().await?;
+            if let Some(metrics) = metrics {
+                metrics.postgres_query("create_clients_begin", start);
+            }
+
+            for (id, client) in clients.by_ref().take(BULK_CREATE_CHUNK_SIZE) {
+                // Each client gets a savepoint so that a failing one doesn't abort the
+                // whole chunk
+                let mut savepoint = transaction.begin().await?;
+                match upsert_client(&mut savepoint, tenant_id, &id, client, metrics).await {
+                    Ok(()) => {
+                        savepoint.commit().await?;
+                        results.push(Ok(()));
+                    }
+                    Err(e) => {
+                        savepoint.rollback().await?;
+                        results.push(Err(e));
+                    }
                 }
             }
-        } else {
+
             let start = Instant::now();
-            let mut insert_query = sqlx::QueryBuilder::new(
-                "INSERT INTO public.clients (id, tenant_id, push_type, device_token, always_raw)",
-            );
-            insert_query.push_values(
-                vec![(
-                    id,
-                    tenant_id,
-                    client.push_type,
-                    client.token,
-                    client.always_raw,
-                )],
-                |mut b, client| {
-                    b.push_bind(client.0)
-                        .push_bind(client.1)
-                        .push_bind(client.2)
-                        .push_bind(client.3)
-                        .push_bind(client.4);
-                },
-            );
-            insert_query.build().execute(&mut transaction).await?;
+            transaction.commit().await?;
             if let Some(metrics) = metrics {
-                metrics.postgres_query("create_client_insert", start);
+                metrics.postgres_query("create_clients_commit", start);
             }
         }
 
-        let start = Instant::now();
-        transaction.commit().await?;
-        if let Some(metrics) = metrics {
-            metrics.postgres_query("create_client_commit", start);
-        }
-
-        Ok(())
+        Ok(results)
     }
 
+    /// Returns the most recently registered device of the client
     #[instrument(skip(self))]
     async fn get_client(&self, tenant_id: &str, id: &str) -> stores::Result<Client> {
         let res = sqlx::query_as::<sqlx::postgres::Postgres, Client>(
-            "SELECT tenant_id, push_type, device_token, always_raw FROM public.clients WHERE id = \
-             $1 and tenant_id = $2",
+            "SELECT tenant_id, push_type, device_token, always_raw, device_id, apns_topic, \
+             fcm_v1_slot FROM public.clients WHERE id = $1 and tenant_id = $2 ORDER BY created_at \
+             DESC LIMIT 1",
         )
         .bind(id)
         .bind(tenant_id)
@@ -232,6 +178,125 @@ impl ClientStore for sqlx::PgPool {
         }
     }
 
+    #[instrument(skip(self))]
+    async fn get_client_devices(&self, tenant_id: &str, id: &str) -> stores::Result<Vec<Client>> {
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Client>(
+            "SELECT tenant_id, push_type, device_token, always_raw, device_id, apns_topic, \
+             fcm_v1_slot FROM public.clients WHERE id = $1 and tenant_id = $2 ORDER BY created_at",
+        )
+        .bind(id)
+        .bind(tenant_id)
+        .fetch_all(self)
+        .await?;
+
+        if res.is_empty() {
+            return Err(NotFound("client".to_string(), id.to_string()));
+        }
+
+        Ok(res)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_client_by_token(&self, token: &str) -> stores::Result<(String, Client)> {
+        let res = sqlx::query_as::<
+            sqlx::postgres::Postgres,
+            (
+                String,
+                String,
+                ProviderKind,
+                String,
+                bool,
+                String,
+                Option<String>,
+                Option<String>,
+            ),
+        >(
+            "SELECT id, tenant_id, push_type, device_token, always_raw, device_id, apns_topic, \
+             fcm_v1_slot FROM public.clients WHERE device_token = $1",
+        )
+        .bind(token)
+        .fetch_one(self)
+        .await;
+
+        match res {
+            Err(sqlx::Error::RowNotFound) => Err(NotFound("client".to_string(), token.to_string())),
+            Err(e) => Err(e.into()),
+            Ok((
+                id,
+                tenant_id,
+                push_type,
+                token,
+                always_raw,
+                device_id,
+                apns_topic,
+                fcm_v1_slot,
+            )) => Ok((
+                id,
+                Client {
+                    tenant_id,
+                    push_type,
+                    token,
+                    always_raw,
+                    device_id,
+                    apns_topic,
+                    fcm_v1_slot,
+                },
+            )),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn get_tenant_clients(&self, tenant_id: &str) -> stores::Result<Vec<(String, Client)>> {
+        let rows = sqlx::query_as::<
+            sqlx::postgres::Postgres,
+            (
+                String,
+                String,
+                ProviderKind,
+                String,
+                bool,
+                String,
+                Option<String>,
+                Option<String>,
+            ),
+        >(
+            "SELECT id, tenant_id, push_type, device_token, always_raw, device_id, apns_topic, \
+             fcm_v1_slot FROM public.clients WHERE tenant_id = $1 ORDER BY created_at",
+        )
+        .bind(tenant_id)
+        .fetch_all(self)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(
+                    id,
+                    tenant_id,
+                    push_type,
+                    token,
+                    always_raw,
+                    device_id,
+                    apns_topic,
+                    fcm_v1_slot,
+                )| {
+                    (
+                        id,
+                        Client {
+                            tenant_id,
+                            push_type,
+                            token,
+                            always_raw,
+                            device_id,
+                            apns_topic,
+                            fcm_v1_slot,
+                        },
+                    )
+                },
+            )
+            .collect())
+    }
+
     #[instrument(skip(self))]
     async fn delete_client(&self, tenant_id: &str, id: &str) -> stores::Result<()> {
         debug!("ClientStore::delete_client tenant_id={tenant_id} id={id}");
@@ -256,4 +321,324 @@ impl ClientStore for sqlx::PgPool {
             Err(e) => Err(e.into()),
         }
     }
+
+    #[instrument(skip(self))]
+    async fn delete_tenant_clients(
+        &self,
+        tenant_id: &str,
+        limit: i64,
+    ) -> stores::Result<Vec<(String, String, ProviderKind)>> {
+        let deleted = sqlx::query_as::<sqlx::postgres::Postgres, (String, String, ProviderKind)>(
+            "DELETE FROM public.clients WHERE ctid IN (SELECT ctid FROM public.clients WHERE \
+             tenant_id = $1 LIMIT $2) RETURNING id, device_id, push_type",
+        )
+        .bind(tenant_id)
+        .bind(limit)
+        .fetch_all(self)
+        .await?;
+
+        Ok(deleted)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_client_device(
+        &self,
+        tenant_id: &str,
+        id: &str,
+        device_id: &str,
+    ) -> stores::Result<()> {
+        debug!(
+            "ClientStore::delete_client_device tenant_id={tenant_id} id={id} device_id={device_id}"
+        );
+
+        let mut transaction = self.begin().await?;
+
+        let query = "
+            DELETE FROM public.clients
+            WHERE id = $1
+                  AND tenant_id = $2
+                  AND device_id = $3
+        ";
+        sqlx::query(query)
+            .bind(id)
+            .bind(tenant_id)
+            .bind(device_id)
+            .execute(&mut transaction)
+            .await?;
+
+        // Notifications are tracked per client, so only drop them with the last device
+        let query = "
+            DELETE FROM public.notifications
+            WHERE client_id = $1
+                  AND tenant_id = $2
+                  AND NOT EXISTS (
+                      SELECT 1
+                      FROM public.clients
+                      WHERE id = $1
+                            AND tenant_id = $2
+                  )
+        ";
+        sqlx::query(query)
+            .bind(id)
+            .bind(tenant_id)
+            .execute(&mut transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Inserts or updates the client's device within the transaction, resolving
+/// conflicts on the client id and device id as well as on the device token
+async fn upsert_client(
+    transaction: &mut Transaction<'_, Postgres>,
+    tenant_id: &str,
+    id: &str,
+    client: Client,
+    metrics: Option<&Metrics>,
+) -> stores::Result<()> {
+    #[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+    pub struct ClientSelect {
+        pub id: String,
+        pub device_id: String,
+        pub device_token: String,
+        pub tenant_id: String,
+    }
+
+    // Lock the records in-case of fast concurrent requests
+    let start = Instant::now();
+    let query = "
+        SELECT
+        pg_advisory_xact_lock(abs(hashtext($1::text))),
+        pg_advisory_xact_lock(abs(hashtext($2::text)))
+    ";
+    sqlx::query(query)
+        .bind(id)
+        .bind(client.token.clone())
+        .execute(&mut *transaction)
+        .await?;
+    if let Some(metrics) = metrics {
+        metrics.postgres_query("create_client_pg_advisory_xact_lock", start);
+    }
+
+    // Pushes are built once per client, so all of its devices take the same
+    // payload shape
+    let query = "
+        SELECT EXISTS (
+            SELECT 1
+            FROM public.clients
+            WHERE id = $1
+                  AND tenant_id = $2
+                  AND device_id != $3
+                  AND always_raw != $4
+        )
+    ";
+    let start = Instant::now();
+    let mismatched = sqlx::query_scalar::<sqlx::postgres::Postgres, bool>(query)
+        .bind(id)
+        .bind(tenant_id)
+        .bind(client.device_id.clone())
+        .bind(client.always_raw)
+        .fetch_one(&mut *transaction)
+        .await?;
+    if let Some(metrics) = metrics {
+        metrics.postgres_query("create_client_select_always_raw", start);
+    }
+    if mismatched {
+        return Err(AlwaysRawMismatch(id.to_string()));
+    }
+
+    let query = "
+        SELECT *
+        FROM public.clients
+        WHERE (id = $1 AND device_id = $3)
+              OR device_token = $2
+        FOR UPDATE
+    ";
+    let start = Instant::now();
+    let existing_clients = sqlx::query_as::<sqlx::postgres::Postgres, ClientSelect>(query)
+        .bind(id)
+        .bind(client.token.clone())
+        .bind(client.device_id.clone())
+        .fetch_all(&mut *transaction)
+        .await?;
+    if let Some(metrics) = metrics {
+        metrics.postgres_query("create_client_select", start);
+    }
+
+    // Both the device's row and another client's row holding the token can
+    // match, the latter wins so that the device's row makes room for the token
+    let existing_client = existing_clients
+        .iter()
+        .find(|existing_client| existing_client.device_token == client.token)
+        .or(existing_clients.first())
+        .cloned();
+
+    #[cfg(feature = "functional_tests")]
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    if let Some(existing_client) = existing_client {
+        let same_device = existing_client.id == id && existing_client.device_id == client.device_id;
+        if same_device && existing_client.device_token != client.token {
+            let query = "
+                UPDATE public.clients
+                SET device_token = $2,
+                    push_type = $3,
+                    always_raw = $4,
+                    tenant_id = $5,
+                    apns_topic = $7,
+                    fcm_v1_slot = $8
+                WHERE id = $1
+                      AND device_id = $6
+            ";
+            let start = Instant::now();
+            sqlx::query(query)
+                .bind(id)
+                .bind(client.token)
+                .bind(client.push_type)
+                .bind(client.always_raw)
+                .bind(tenant_id)
+                .bind(client.device_id)
+                .bind(client.apns_topic)
+                .bind(client.fcm_v1_slot)
+                .execute(&mut *transaction)
+                .await?;
+            if let Some(metrics) = metrics {
+                metrics.postgres_query("create_client_update_device_token", start);
+            }
+        } else if existing_client.device_token == client.token && !same_device {
+            // The device token moved to another client, the previous client's
+            // notifications are only dropped once it has no devices left
+            if existing_client.id != id {
+                let query = "
+                    DELETE FROM public.notifications
+                    WHERE client_id = $1
+                          AND tenant_id = $2
+                          AND NOT EXISTS (
+                              SELECT 1
+                              FROM public.clients
+                              WHERE id = $1
+                                    AND tenant_id = $2
+                                    AND device_token != $3
+                          )
+                ";
+                let start = Instant::now();
+                sqlx::query(query)
+                    .bind(existing_client.id)
+                    .bind(existing_client.tenant_id)
+                    .bind(client.token.clone())
+                    .execute(&mut *transaction)
+                    .await?;
+                if let Some(metrics) = metrics {
+                    metrics.postgres_query("create_client_delete_notifications", start);
+                }
+            }
+
+            // Make room in case the target device was registered with another token
+            let query = "
+                DELETE FROM public.clients
+                WHERE id = $1
+                      AND device_id = $2
+                      AND device_token != $3
+            ";
+            let start = Instant::now();
+            sqlx::query(query)
+                .bind(id)
+                .bind(client.device_id.clone())
+                .bind(client.token.clone())
+                .execute(&mut *transaction)
+                .await?;
+            if let Some(metrics) = metrics {
+                metrics.postgres_query("create_client_delete_replaced_device", start);
+            }
+
+            let query = "
+                UPDATE public.clients
+                SET id = $2,
+                    push_type = $3,
+                    always_raw = $4,
+                    tenant_id = $5,
+                    device_id = $6,
+                    apns_topic = $7,
+                    fcm_v1_slot = $8
+                WHERE device_token = $1
+            ";
+            let start = Instant::now();
+            sqlx::query(query)
+                .bind(client.token)
+                .bind(id)
+                .bind(client.push_type)
+                .bind(client.always_raw)
+                .bind(tenant_id)
+                .bind(client.device_id)
+                .bind(client.apns_topic)
+                .bind(client.fcm_v1_slot)
+                .execute(&mut *transaction)
+                .await?;
+            if let Some(metrics) = metrics {
+                metrics.postgres_query("create_client_update_id", start);
+            }
+        } else {
+            let query = "
+                UPDATE public.clients
+                SET push_type = $2,
+                    always_raw = $3,
+                    tenant_id = $4,
+                    apns_topic = $6,
+                    fcm_v1_slot = $7
+                WHERE id = $1
+                      AND device_id = $5
+            ";
+            let start = Instant::now();
+            sqlx::query(query)
+                .bind(id)
+                .bind(client.push_type)
+                .bind(client.always_raw)
+                .bind(tenant_id)
+                .bind(client.device_id)
+                .bind(client.apns_topic)
+                .bind(client.fcm_v1_slot)
+                .execute(&mut *transaction)
+                .await?;
+            if let Some(metrics) = metrics {
+                metrics.postgres_query("create_client_update_id", start);
+            }
+        }
+    } else {
+        let start = Instant::now();
+        let mut insert_query = sqlx::QueryBuilder::new(
+            "INSERT INTO public.clients (id, tenant_id, push_type, device_token, always_raw, \
+             device_id, apns_topic, fcm_v1_slot)",
+        );
+        insert_query.push_values(
+            vec![(
+                id,
+                tenant_id,
+                client.push_type,
+                client.token,
+                client.always_raw,
+                client.device_id,
+                client.apns_topic,
+                client.fcm_v1_slot,
+            )],
+            |mut b, client| {
+                b.push_bind(client.0)
+                    .push_bind(client.1)
+                    .push_bind(client.2)
+                    .push_bind(client.3)
+                    .push_bind(client.4)
+                    .push_bind(client.5)
+                    .push_bind(client.6)
+                    .push_bind(client.7);
+            },
+        );
+        insert_query.build().execute(&mut *transaction).await?;
+        if let Some(metrics) = metrics {
+            metrics.postgres_query("create_client_insert", start);
+        }
+    }
+
+    Ok(())
 }
@@ -1,6 +1,7 @@
 pub mod client;
 pub mod notification;
 pub mod tenant;
+pub mod webhook;
 
 type Result<T> = std::result::Result<T, StoreError>;
 
@@ -12,4 +13,9 @@ pub enum StoreError {
     /// Not found error, params are entity name and identifier
     #[error("Cannot find {0} with specified identifier {1}")]
     NotFound(String, String),
+
+    /// The client's other devices were registered with a different
+    /// `always_raw`, param is the client id
+    #[error("Client {0} has devices registered with a different always_raw")]
+    AlwaysRawMismatch(String),
 }
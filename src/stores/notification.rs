@@ -38,6 +38,10 @@ pub trait NotificationStore {
         tenant_id: &str,
     ) -> stores::Result<Notification>;
     async fn delete_notification(&self, id: &str, tenant_id: &str) -> stores::Result<()>;
+    /// Deletes up to `limit` of the tenant's notifications, returning how many
+    /// were deleted
+    async fn delete_tenant_notifications(&self, tenant_id: &str, limit: i64)
+        -> stores::Result<u64>;
 }
 
 #[async_trait]
@@ -121,4 +125,22 @@ impl NotificationStore for sqlx::PgPool {
 
         Ok(())
     }
+
+    #[instrument(skip(self))]
+    async fn delete_tenant_notifications(
+        &self,
+        tenant_id: &str,
+        limit: i64,
+    ) -> stores::Result<u64> {
+        let res = sqlx::query(
+            "DELETE FROM public.notifications WHERE ctid IN (SELECT ctid FROM \
+             public.notifications WHERE tenant_id = $1 LIMIT $2)",
+        )
+        .bind(tenant_id)
+        .bind(limit)
+        .execute(self)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
 }
@@ -1,18 +1,19 @@
-#[cfg(not(feature = "multitenant"))]
-use {crate::config::Config, std::sync::Arc};
 use {
     crate::{
+        config::Config,
         error::{
             self,
-            Error::{self, InvalidTenantId, ProviderNotAvailable},
+            Error::{
+                self, FcmV1SlotNotFound, InvalidFcmV1Slot, InvalidTenantId, ProviderNotAvailable,
+                TenantPendingDeletion,
+            },
             Result,
         },
         providers::{
             apns::ApnsProvider,
-            fcm::FcmProvider,
             fcm_v1::FcmV1Provider,
-            Provider::{self, Apns, Fcm, FcmV1},
-            ProviderKind,
+            Provider::{self, Apns, FcmV1},
+            ProviderKind, PROVIDER_APNS, PROVIDER_FCM, PROVIDER_FCM_V1,
         },
     },
     async_trait::async_trait,
@@ -21,10 +22,18 @@ use {
     moka::future::Cache,
     reqwest::Client,
     serde::{Deserialize, Serialize},
-    sqlx::{Executor, PgPool},
+    sqlx::{types::Json, Executor, PgPool},
+    std::{
+        collections::BTreeMap,
+        fmt::{Display, Formatter},
+        sync::Arc,
+    },
+    tokio::sync::RwLock,
     tracing::{debug, instrument},
 };
 
+#[cfg(feature = "legacy_fcm")]
+use crate::providers::{fcm::FcmProvider, Provider::Fcm};
 #[cfg(any(debug_assertions, test))]
 use crate::providers::{noop::NoopProvider, Provider::Noop};
 
@@ -33,6 +42,11 @@ const APNS_TYPE_TOKEN: &str = "token";
 
 pub const DEFAULT_TENANT_ID: &str = "0000-0000-0000-0000";
 
+/// Slot of the tenant's `fcm_v1_credentials`, named slots are kept in
+/// `fcm_v1_slots`
+pub const DEFAULT_FCM_V1_SLOT: &str = "default";
+const MAX_FCM_V1_SLOT_LENGTH: usize = 64;
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, sqlx::Type)]
 #[sqlx(type_name = "apns_type")]
 #[sqlx(rename_all = "lowercase")]
@@ -86,27 +100,128 @@ pub struct Tenant {
 
     pub fcm_api_key: Option<String>,
     pub fcm_v1_credentials: Option<String>,
+    /// Named FCM v1 credentials for tenants with several Firebase projects,
+    /// keyed by slot
+    pub fcm_v1_slots: Json<BTreeMap<String, String>>,
 
     pub apns_type: Option<ApnsType>,
+    /// Default topic, used for clients that didn't register for a topic
     pub apns_topic: Option<String>,
+    /// Additional topics clients can register for
+    pub apns_topics: Vec<String>,
 
     // Certificate Based
     pub apns_certificate: Option<String>,
     pub apns_certificate_password: Option<String>,
+    /// Expiry of the certificate, read from the p12 when it's uploaded
+    pub apns_certificate_not_after: Option<DateTime<Utc>>,
+    /// When the tenant was warned the certificate is about to expire
+    pub apns_certificate_expiry_notified_at: Option<DateTime<Utc>>,
 
     // Token Based
     pub apns_pkcs8_pem: Option<String>,
     pub apns_key_id: Option<String>,
     pub apns_team_id: Option<String>,
 
-    // Suspension
-    pub suspended: bool,
-    pub suspended_reason: Option<String>,
+    /// Status of each set of credentials that has been suspended at least
+    /// once, read from `tenant_provider_status`
+    pub provider_status: Json<Vec<CredentialsStatus>>,
+
+    /// Set while the tenant's clients and notifications are being deleted,
+    /// the tenant can't be fetched or re-created until the deletion finishes
+    pub deletion_requested_at: Option<DateTime<Utc>>,
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Selects the tenant's columns along with the aggregated rows of
+/// `tenant_provider_status`, every query returning a [`Tenant`] must use it
+macro_rules! tenant_columns {
+    () => {
+        "tenants.*, COALESCE((SELECT json_agg(json_build_object('credentials', s.provider, \
+         'slot', NULLIF(s.slot, ''), 'suspended', s.suspended, 'suspended_reason', \
+         s.suspended_reason, 'updated_at', s.updated_at)) FROM public.tenant_provider_status s \
+         WHERE s.tenant_id = tenants.id), '[]'::json) AS provider_status"
+    };
+}
+
+/// A set of credentials stored on the tenant. Each is suspended separately so
+/// that invalid FCM credentials don't stop pushes to APNs
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialsKind {
+    Apns,
+    Fcm,
+    FcmV1,
+}
+
+impl CredentialsKind {
+    pub const ALL: [Self; 3] = [Self::Apns, Self::Fcm, Self::FcmV1];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Apns => PROVIDER_APNS,
+            Self::Fcm => PROVIDER_FCM,
+            Self::FcmV1 => PROVIDER_FCM_V1,
+        }
+    }
+}
+
+impl TryFrom<&str> for CredentialsKind {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            PROVIDER_APNS => Ok(Self::Apns),
+            PROVIDER_FCM => Ok(Self::Fcm),
+            PROVIDER_FCM_V1 => Ok(Self::FcmV1),
+            _ => Err(Error::ProviderNotFound(value.to_owned())),
+        }
+    }
+}
+
+impl Display for CredentialsKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CredentialsStatus {
+    pub credentials: CredentialsKind,
+    /// Named FCM v1 credentials slot, `None` for the default slot and the
+    /// other credentials
+    pub slot: Option<String>,
+    pub suspended: bool,
+    pub suspended_reason: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Slot of `tenant_provider_status`, named FCM v1 slots are suspended
+/// separately from the default slot
+fn status_slot(slot: Option<&str>) -> &str {
+    match slot {
+        None | Some(DEFAULT_FCM_V1_SLOT) => "",
+        Some(slot) => slot,
+    }
+}
+
+/// A provider the tenant can send to and whether the credentials it sends
+/// with are suspended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantProvider {
+    pub kind: ProviderKind,
+    pub credentials: Option<CredentialsKind>,
+    pub suspended_reason: Option<String>,
+}
+
+impl TenantProvider {
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_reason.is_some()
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct TenantUpdateParams {
     pub id: String,
@@ -122,7 +237,7 @@ pub struct TenantFcmV1UpdateParams {
     pub fcm_v1_credentials: String,
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 pub enum TenantApnsUpdateAuth {
     Certificate {
         apns_certificate: String,
@@ -141,7 +256,9 @@ pub struct TenantApnsUpdateParams {
 }
 
 impl Tenant {
-    pub fn providers(&self) -> Vec<ProviderKind> {
+    /// Providers the tenant has credentials for, clients of suspended providers
+    /// can still be registered
+    pub fn providers(&self) -> Vec<TenantProvider> {
         let mut supported = vec![];
 
         if self.get_apns_type().is_some() {
@@ -149,7 +266,10 @@ impl Tenant {
             supported.push(ProviderKind::ApnsSandbox);
         }
 
-        if self.fcm_api_key.is_some() || self.fcm_v1_credentials.is_some() {
+        if self.fcm_api_key.is_some()
+            || self.fcm_v1_credentials.is_some()
+            || !self.fcm_v1_slots.is_empty()
+        {
             supported.push(ProviderKind::Fcm);
         }
 
@@ -158,6 +278,94 @@ impl Tenant {
         supported.push(ProviderKind::Noop);
 
         supported
+            .into_iter()
+            .map(|kind| {
+                let credentials = self.credentials_kind(&kind);
+                TenantProvider {
+                    kind,
+                    credentials,
+                    suspended_reason: credentials
+                        .and_then(|credentials| self.suspended_reason(credentials))
+                        .map(ToString::to_string),
+                }
+            })
+            .collect()
+    }
+
+    pub fn provider_kinds(&self) -> Vec<ProviderKind> {
+        self.providers()
+            .into_iter()
+            .map(|provider| provider.kind)
+            .collect()
+    }
+
+    /// Credentials used to send to the provider, `None` when the provider
+    /// doesn't use any
+    pub fn credentials_kind(&self, provider: &ProviderKind) -> Option<CredentialsKind> {
+        match provider {
+            ProviderKind::Apns | ProviderKind::ApnsSandbox => Some(CredentialsKind::Apns),
+            // FCM v1 takes precedence, as in `Tenant::provider`. Each slot has its own
+            // status, see `Tenant::slot_suspended_reason`
+            ProviderKind::Fcm
+                if self.fcm_v1_credentials.is_some() || !self.fcm_v1_slots.is_empty() =>
+            {
+                Some(CredentialsKind::FcmV1)
+            }
+            ProviderKind::Fcm => Some(CredentialsKind::Fcm),
+            #[cfg(any(debug_assertions, test))]
+            ProviderKind::Noop => None,
+        }
+    }
+
+    /// Whether clients can register for the APNs topic, either the default
+    /// topic or one of the additional topics
+    pub fn has_apns_topic(&self, topic: &str) -> bool {
+        self.apns_topic.as_deref() == Some(topic)
+            || self
+                .apns_topics
+                .iter()
+                .any(|apns_topic| apns_topic == topic)
+    }
+
+    /// Whether FCM clients can only be sent to with the legacy FCM HTTP API,
+    /// which Google has shut down
+    pub fn is_legacy_fcm_only(&self) -> bool {
+        self.fcm_api_key.is_some()
+            && self.fcm_v1_credentials.is_none()
+            && self.fcm_v1_slots.is_empty()
+    }
+
+    /// Whether clients can register for the FCM v1 credentials slot
+    pub fn has_fcm_v1_slot(&self, slot: &str) -> bool {
+        if slot == DEFAULT_FCM_V1_SLOT {
+            self.fcm_v1_credentials.is_some()
+        } else {
+            self.fcm_v1_slots.contains_key(slot)
+        }
+    }
+
+    /// Reason the credentials were suspended, `None` when they aren't. For
+    /// FCM v1 this is the status of the default slot
+    pub fn suspended_reason(&self, credentials: CredentialsKind) -> Option<&str> {
+        self.slot_suspended_reason(credentials, None)
+    }
+
+    /// Reason the credentials of the FCM v1 slot were suspended, `None` names
+    /// the default slot and the other credentials
+    pub fn slot_suspended_reason(
+        &self,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+    ) -> Option<&str> {
+        let slot = status_slot(slot);
+        self.provider_status
+            .iter()
+            .find(|status| {
+                status.credentials == credentials
+                    && status.slot.as_deref().unwrap_or_default() == slot
+                    && status.suspended
+            })
+            .map(|status| status.suspended_reason.as_deref().unwrap_or_default())
     }
 
     pub fn get_apns_type(&self) -> Option<ApnsType> {
@@ -191,10 +399,11 @@ impl Tenant {
     pub async fn provider(
         &self,
         provider: &ProviderKind,
+        fcm_v1_slot: Option<&str>,
         http_client: Client,
         provider_cache: &Cache<String, Provider>,
     ) -> Result<Provider> {
-        if !self.providers().contains(provider) {
+        if !self.provider_kinds().contains(provider) {
             return Err(ProviderNotAvailable(provider.into()));
         }
 
@@ -250,16 +459,19 @@ impl Tenant {
                     None => Err(ProviderNotAvailable(provider.into())),
                 }
             }
-            ProviderKind::Fcm => match self.fcm_v1_credentials.clone() {
-                Some(fcm_v1_credentials) => {
-                    debug!("fcm v1 provider is matched");
-                    if let Some(provider) = provider_cache.get(&fcm_v1_credentials).await {
+            ProviderKind::Fcm => match self.fcm_v1_credentials(fcm_v1_slot)? {
+                Some((slot, fcm_v1_credentials)) => {
+                    debug!(%slot, "fcm v1 provider is matched");
+                    // Keyed by the credentials too so that updated credentials aren't
+                    // served from the cache
+                    let cache_key = format!("{}:{slot}:{fcm_v1_credentials}", self.id);
+                    if let Some(provider) = provider_cache.get(&cache_key).await {
                         return Ok(provider);
                     }
                     #[allow(clippy::match_single_binding)]
                     let fcm = FcmV1(
                         FcmV1Provider::new(
-                            serde_json::from_str(&fcm_v1_credentials)
+                            serde_json::from_str(fcm_v1_credentials)
                                 .map_err(Error::InternalFcmV1InvalidServiceAccountKey)?,
                             http_client,
                         )
@@ -270,11 +482,10 @@ impl Tenant {
                             _ => Error::BadFcmV1Credentials,
                         })?,
                     );
-                    provider_cache
-                        .insert(fcm_v1_credentials.clone(), fcm.clone())
-                        .await;
+                    provider_cache.insert(cache_key, fcm.clone()).await;
                     Ok(fcm)
                 }
+                #[cfg(feature = "legacy_fcm")]
                 None => match self.fcm_api_key.clone() {
                     Some(api_key) => {
                         debug!("fcm provider is matched");
@@ -283,6 +494,10 @@ impl Tenant {
                     }
                     None => Err(ProviderNotAvailable(provider.into())),
                 },
+                #[cfg(not(feature = "legacy_fcm"))]
+                None if self.fcm_api_key.is_some() => Err(Error::LegacyFcmDeprecated),
+                #[cfg(not(feature = "legacy_fcm"))]
+                None => Err(ProviderNotAvailable(provider.into())),
             },
             #[cfg(any(debug_assertions, test))]
             ProviderKind::Noop => {
@@ -291,12 +506,49 @@ impl Tenant {
             }
         }
     }
+
+    /// FCM v1 credentials of the slot along with the slot's name, `None` when
+    /// the tenant has no default FCM v1 credentials
+    fn fcm_v1_credentials<'a>(
+        &'a self,
+        slot: Option<&'a str>,
+    ) -> Result<Option<(&'a str, &'a str)>> {
+        match slot {
+            Some(slot) if slot != DEFAULT_FCM_V1_SLOT => self
+                .fcm_v1_slots
+                .get(slot)
+                .map(|credentials| Some((slot, credentials.as_str())))
+                // The slot was removed after the client registered for it
+                .ok_or_else(|| ProviderNotAvailable(format!("{PROVIDER_FCM_V1}/{slot}"))),
+            _ => Ok(self
+                .fcm_v1_credentials
+                .as_deref()
+                .map(|credentials| (DEFAULT_FCM_V1_SLOT, credentials))),
+        }
+    }
+}
+
+/// Slot names are used in URLs so they are limited to alphanumerics, `-` and
+/// `_`
+pub fn validate_fcm_v1_slot(slot: &str) -> Result<()> {
+    if slot.is_empty()
+        || slot.len() > MAX_FCM_V1_SLOT_LENGTH
+        || !slot
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(InvalidFcmV1Slot(slot.to_string()));
+    }
+
+    Ok(())
 }
 
 #[async_trait]
 pub trait TenantStore {
     async fn get_tenant(&self, id: &str) -> Result<Tenant>;
     async fn delete_tenant(&self, id: &str) -> Result<()>;
+    async fn mark_tenant_pending_deletion(&self, id: &str) -> Result<()>;
+    async fn get_tenants_pending_deletion(&self) -> Result<Vec<String>>;
     async fn create_tenant(&self, params: TenantUpdateParams) -> Result<Tenant>;
     async fn update_tenant_fcm(&self, id: &str, params: TenantFcmUpdateParams) -> Result<Tenant>;
     async fn update_tenant_delete_fcm(&self, id: &str) -> Result<Tenant>;
@@ -306,24 +558,86 @@ pub trait TenantStore {
         params: TenantFcmV1UpdateParams,
     ) -> Result<Tenant>;
     async fn update_tenant_delete_fcm_v1(&self, id: &str) -> Result<Tenant>;
+    /// Adds or replaces the credentials of a named slot
+    async fn update_tenant_fcm_v1_slot(
+        &self,
+        id: &str,
+        slot: &str,
+        params: TenantFcmV1UpdateParams,
+    ) -> Result<Tenant>;
+    async fn update_tenant_delete_fcm_v1_slot(&self, id: &str, slot: &str) -> Result<Tenant>;
     async fn update_tenant_apns(&self, id: &str, params: TenantApnsUpdateParams) -> Result<Tenant>;
+    /// Replaces the additional APNs topics, the default topic is unchanged
+    async fn update_tenant_apns_topics(&self, id: &str, topics: Vec<String>) -> Result<Tenant>;
     async fn update_tenant_apns_auth(
         &self,
         id: &str,
         params: TenantApnsUpdateAuth,
     ) -> Result<Tenant>;
     async fn update_tenant_delete_apns(&self, id: &str) -> Result<Tenant>;
-    async fn suspend_tenant(&self, id: &str, reason: &str) -> Result<()>;
-    async fn unsuspend_tenant(&self, id: &str) -> Result<()>;
+    /// Records when the tenant's APNs certificate expires, resetting the
+    /// expiry warning
+    async fn update_tenant_apns_certificate_expiry(
+        &self,
+        id: &str,
+        not_after: DateTime<Utc>,
+    ) -> Result<Tenant>;
+    /// Tenants using an APNs certificate that expires before the given time
+    async fn get_tenants_with_apns_certificate_expiring(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<Tenant>>;
+    /// Tenants using an APNs certificate uploaded before its expiry was
+    /// recorded
+    async fn get_tenants_missing_apns_certificate_expiry(&self) -> Result<Vec<Tenant>>;
+    /// Records the expiry of the given certificate, unless it has been
+    /// replaced or its expiry recorded in the meantime
+    async fn backfill_tenant_apns_certificate_expiry(
+        &self,
+        id: &str,
+        apns_certificate: &str,
+        not_after: DateTime<Utc>,
+    ) -> Result<()>;
+    /// Returns whether the tenant hadn't been notified yet, so that only one
+    /// instance sends the warning
+    async fn mark_tenant_apns_certificate_expiry_notified(&self, id: &str) -> Result<bool>;
+    /// Stops pushes sent with the credentials, pushes to other providers are
+    /// unaffected. The slot names the FCM v1 credentials slot, `None` for the
+    /// default slot and the other credentials
+    async fn suspend_tenant_credentials(
+        &self,
+        id: &str,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+        reason: &str,
+    ) -> Result<()>;
+    /// Like [`TenantStore::suspend_tenant_credentials`] but leaves suspended
+    /// credentials untouched, returning whether they were suspended by this
+    /// call so that only one instance reports the suspension
+    async fn suspend_tenant_credentials_once(
+        &self,
+        id: &str,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+        reason: &str,
+    ) -> Result<bool>;
+    async fn unsuspend_tenant_credentials(
+        &self,
+        id: &str,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+    ) -> Result<()>;
 }
 
 #[async_trait]
 impl TenantStore for PgPool {
     #[instrument(skip(self))]
     async fn get_tenant(&self, id: &str) -> Result<Tenant> {
-        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(
-            "SELECT * FROM public.tenants WHERE id = $1",
-        )
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
+            "SELECT ",
+            tenant_columns!(),
+            " FROM public.tenants WHERE id = $1 AND deletion_requested_at IS NULL"
+        ))
         .bind(id)
         .fetch_one(self)
         .await;
@@ -346,28 +660,67 @@ impl TenantStore for PgPool {
         Ok(())
     }
 
+    #[instrument(skip(self))]
+    async fn mark_tenant_pending_deletion(&self, id: &str) -> Result<()> {
+        let res = sqlx::query(
+            "UPDATE public.tenants SET deletion_requested_at = COALESCE(deletion_requested_at, \
+             NOW()), updated_at = NOW() WHERE id = $1",
+        )
+        .bind(id)
+        .execute(self)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(InvalidTenantId(id.into()));
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_tenants_pending_deletion(&self) -> Result<Vec<String>> {
+        let res = sqlx::query_scalar::<sqlx::postgres::Postgres, String>(
+            "SELECT id FROM public.tenants WHERE deletion_requested_at IS NOT NULL ORDER BY \
+             deletion_requested_at",
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(res)
+    }
+
     #[instrument(skip(self))]
     async fn create_tenant(&self, params: TenantUpdateParams) -> Result<Tenant> {
-        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(
+        // The conflict update is skipped for tenants pending deletion so no row is
+        // returned for them
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
             "INSERT INTO public.tenants (id)
             VALUES ($1)
             ON CONFLICT (id)
             DO UPDATE SET updated_at = NOW()
-            RETURNING *",
-        )
-        .bind(params.id)
+            WHERE tenants.deletion_requested_at IS NULL
+            RETURNING ",
+            tenant_columns!()
+        ))
+        .bind(&params.id)
         .fetch_one(self)
-        .await?;
+        .await;
 
-        Ok(res)
+        match res {
+            Err(sqlx::Error::RowNotFound) => Err(TenantPendingDeletion(params.id)),
+            Err(e) => Err(e.into()),
+            Ok(row) => Ok(row),
+        }
     }
 
-    #[instrument(skip(self))]
+    #[instrument(skip(self, params))]
     async fn update_tenant_fcm(&self, id: &str, params: TenantFcmUpdateParams) -> Result<Tenant> {
-        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
             "UPDATE public.tenants SET fcm_api_key = $2, updated_at = NOW() WHERE id = $1 \
-             RETURNING *;",
-        )
+             RETURNING ",
+            tenant_columns!(),
+            ";"
+        ))
         .bind(id)
         .bind(params.fcm_api_key)
         .fetch_one(self)
@@ -378,13 +731,15 @@ impl TenantStore for PgPool {
 
     #[instrument(skip(self))]
     async fn update_tenant_delete_fcm(&self, id: &str) -> Result<Tenant> {
-        let query = "
+        let query = concat!(
+            "
             UPDATE public.tenants
             SET updated_at = NOW(),
                 fcm_api_key = NULL
             WHERE id = $1
-            RETURNING *
-        ";
+            RETURNING ",
+            tenant_columns!()
+        );
         let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(query)
             .bind(id)
             .fetch_one(self)
@@ -393,16 +748,18 @@ impl TenantStore for PgPool {
         Ok(res)
     }
 
-    #[instrument(skip(self))]
+    #[instrument(skip(self, params))]
     async fn update_tenant_fcm_v1(
         &self,
         id: &str,
         params: TenantFcmV1UpdateParams,
     ) -> Result<Tenant> {
-        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
             "UPDATE public.tenants SET fcm_v1_credentials = $2, updated_at = NOW() WHERE id = $1 \
-             RETURNING *;",
-        )
+             RETURNING ",
+            tenant_columns!(),
+            ";"
+        ))
         .bind(id)
         .bind(params.fcm_v1_credentials)
         .fetch_one(self)
@@ -413,13 +770,15 @@ impl TenantStore for PgPool {
 
     #[instrument(skip(self))]
     async fn update_tenant_delete_fcm_v1(&self, id: &str) -> Result<Tenant> {
-        let query = "
+        let query = concat!(
+            "
             UPDATE public.tenants
             SET updated_at = NOW(),
                 fcm_v1_credentials = NULL
             WHERE id = $1
-            RETURNING *
-        ";
+            RETURNING ",
+            tenant_columns!()
+        );
         let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(query)
             .bind(id)
             .fetch_one(self)
@@ -428,12 +787,59 @@ impl TenantStore for PgPool {
         Ok(res)
     }
 
+    #[instrument(skip(self, params))]
+    async fn update_tenant_fcm_v1_slot(
+        &self,
+        id: &str,
+        slot: &str,
+        params: TenantFcmV1UpdateParams,
+    ) -> Result<Tenant> {
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
+            "UPDATE public.tenants SET fcm_v1_slots = fcm_v1_slots || jsonb_build_object($2::text, \
+             $3::text), updated_at = NOW() WHERE id = $1 RETURNING ",
+            tenant_columns!(),
+            ";"
+        ))
+        .bind(id)
+        .bind(slot)
+        .bind(params.fcm_v1_credentials)
+        .fetch_one(self)
+        .await?;
+
+        Ok(res)
+    }
+
+    #[instrument(skip(self))]
+    async fn update_tenant_delete_fcm_v1_slot(&self, id: &str, slot: &str) -> Result<Tenant> {
+        let query = concat!(
+            "
+            UPDATE public.tenants
+            SET updated_at = NOW(),
+                fcm_v1_slots = fcm_v1_slots - $2::text
+            WHERE id = $1 AND fcm_v1_slots ? $2::text
+            RETURNING ",
+            tenant_columns!()
+        );
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(query)
+            .bind(id)
+            .bind(slot)
+            .fetch_one(self)
+            .await;
+
+        match res {
+            Err(sqlx::Error::RowNotFound) => Err(FcmV1SlotNotFound(slot.to_string())),
+            res => Ok(res?),
+        }
+    }
+
     #[instrument(skip(self))]
     async fn update_tenant_apns(&self, id: &str, params: TenantApnsUpdateParams) -> Result<Tenant> {
-        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
             "UPDATE public.tenants SET apns_topic = $2, updated_at = NOW() WHERE id = $1 \
-             RETURNING *;",
-        )
+             RETURNING ",
+            tenant_columns!(),
+            ";"
+        ))
         .bind(id)
         .bind(params.apns_topic)
         .fetch_one(self)
@@ -443,6 +849,21 @@ impl TenantStore for PgPool {
     }
 
     #[instrument(skip(self))]
+    async fn update_tenant_apns_topics(&self, id: &str, topics: Vec<String>) -> Result<Tenant> {
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
+            "UPDATE public.tenants SET apns_topics = $2, updated_at = NOW() WHERE id = $1 \
+             RETURNING ",
+            tenant_columns!()
+        ))
+        .bind(id)
+        .bind(topics)
+        .fetch_one(self)
+        .await?;
+
+        Ok(res)
+    }
+
+    #[instrument(skip(self, params))]
     async fn update_tenant_apns_auth(
         &self,
         id: &str,
@@ -452,11 +873,14 @@ impl TenantStore for PgPool {
             TenantApnsUpdateAuth::Certificate {
                 apns_certificate,
                 apns_certificate_password,
-            } => sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(
+            } => sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
                 "UPDATE public.tenants SET apns_type = 'certificate'::apns_type, apns_certificate \
-                 = $2, apns_certificate_password = $3, apns_pkcs8_pem = null, apns_team_id = \
-                 null, apns_key_id = null, updated_at = NOW() WHERE id = $1 RETURNING *;",
-            )
+                 = $2, apns_certificate_password = $3, apns_certificate_not_after = null, \
+                 apns_certificate_expiry_notified_at = null, apns_pkcs8_pem = null, apns_team_id \
+                 = null, apns_key_id = null, updated_at = NOW() WHERE id = $1 RETURNING ",
+                tenant_columns!(),
+                ";"
+            ))
             .bind(id)
             .bind(apns_certificate)
             .bind(apns_certificate_password),
@@ -464,11 +888,15 @@ impl TenantStore for PgPool {
                 apns_pkcs8_pem,
                 apns_team_id,
                 apns_key_id,
-            } => sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(
+            } => sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
                 "UPDATE public.tenants SET apns_type = 'token'::apns_type, apns_pkcs8_pem = $2, \
                  apns_team_id = $3, apns_key_id = $4, apns_certificate = null, \
-                 apns_certificate_password = null, updated_at = NOW() WHERE id = $1 RETURNING *;",
-            )
+                 apns_certificate_password = null, apns_certificate_not_after = null, \
+                 apns_certificate_expiry_notified_at = null, updated_at = NOW() WHERE id = $1 \
+                 RETURNING ",
+                tenant_columns!(),
+                ";"
+            ))
             .bind(id)
             .bind(apns_pkcs8_pem)
             .bind(apns_team_id)
@@ -482,19 +910,24 @@ impl TenantStore for PgPool {
 
     #[instrument(skip(self))]
     async fn update_tenant_delete_apns(&self, id: &str) -> Result<Tenant> {
-        let query = "
+        let query = concat!(
+            "
             UPDATE public.tenants
             SET updated_at = NOW(),
                 apns_topic = NULL,
+                apns_topics = '{}',
                 apns_type = NULL,
                 apns_certificate = NULL,
                 apns_certificate_password = NULL,
+                apns_certificate_not_after = NULL,
+                apns_certificate_expiry_notified_at = NULL,
                 apns_pkcs8_pem = NULL,
                 apns_team_id = NULL,
                 apns_key_id = NULL
             WHERE id = $1
-            RETURNING *
-        ";
+            RETURNING ",
+            tenant_columns!()
+        );
         let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(query)
             .bind(id)
             .fetch_one(self)
@@ -504,71 +937,255 @@ impl TenantStore for PgPool {
     }
 
     #[instrument(skip(self))]
-    async fn suspend_tenant(&self, id: &str, reason: &str) -> Result<()> {
-        let mut query_builder = sqlx::QueryBuilder::new(
-            "UPDATE public.tenants SET suspended = true, updated_at = NOW(), suspended_reason =",
-        );
-        query_builder.push_bind(reason);
-        query_builder.push(" WHERE id = ");
-        query_builder.push_bind(id);
-        let query = query_builder.build();
+    async fn update_tenant_apns_certificate_expiry(
+        &self,
+        id: &str,
+        not_after: DateTime<Utc>,
+    ) -> Result<Tenant> {
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
+            "UPDATE public.tenants SET apns_certificate_not_after = $2, \
+             apns_certificate_expiry_notified_at = null, updated_at = NOW() WHERE id = $1 \
+             RETURNING ",
+            tenant_columns!()
+        ))
+        .bind(id)
+        .bind(not_after)
+        .fetch_one(self)
+        .await?;
 
-        self.execute(query).await?;
+        Ok(res)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_tenants_with_apns_certificate_expiring(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<Tenant>> {
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
+            "SELECT ",
+            tenant_columns!(),
+            " FROM public.tenants WHERE apns_type = 'certificate'::apns_type AND \
+             apns_certificate_not_after < $1 AND deletion_requested_at IS NULL ORDER BY \
+             apns_certificate_not_after"
+        ))
+        .bind(before)
+        .fetch_all(self)
+        .await?;
+
+        Ok(res)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_tenants_missing_apns_certificate_expiry(&self) -> Result<Vec<Tenant>> {
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Tenant>(concat!(
+            "SELECT ",
+            tenant_columns!(),
+            " FROM public.tenants WHERE apns_type = 'certificate'::apns_type AND \
+             apns_certificate IS NOT NULL AND apns_certificate_not_after IS NULL AND \
+             deletion_requested_at IS NULL"
+        ))
+        .fetch_all(self)
+        .await?;
+
+        Ok(res)
+    }
+
+    #[instrument(skip(self, apns_certificate))]
+    async fn backfill_tenant_apns_certificate_expiry(
+        &self,
+        id: &str,
+        apns_certificate: &str,
+        not_after: DateTime<Utc>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE public.tenants SET apns_certificate_not_after = $3 WHERE id = $1 AND \
+             apns_certificate = $2 AND apns_certificate_not_after IS NULL",
+        )
+        .bind(id)
+        .bind(apns_certificate)
+        .bind(not_after)
+        .execute(self)
+        .await?;
 
         Ok(())
     }
 
     #[instrument(skip(self))]
-    async fn unsuspend_tenant(&self, id: &str) -> Result<()> {
-        let mut query_builder = sqlx::QueryBuilder::new(
-            "UPDATE public.tenants SET suspended = false, updated_at = NOW(), suspended_reason = \
-             null WHERE id = ",
-        );
-        query_builder.push_bind(id);
-        let query = query_builder.build();
+    async fn mark_tenant_apns_certificate_expiry_notified(&self, id: &str) -> Result<bool> {
+        let res = sqlx::query(
+            "UPDATE public.tenants SET apns_certificate_expiry_notified_at = NOW() WHERE id = $1 \
+             AND apns_certificate_expiry_notified_at IS NULL",
+        )
+        .bind(id)
+        .execute(self)
+        .await?;
 
-        self.execute(query).await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[instrument(skip(self))]
+    async fn suspend_tenant_credentials(
+        &self,
+        id: &str,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+        reason: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO public.tenant_provider_status (tenant_id, provider, slot, suspended, \
+             suspended_reason) VALUES ($1, $2, $3, true, $4) ON CONFLICT (tenant_id, provider, \
+             slot) DO UPDATE SET suspended = true, suspended_reason = $4, updated_at = NOW()",
+        )
+        .bind(id)
+        .bind(credentials.as_str())
+        .bind(status_slot(slot))
+        .bind(reason)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn suspend_tenant_credentials_once(
+        &self,
+        id: &str,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+        reason: &str,
+    ) -> Result<bool> {
+        let res = sqlx::query(
+            "INSERT INTO public.tenant_provider_status (tenant_id, provider, slot, suspended, \
+             suspended_reason) VALUES ($1, $2, $3, true, $4) ON CONFLICT (tenant_id, provider, \
+             slot) DO UPDATE SET suspended = true, suspended_reason = $4, updated_at = NOW() \
+             WHERE NOT tenant_provider_status.suspended",
+        )
+        .bind(id)
+        .bind(credentials.as_str())
+        .bind(status_slot(slot))
+        .bind(reason)
+        .execute(self)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[instrument(skip(self))]
+    async fn unsuspend_tenant_credentials(
+        &self,
+        id: &str,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE public.tenant_provider_status SET suspended = false, suspended_reason = null, \
+             updated_at = NOW() WHERE tenant_id = $1 AND provider = $2 AND slot = $3",
+        )
+        .bind(id)
+        .bind(credentials.as_str())
+        .bind(status_slot(slot))
+        .execute(self)
+        .await?;
 
         Ok(())
     }
 }
 
-#[cfg(not(feature = "multitenant"))]
-pub struct DefaultTenantStore(Tenant);
+/// Tenant of single-tenant mode, its credentials come from the config and can
+/// be replaced when they are reloaded
+pub struct DefaultTenantStore(RwLock<Tenant>);
 
-#[cfg(not(feature = "multitenant"))]
 impl DefaultTenantStore {
     pub fn new(config: Arc<Config>) -> Result<DefaultTenantStore> {
-        Ok(DefaultTenantStore(Tenant {
+        Ok(DefaultTenantStore(RwLock::new(Self::tenant_from_config(
+            &config,
+        ))))
+    }
+
+    pub fn tenant_from_config(config: &Config) -> Tenant {
+        Tenant {
             id: DEFAULT_TENANT_ID.to_string(),
             fcm_api_key: config.fcm_api_key.clone(),
             fcm_v1_credentials: config.fcm_v1_credentials.clone(),
+            fcm_v1_slots: Json(BTreeMap::new()),
             apns_type: config.apns_type,
             apns_topic: config.apns_topic.clone(),
+            apns_topics: vec![],
             apns_certificate: config.apns_certificate.clone(),
             apns_certificate_password: config.apns_certificate_password.clone(),
+            apns_certificate_not_after: None,
+            apns_certificate_expiry_notified_at: None,
             apns_pkcs8_pem: config.apns_pkcs8_pem.clone(),
             apns_key_id: config.apns_key_id.clone(),
             apns_team_id: config.apns_team_id.clone(),
-            suspended: false,
-            suspended_reason: None,
+            provider_status: Json(vec![]),
+            deletion_requested_at: None,
             created_at: Default::default(),
             updated_at: Default::default(),
-        }))
+        }
+    }
+
+    /// Swaps in the tenant with reloaded credentials, returns `false` when
+    /// they are unchanged. Suspensions are kept until the credentials change
+    pub async fn replace_tenant(&self, mut tenant: Tenant) -> bool {
+        let mut current = self.0.write().await;
+        let provider_status = std::mem::take(&mut tenant.provider_status);
+        tenant.provider_status = current.provider_status.clone();
+        if *current == tenant {
+            return false;
+        }
+
+        tenant.provider_status = provider_status;
+        *current = tenant;
+        true
+    }
+
+    /// Suspends the credentials in memory, returns `false` when they already
+    /// were
+    async fn suspend(
+        &self,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+        reason: &str,
+    ) -> bool {
+        let mut tenant = self.0.write().await;
+        if tenant.slot_suspended_reason(credentials, slot).is_some() {
+            return false;
+        }
+
+        let slot = Some(status_slot(slot)).filter(|slot| !slot.is_empty());
+        tenant
+            .provider_status
+            .retain(|status| status.credentials != credentials || status.slot.as_deref() != slot);
+        tenant.provider_status.push(CredentialsStatus {
+            credentials,
+            slot: slot.map(ToOwned::to_owned),
+            suspended: true,
+            suspended_reason: Some(reason.to_string()),
+            updated_at: Utc::now(),
+        });
+        true
     }
 }
 
 #[async_trait]
-#[cfg(not(feature = "multitenant"))]
 impl TenantStore for DefaultTenantStore {
     async fn get_tenant(&self, _id: &str) -> Result<Tenant> {
-        Ok(self.0.clone())
+        Ok(self.0.read().await.clone())
     }
 
     async fn delete_tenant(&self, _id: &str) -> Result<()> {
         panic!("Shouldn't have run in single tenant mode")
     }
 
+    async fn mark_tenant_pending_deletion(&self, _id: &str) -> Result<()> {
+        panic!("Shouldn't have run in single tenant mode")
+    }
+
+    async fn get_tenants_pending_deletion(&self) -> Result<Vec<String>> {
+        panic!("Shouldn't have run in single tenant mode")
+    }
+
     async fn create_tenant(&self, _params: TenantUpdateParams) -> Result<Tenant> {
         panic!("Shouldn't have run in single tenant mode")
     }
@@ -593,6 +1210,19 @@ impl TenantStore for DefaultTenantStore {
         panic!("Shouldn't have run in single tenant mode")
     }
 
+    async fn update_tenant_fcm_v1_slot(
+        &self,
+        _id: &str,
+        _slot: &str,
+        _params: TenantFcmV1UpdateParams,
+    ) -> Result<Tenant> {
+        panic!("Shouldn't have run in single tenant mode")
+    }
+
+    async fn update_tenant_delete_fcm_v1_slot(&self, _id: &str, _slot: &str) -> Result<Tenant> {
+        panic!("Shouldn't have run in single tenant mode")
+    }
+
     async fn update_tenant_apns(
         &self,
         _id: &str,
@@ -601,6 +1231,10 @@ impl TenantStore for DefaultTenantStore {
         panic!("Shouldn't have run in single tenant mode")
     }
 
+    async fn update_tenant_apns_topics(&self, _id: &str, _topics: Vec<String>) -> Result<Tenant> {
+        panic!("Shouldn't have run in single tenant mode")
+    }
+
     async fn update_tenant_apns_auth(
         &self,
         _id: &str,
@@ -613,11 +1247,72 @@ impl TenantStore for DefaultTenantStore {
         panic!("Shouldn't have run in single tenant mode")
     }
 
-    async fn suspend_tenant(&self, _id: &str, _reason: &str) -> Result<()> {
+    async fn update_tenant_apns_certificate_expiry(
+        &self,
+        _id: &str,
+        _not_after: DateTime<Utc>,
+    ) -> Result<Tenant> {
+        panic!("Shouldn't have run in single tenant mode")
+    }
+
+    async fn get_tenants_with_apns_certificate_expiring(
+        &self,
+        _before: DateTime<Utc>,
+    ) -> Result<Vec<Tenant>> {
+        panic!("Shouldn't have run in single tenant mode")
+    }
+
+    async fn get_tenants_missing_apns_certificate_expiry(&self) -> Result<Vec<Tenant>> {
+        panic!("Shouldn't have run in single tenant mode")
+    }
+
+    async fn backfill_tenant_apns_certificate_expiry(
+        &self,
+        _id: &str,
+        _apns_certificate: &str,
+        _not_after: DateTime<Utc>,
+    ) -> Result<()> {
         panic!("Shouldn't have run in single tenant mode")
     }
 
-    async fn unsuspend_tenant(&self, _id: &str) -> Result<()> {
+    async fn mark_tenant_apns_certificate_expiry_notified(&self, _id: &str) -> Result<bool> {
         panic!("Shouldn't have run in single tenant mode")
     }
+
+    /// Suspended until the credentials are reloaded or the server restarts
+    async fn suspend_tenant_credentials(
+        &self,
+        _id: &str,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+        reason: &str,
+    ) -> Result<()> {
+        self.suspend(credentials, slot, reason).await;
+        Ok(())
+    }
+
+    async fn suspend_tenant_credentials_once(
+        &self,
+        _id: &str,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+        reason: &str,
+    ) -> Result<bool> {
+        Ok(self.suspend(credentials, slot, reason).await)
+    }
+
+    async fn unsuspend_tenant_credentials(
+        &self,
+        _id: &str,
+        credentials: CredentialsKind,
+        slot: Option<&str>,
+    ) -> Result<()> {
+        let slot = Some(status_slot(slot)).filter(|slot| !slot.is_empty());
+        self.0
+            .write()
+            .await
+            .provider_status
+            .retain(|status| status.credentials != credentials || status.slot.as_deref() != slot);
+        Ok(())
+    }
 }
@@ -0,0 +1,222 @@
+use {
+    crate::error::{Error::WebhookNotFound, Result},
+    async_trait::async_trait,
+    chrono::{DateTime, Utc},
+    serde::{Deserialize, Serialize},
+    sqlx::{types::Json, PgPool},
+    std::time::Duration,
+    tracing::instrument,
+};
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub id: String,
+    pub tenant_id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A webhook event claimed for delivery along with where to deliver it
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub event: String,
+    pub payload: Json<serde_json::Value>,
+    /// Attempts including the current one
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
+
+#[async_trait]
+pub trait WebhookStore {
+    async fn create_webhook(
+        &self,
+        tenant_id: &str,
+        url: &str,
+        secret: &str,
+        events: &[String],
+    ) -> Result<Webhook>;
+    async fn get_webhooks(&self, tenant_id: &str) -> Result<Vec<Webhook>>;
+    async fn delete_webhook(&self, tenant_id: &str, id: &str) -> Result<()>;
+    /// Queues the event for every webhook of the tenant subscribed to it,
+    /// returns the number of deliveries queued
+    async fn enqueue_webhook_event(
+        &self,
+        tenant_id: &str,
+        event: &str,
+        payload: &serde_json::Value,
+    ) -> Result<u64>;
+    /// Claims deliveries that are due, a claimed delivery isn't returned again
+    /// until the lease expires
+    async fn claim_webhook_deliveries(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<WebhookDelivery>>;
+    /// Claims the tenant's deliveries that are due, like
+    /// [`WebhookStore::claim_webhook_deliveries`]
+    async fn claim_tenant_webhook_deliveries(
+        &self,
+        tenant_id: &str,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<WebhookDelivery>>;
+    /// Removes a delivery that succeeded or won't be retried
+    async fn delete_webhook_delivery(&self, id: &str) -> Result<()>;
+    async fn retry_webhook_delivery(&self, id: &str, delay: Duration) -> Result<()>;
+}
+
+#[async_trait]
+impl WebhookStore for PgPool {
+    #[instrument(skip(self, secret))]
+    async fn create_webhook(
+        &self,
+        tenant_id: &str,
+        url: &str,
+        secret: &str,
+        events: &[String],
+    ) -> Result<Webhook> {
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Webhook>(
+            "INSERT INTO public.tenant_webhooks (tenant_id, url, secret, events) VALUES ($1, $2, \
+             $3, $4) RETURNING id, tenant_id, url, events, created_at",
+        )
+        .bind(tenant_id)
+        .bind(url)
+        .bind(secret)
+        .bind(events.to_vec())
+        .fetch_one(self)
+        .await?;
+
+        Ok(res)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_webhooks(&self, tenant_id: &str) -> Result<Vec<Webhook>> {
+        let res = sqlx::query_as::<sqlx::postgres::Postgres, Webhook>(
+            "SELECT id, tenant_id, url, events, created_at FROM public.tenant_webhooks WHERE \
+             tenant_id = $1 ORDER BY created_at",
+        )
+        .bind(tenant_id)
+        .fetch_all(self)
+        .await?;
+
+        Ok(res)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_webhook(&self, tenant_id: &str, id: &str) -> Result<()> {
+        let res =
+            sqlx::query("DELETE FROM public.tenant_webhooks WHERE id = $1 AND tenant_id = $2")
+                .bind(id)
+                .bind(tenant_id)
+                .execute(self)
+                .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(WebhookNotFound(id.to_string()));
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, payload))]
+    async fn enqueue_webhook_event(
+        &self,
+        tenant_id: &str,
+        event: &str,
+        payload: &serde_json::Value,
+    ) -> Result<u64> {
+        let res = sqlx::query(
+            "INSERT INTO public.tenant_webhook_deliveries (webhook_id, event, payload) SELECT id, \
+             $2, $3 FROM public.tenant_webhooks WHERE tenant_id = $1 AND $2 = ANY(events)",
+        )
+        .bind(tenant_id)
+        .bind(event)
+        .bind(Json(payload))
+        .execute(self)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    #[instrument(skip(self))]
+    async fn claim_webhook_deliveries(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<WebhookDelivery>> {
+        claim_deliveries(self, None, limit, lease).await
+    }
+
+    #[instrument(skip(self))]
+    async fn claim_tenant_webhook_deliveries(
+        &self,
+        tenant_id: &str,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<WebhookDelivery>> {
+        claim_deliveries(self, Some(tenant_id), limit, lease).await
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_webhook_delivery(&self, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM public.tenant_webhook_deliveries WHERE id = $1")
+            .bind(id)
+            .execute(self)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn retry_webhook_delivery(&self, id: &str, delay: Duration) -> Result<()> {
+        sqlx::query(
+            "UPDATE public.tenant_webhook_deliveries SET next_attempt_at = NOW() + \
+             make_interval(secs => $2) WHERE id = $1",
+        )
+        .bind(id)
+        .bind(delay.as_secs_f64())
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Claims the due deliveries, of all tenants unless `tenant_id` is set
+async fn claim_deliveries(
+    pool: &PgPool,
+    tenant_id: Option<&str>,
+    limit: i64,
+    lease: Duration,
+) -> Result<Vec<WebhookDelivery>> {
+    let res = sqlx::query_as::<sqlx::postgres::Postgres, WebhookDelivery>(
+        "
+        UPDATE public.tenant_webhook_deliveries AS deliveries
+        SET attempts = deliveries.attempts + 1,
+            next_attempt_at = NOW() + make_interval(secs => $2)
+        FROM public.tenant_webhooks AS webhooks
+        WHERE webhooks.id = deliveries.webhook_id
+            AND deliveries.id IN (
+                SELECT id FROM public.tenant_webhook_deliveries
+                WHERE next_attempt_at <= NOW()
+                    AND ($3::varchar IS NULL OR webhook_id IN (
+                        SELECT id FROM public.tenant_webhooks WHERE tenant_id = $3
+                    ))
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+        RETURNING deliveries.id, deliveries.event, deliveries.payload, deliveries.attempts,
+            webhooks.url, webhooks.secret",
+    )
+    .bind(limit)
+    .bind(lease.as_secs_f64())
+    .bind(tenant_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(res)
+}
@@ -0,0 +1,461 @@
+//! Export and import of a tenant's configuration and clients, used to move a
+//! tenant between deployments.
+//!
+//! Credentials can optionally be encrypted to the X25519 public key of the
+//! deployment the archive is destined for. The key is agreed with an ephemeral
+//! X25519 key pair and the credentials sealed with AES-256-GCM, using the
+//! tenant id as additional data so they can't be swapped into another archive.
+//! Notifications are not exported as they are only kept for deduplication.
+use {
+    crate::{
+        error::{
+            Error::{self, InvalidArchiveKey, InvalidTenantArchive, UnsupportedArchiveVersion},
+            Result,
+        },
+        handlers::update_apns::p12_not_after,
+        log::prelude::*,
+        metrics::Metrics,
+        stores::{
+            client::{Client, ClientStore},
+            tenant::{
+                ApnsType, Tenant, TenantApnsUpdateAuth, TenantApnsUpdateParams,
+                TenantFcmUpdateParams, TenantFcmV1UpdateParams, TenantStore, TenantUpdateParams,
+            },
+        },
+    },
+    base64::{engine::general_purpose::STANDARD, Engine as _},
+    chrono::{DateTime, Utc},
+    openssl::{
+        derive::Deriver,
+        pkey::{HasPrivate, HasPublic, Id, PKey, PKeyRef, Private, Public},
+        rand::rand_bytes,
+        sha::Sha256,
+        symm::{decrypt_aead, encrypt_aead, Cipher},
+    },
+    serde::{Deserialize, Serialize},
+    std::collections::BTreeMap,
+};
+
+/// Version written to new archives, bumped whenever the format changes
+pub const TENANT_ARCHIVE_VERSION: u32 = 1;
+
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TenantArchive {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub tenant: ArchivedTenant,
+    pub credentials: ArchivedCredentials,
+    pub clients: Vec<ArchivedClient>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedTenant {
+    pub id: String,
+    pub apns_topic: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub apns_topics: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TenantCredentials {
+    pub fcm_api_key: Option<String>,
+    pub fcm_v1_credentials: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fcm_v1_slots: BTreeMap<String, String>,
+    pub apns: Option<TenantApnsUpdateAuth>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ArchivedCredentials {
+    Plain(TenantCredentials),
+    Encrypted(EncryptedCredentials),
+}
+
+/// Base64 encoded output of [`encrypt_credentials`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedCredentials {
+    pub ephemeral_public_key: String,
+    pub nonce: String,
+    pub tag: String,
+    pub ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedClient {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub push_type: String,
+    pub token: String,
+    pub always_raw: bool,
+    pub device_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apns_topic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fcm_v1_slot: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub tenant_id: String,
+    pub clients_imported: usize,
+    pub clients_failed: usize,
+}
+
+impl From<&Tenant> for TenantCredentials {
+    fn from(tenant: &Tenant) -> Self {
+        let apns = match tenant.get_apns_type() {
+            Some(ApnsType::Certificate) => {
+                match (&tenant.apns_certificate, &tenant.apns_certificate_password) {
+                    (Some(apns_certificate), Some(apns_certificate_password)) => {
+                        Some(TenantApnsUpdateAuth::Certificate {
+                            apns_certificate: apns_certificate.clone(),
+                            apns_certificate_password: apns_certificate_password.clone(),
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            Some(ApnsType::Token) => match (
+                &tenant.apns_pkcs8_pem,
+                &tenant.apns_key_id,
+                &tenant.apns_team_id,
+            ) {
+                (Some(apns_pkcs8_pem), Some(apns_key_id), Some(apns_team_id)) => {
+                    Some(TenantApnsUpdateAuth::Token {
+                        apns_pkcs8_pem: apns_pkcs8_pem.clone(),
+                        apns_key_id: apns_key_id.clone(),
+                        apns_team_id: apns_team_id.clone(),
+                    })
+                }
+                _ => None,
+            },
+            None => None,
+        };
+
+        TenantCredentials {
+            fcm_api_key: tenant.fcm_api_key.clone(),
+            fcm_v1_credentials: tenant.fcm_v1_credentials.clone(),
+            fcm_v1_slots: tenant.fcm_v1_slots.0.clone(),
+            apns,
+        }
+    }
+}
+
+/// Builds an archive of the tenant, encrypting the credentials when a
+/// recipient public key is provided
+pub async fn export_tenant(
+    tenant_store: &(dyn TenantStore + Send + Sync),
+    client_store: &(dyn ClientStore + Send + Sync),
+    tenant_id: &str,
+    recipient_public_key: Option<&str>,
+) -> Result<TenantArchive> {
+    let tenant = tenant_store.get_tenant(tenant_id).await?;
+    let clients = client_store.get_tenant_clients(tenant_id).await?;
+
+    let credentials = TenantCredentials::from(&tenant);
+    let credentials = match recipient_public_key {
+        Some(key) => {
+            ArchivedCredentials::Encrypted(encrypt_credentials(&credentials, tenant_id, key)?)
+        }
+        None => ArchivedCredentials::Plain(credentials),
+    };
+
+    debug!(%tenant_id, clients = clients.len(), "exported tenant");
+
+    Ok(TenantArchive {
+        version: TENANT_ARCHIVE_VERSION,
+        exported_at: Utc::now(),
+        tenant: ArchivedTenant {
+            id: tenant.id,
+            apns_topic: tenant.apns_topic,
+            apns_topics: tenant.apns_topics,
+        },
+        credentials,
+        clients: clients
+            .into_iter()
+            .map(|(id, client)| ArchivedClient {
+                id,
+                push_type: client.push_type.as_str().to_string(),
+                token: client.token,
+                always_raw: client.always_raw,
+                device_id: client.device_id,
+                apns_topic: client.apns_topic,
+                fcm_v1_slot: client.fcm_v1_slot,
+            })
+            .collect(),
+    })
+}
+
+/// Creates or updates the tenant from the archive and registers its clients.
+/// Encrypted credentials require the private key matching the recipient key
+/// the archive was exported for
+pub async fn import_tenant(
+    tenant_store: &(dyn TenantStore + Send + Sync),
+    client_store: &(dyn ClientStore + Send + Sync),
+    archive: TenantArchive,
+    private_key: Option<&str>,
+    metrics: Option<&Metrics>,
+) -> Result<ImportSummary> {
+    if archive.version != TENANT_ARCHIVE_VERSION {
+        return Err(UnsupportedArchiveVersion(archive.version));
+    }
+
+    let tenant_id = archive.tenant.id;
+    if tenant_id.is_empty() {
+        return Err(InvalidTenantArchive("tenant id is empty".to_string()));
+    }
+
+    // Decrypt before writing anything so a bad key doesn't leave a partially
+    // imported tenant behind
+    let credentials = match archive.credentials {
+        ArchivedCredentials::Plain(credentials) => credentials,
+        ArchivedCredentials::Encrypted(encrypted) => {
+            let private_key = private_key.ok_or_else(|| {
+                InvalidTenantArchive(
+                    "credentials are encrypted but no private key was provided".to_string(),
+                )
+            })?;
+            decrypt_credentials(&encrypted, &tenant_id, private_key)?
+        }
+    };
+
+    let mut clients_failed = 0;
+    let mut clients = Vec::with_capacity(archive.clients.len());
+    for client in archive.clients {
+        match client.push_type.as_str().try_into() {
+            Ok(push_type) => clients.push((
+                client.id,
+                Client {
+                    tenant_id: tenant_id.clone(),
+                    push_type,
+                    token: client.token,
+                    always_raw: client.always_raw,
+                    device_id: client.device_id,
+                    apns_topic: client.apns_topic,
+                    fcm_v1_slot: client.fcm_v1_slot,
+                },
+            )),
+            Err(e) => {
+                warn!(%tenant_id, client_id = %client.id, "skipping archived client: {e}");
+                clients_failed += 1;
+            }
+        }
+    }
+
+    tenant_store
+        .create_tenant(TenantUpdateParams {
+            id: tenant_id.clone(),
+        })
+        .await?;
+
+    if let Some(fcm_api_key) = credentials.fcm_api_key {
+        tenant_store
+            .update_tenant_fcm(&tenant_id, TenantFcmUpdateParams { fcm_api_key })
+            .await?;
+    }
+
+    if let Some(fcm_v1_credentials) = credentials.fcm_v1_credentials {
+        tenant_store
+            .update_tenant_fcm_v1(&tenant_id, TenantFcmV1UpdateParams { fcm_v1_credentials })
+            .await?;
+    }
+
+    for (slot, fcm_v1_credentials) in credentials.fcm_v1_slots {
+        tenant_store
+            .update_tenant_fcm_v1_slot(
+                &tenant_id,
+                &slot,
+                TenantFcmV1UpdateParams { fcm_v1_credentials },
+            )
+            .await?;
+    }
+
+    if let Some(apns_topic) = archive.tenant.apns_topic {
+        tenant_store
+            .update_tenant_apns(&tenant_id, TenantApnsUpdateParams { apns_topic })
+            .await?;
+    }
+
+    if !archive.tenant.apns_topics.is_empty() {
+        tenant_store
+            .update_tenant_apns_topics(&tenant_id, archive.tenant.apns_topics)
+            .await?;
+    }
+
+    if let Some(apns) = credentials.apns {
+        // Expired certificates are still imported, the expiry check suspends
+        // them
+        let certificate_not_after = match &apns {
+            TenantApnsUpdateAuth::Certificate {
+                apns_certificate,
+                apns_certificate_password,
+            } => Some(p12_not_after(apns_certificate, apns_certificate_password)?),
+            TenantApnsUpdateAuth::Token { .. } => None,
+        };
+        tenant_store
+            .update_tenant_apns_auth(&tenant_id, apns)
+            .await?;
+        if let Some(not_after) = certificate_not_after {
+            tenant_store
+                .update_tenant_apns_certificate_expiry(&tenant_id, not_after)
+                .await?;
+        }
+    }
+
+    let results = client_store
+        .create_clients(&tenant_id, clients, metrics)
+        .await?;
+    let clients_imported = results.iter().filter(|result| result.is_ok()).count();
+    clients_failed += results.len() - clients_imported;
+
+    info!(%tenant_id, %clients_imported, %clients_failed, "imported tenant");
+
+    Ok(ImportSummary {
+        tenant_id,
+        clients_imported,
+        clients_failed,
+    })
+}
+
+/// Seals the credentials to the recipient's X25519 public key, given either as
+/// PEM or as the base64 encoded raw key
+pub fn encrypt_credentials(
+    credentials: &TenantCredentials,
+    tenant_id: &str,
+    recipient_public_key: &str,
+) -> Result<EncryptedCredentials> {
+    let recipient = parse_public_key(recipient_public_key)?;
+    let ephemeral = PKey::generate_x25519()?;
+    let ephemeral_public_key = ephemeral.raw_public_key()?;
+    let key = derive_key(
+        &ephemeral,
+        &recipient,
+        &ephemeral_public_key,
+        &recipient.raw_public_key()?,
+    )?;
+
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rand_bytes(&mut nonce)?;
+
+    let plaintext = serde_json::to_vec(credentials).map_err(Error::InternalSerializationError)?;
+    let mut tag = [0u8; TAG_LENGTH];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(&nonce),
+        tenant_id.as_bytes(),
+        &plaintext,
+        &mut tag,
+    )?;
+
+    Ok(EncryptedCredentials {
+        ephemeral_public_key: STANDARD.encode(ephemeral_public_key),
+        nonce: STANDARD.encode(nonce),
+        tag: STANDARD.encode(tag),
+        ciphertext: STANDARD.encode(ciphertext),
+    })
+}
+
+/// Opens credentials sealed by [`encrypt_credentials`] with the recipient's
+/// X25519 private key, given either as PEM or as the base64 encoded raw key
+pub fn decrypt_credentials(
+    encrypted: &EncryptedCredentials,
+    tenant_id: &str,
+    private_key: &str,
+) -> Result<TenantCredentials> {
+    let private_key = parse_private_key(private_key)?;
+    let ephemeral_public_key =
+        decode_field("ephemeral_public_key", &encrypted.ephemeral_public_key)?;
+    let ephemeral = PKey::public_key_from_raw_bytes(&ephemeral_public_key, Id::X25519)
+        .map_err(|e| InvalidTenantArchive(format!("invalid ephemeral public key: {e}")))?;
+    let key = derive_key(
+        &private_key,
+        &ephemeral,
+        &ephemeral_public_key,
+        &private_key.raw_public_key()?,
+    )?;
+
+    let plaintext = decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(&decode_field("nonce", &encrypted.nonce)?),
+        tenant_id.as_bytes(),
+        &decode_field("ciphertext", &encrypted.ciphertext)?,
+        &decode_field("tag", &encrypted.tag)?,
+    )
+    .map_err(|_| {
+        InvalidTenantArchive(
+            "failed to decrypt credentials, the private key doesn't match the recipient key"
+                .to_string(),
+        )
+    })?;
+
+    serde_json::from_slice(&plaintext)
+        .map_err(|e| InvalidTenantArchive(format!("invalid credentials: {e}")))
+}
+
+/// Derives the AES key from the X25519 shared secret, binding both public keys
+fn derive_key<T: HasPrivate, U: HasPublic>(
+    private_key: &PKeyRef<T>,
+    peer: &PKeyRef<U>,
+    ephemeral_public_key: &[u8],
+    recipient_public_key: &[u8],
+) -> Result<[u8; 32]> {
+    let mut deriver = Deriver::new(private_key)?;
+    deriver.set_peer(peer)?;
+    let shared_secret = deriver.derive_to_vec()?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(&shared_secret);
+    hasher.update(ephemeral_public_key);
+    hasher.update(recipient_public_key);
+    Ok(hasher.finish())
+}
+
+fn parse_public_key(key: &str) -> Result<PKey<Public>> {
+    let key = key.trim();
+    let parsed = if key.starts_with("-----BEGIN") {
+        PKey::public_key_from_pem(key.as_bytes())
+    } else {
+        let raw = STANDARD
+            .decode(key)
+            .map_err(|e| InvalidArchiveKey(e.to_string()))?;
+        PKey::public_key_from_raw_bytes(&raw, Id::X25519)
+    }
+    .map_err(|e| InvalidArchiveKey(e.to_string()))?;
+
+    if parsed.id() != Id::X25519 {
+        return Err(InvalidArchiveKey("expected an X25519 key".to_string()));
+    }
+
+    Ok(parsed)
+}
+
+fn parse_private_key(key: &str) -> Result<PKey<Private>> {
+    let key = key.trim();
+    let parsed = if key.starts_with("-----BEGIN") {
+        PKey::private_key_from_pem(key.as_bytes())
+    } else {
+        let raw = STANDARD
+            .decode(key)
+            .map_err(|e| InvalidArchiveKey(e.to_string()))?;
+        PKey::private_key_from_raw_bytes(&raw, Id::X25519)
+    }
+    .map_err(|e| InvalidArchiveKey(e.to_string()))?;
+
+    if parsed.id() != Id::X25519 {
+        return Err(InvalidArchiveKey("expected an X25519 key".to_string()));
+    }
+
+    Ok(parsed)
+}
+
+fn decode_field(name: &str, value: &str) -> Result<Vec<u8>> {
+    STANDARD
+        .decode(value)
+        .map_err(|e| InvalidTenantArchive(format!("invalid `{name}`: {e}")))
+}
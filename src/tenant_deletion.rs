@@ -0,0 +1,110 @@
+//! Deletion of a tenant across the tenant database and the client database.
+//!
+//! The tenant is first marked as pending deletion, which hides it from
+//! [`TenantStore::get_tenant`] and blocks it from being re-created. Its
+//! notifications and clients are then deleted in batches and finally the
+//! tenant row itself. Every step can be repeated, so a deletion interrupted by
+//! a restart is finished by [`run_reconciler`].
+#[cfg(feature = "analytics")]
+use crate::analytics::client_deleted_info::{ClientDeletedInfo, CLIENT_DELETED_TENANT_DELETED};
+use {
+    crate::{error::Result, log::prelude::*, state::AppState},
+    std::{sync::Arc, time::Duration},
+};
+
+/// Number of rows deleted per query
+const DELETION_BATCH_SIZE: i64 = 1000;
+
+/// How often the reconciler looks for interrupted deletions
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Marks the tenant as pending deletion and deletes it in the background
+pub async fn delete_tenant(state: Arc<AppState>, tenant_id: &str) -> Result<()> {
+    state
+        .tenant_store
+        .mark_tenant_pending_deletion(tenant_id)
+        .await?;
+
+    let tenant_id = tenant_id.to_string();
+    state.tasks.clone().spawn(async move {
+        if let Err(e) = purge_tenant(&state, &tenant_id).await {
+            // The reconciler retries it
+            warn!(%tenant_id, "failed to delete tenant: {e:?}");
+        }
+    });
+
+    Ok(())
+}
+
+/// Deletes the notifications, clients and finally the tenant row of a tenant
+/// that was marked as pending deletion. Every deleted client device is
+/// recorded in the analytics
+pub async fn purge_tenant(state: &AppState, tenant_id: &str) -> Result<()> {
+    let mut notifications = 0;
+    loop {
+        let deleted = state
+            .notification_store
+            .delete_tenant_notifications(tenant_id, DELETION_BATCH_SIZE)
+            .await?;
+        notifications += deleted;
+        if deleted < DELETION_BATCH_SIZE as u64 {
+            break;
+        }
+    }
+
+    let mut clients = 0;
+    loop {
+        let deleted = state
+            .client_store
+            .delete_tenant_clients(tenant_id, DELETION_BATCH_SIZE)
+            .await?;
+        clients += deleted.len();
+
+        #[cfg(feature = "analytics")]
+        if let Some(analytics) = &state.analytics {
+            for (client_id, device_id, push_type) in &deleted {
+                analytics.client_deleted(ClientDeletedInfo {
+                    project_id: tenant_id.into(),
+                    client_id: client_id.as_str().into(),
+                    device_id: Some(device_id.as_str().into()),
+                    push_provider: Some(push_type.as_str().into()),
+                    reason: CLIENT_DELETED_TENANT_DELETED.into(),
+                    deleted_at: wc::analytics::time::now(),
+                });
+            }
+        }
+
+        if deleted.len() < DELETION_BATCH_SIZE as usize {
+            break;
+        }
+    }
+
+    state.tenant_store.delete_tenant(tenant_id).await?;
+
+    info!(%tenant_id, %notifications, %clients, "deleted tenant");
+
+    Ok(())
+}
+
+/// Periodically finishes deletions of tenants still marked as pending deletion
+pub async fn run_reconciler(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let tenant_ids = match state.tenant_store.get_tenants_pending_deletion().await {
+            Ok(tenant_ids) => tenant_ids,
+            Err(e) => {
+                warn!("failed to get tenants pending deletion: {e:?}");
+                continue;
+            }
+        };
+
+        for tenant_id in tenant_ids {
+            debug!(%tenant_id, "resuming tenant deletion");
+            if let Err(e) = purge_tenant(&state, &tenant_id).await {
+                warn!(%tenant_id, "failed to delete tenant: {e:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,346 @@
+//! Webhooks notifying tenants of lifecycle events such as the suspension of
+//! their credentials.
+//!
+//! Events are queued in the tenant database and delivered by
+//! [`Webhooks::run_dispatcher`], which retries failed deliveries with an exponential
+//! backoff. Every delivery is signed with the webhook's secret: the
+//! `X-Webhook-Signature` header holds `sha256=` followed by the hex
+//! HMAC-SHA256 of `"{timestamp}.{body}"`, where the timestamp is sent in
+//! `X-Webhook-Timestamp`.
+//!
+//! Webhook URLs must use https and resolve to public addresses only. The host
+//! is resolved again before every delivery and the request is sent to the
+//! checked addresses, without following redirects.
+use {
+    crate::{
+        error::{
+            Error::{InternalSerializationError, InvalidWebhook},
+            Result,
+        },
+        log::prelude::*,
+        networking::is_public_ip_addr,
+        stores::{
+            tenant::CredentialsKind,
+            webhook::{WebhookDelivery, WebhookStore},
+        },
+    },
+    chrono::{DateTime, Utc},
+    openssl::{hash::MessageDigest, pkey::PKey, rand::rand_bytes, sign::Signer},
+    serde::{Deserialize, Serialize},
+    std::{
+        net::{IpAddr, SocketAddr},
+        sync::Arc,
+        time::Duration,
+    },
+    tokio::sync::Notify,
+};
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+
+const SECRET_LENGTH: usize = 32;
+/// Deliveries claimed per query
+const DELIVERY_BATCH_SIZE: i64 = 50;
+/// How long a claimed delivery is hidden from other instances
+const DELIVERY_LEASE: Duration = Duration::from_secs(60);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the queue is polled when no event was emitted by this instance
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(10);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+/// Deliveries are dropped after this many failed attempts
+const MAX_DELIVERY_ATTEMPTS: i32 = 10;
+
+pub type WebhookStoreArc = Arc<dyn WebhookStore + Send + Sync + 'static>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    TenantSuspended,
+    TenantUnsuspended,
+    ClientDeletedBadToken,
+    CredentialsUpdated,
+    CertificateExpiring,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 5] = [
+        Self::TenantSuspended,
+        Self::TenantUnsuspended,
+        Self::ClientDeletedBadToken,
+        Self::CredentialsUpdated,
+        Self::CertificateExpiring,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TenantSuspended => "tenant_suspended",
+            Self::TenantUnsuspended => "tenant_unsuspended",
+            Self::ClientDeletedBadToken => "client_deleted_bad_token",
+            Self::CredentialsUpdated => "credentials_updated",
+            Self::CertificateExpiring => "certificate_expiring",
+        }
+    }
+}
+
+impl TryFrom<&str> for WebhookEvent {
+    type Error = crate::error::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|event| event.as_str() == value)
+            .ok_or_else(|| InvalidWebhook(format!("unknown event {value}")))
+    }
+}
+
+/// Body of a webhook delivery
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    pub tenant_id: String,
+    pub created_at: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+/// Queues events for the tenant's webhooks and wakes the dispatcher
+#[derive(Clone)]
+pub struct Webhooks {
+    store: WebhookStoreArc,
+    notify: Arc<Notify>,
+}
+
+impl Webhooks {
+    pub fn new(store: WebhookStoreArc) -> Self {
+        Self {
+            store,
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn store(&self) -> &WebhookStoreArc {
+        &self.store
+    }
+
+    /// Queues the event, failures are logged rather than returned so that
+    /// webhooks never fail the operation that emitted the event
+    pub async fn emit(&self, tenant_id: &str, event: WebhookEvent, data: serde_json::Value) {
+        match enqueue(self.store.as_ref(), tenant_id, event, data).await {
+            Ok(0) => {}
+            Ok(_) => self.notify.notify_one(),
+            Err(e) => warn!(%tenant_id, event = event.as_str(), "failed to queue webhook: {e:?}"),
+        }
+    }
+
+    /// Emits `credentials_updated` for updated or removed credentials,
+    /// followed by `tenant_unsuspended` when it lifted their suspension
+    pub async fn emit_credentials_updated(
+        &self,
+        tenant_id: &str,
+        credentials: CredentialsKind,
+        removed: bool,
+        unsuspended: bool,
+    ) {
+        self.emit(
+            tenant_id,
+            WebhookEvent::CredentialsUpdated,
+            serde_json::json!({
+                "credentials": credentials,
+                "removed": removed,
+            }),
+        )
+        .await;
+
+        if unsuspended {
+            self.emit(
+                tenant_id,
+                WebhookEvent::TenantUnsuspended,
+                serde_json::json!({ "credentials": credentials }),
+            )
+            .await;
+        }
+    }
+
+    /// Delivers queued events until the task is aborted
+    pub async fn run_dispatcher(self) {
+        loop {
+            if let Err(e) = self.dispatch().await {
+                warn!("failed to dispatch webhooks: {e:?}");
+            }
+
+            // Deliveries queued by other instances or due for a retry are picked up
+            // on the next poll
+            let _ = tokio::time::timeout(POLL_INTERVAL, self.notify.notified()).await;
+        }
+    }
+
+    async fn dispatch(&self) -> Result<()> {
+        loop {
+            let deliveries = self
+                .store
+                .claim_webhook_deliveries(DELIVERY_BATCH_SIZE, DELIVERY_LEASE)
+                .await?;
+            let claimed = deliveries.len();
+
+            for delivery in deliveries {
+                match deliver(&delivery).await {
+                    Ok(()) => {
+                        debug!(delivery_id = %delivery.id, event = %delivery.event, "delivered webhook");
+                        self.store.delete_webhook_delivery(&delivery.id).await?;
+                    }
+                    Err(e) if delivery.attempts >= MAX_DELIVERY_ATTEMPTS => {
+                        warn!(
+                            delivery_id = %delivery.id,
+                            event = %delivery.event,
+                            attempts = delivery.attempts,
+                            "dropping webhook delivery: {e}"
+                        );
+                        self.store.delete_webhook_delivery(&delivery.id).await?;
+                    }
+                    Err(e) => {
+                        debug!(
+                            delivery_id = %delivery.id,
+                            event = %delivery.event,
+                            attempts = delivery.attempts,
+                            "webhook delivery failed: {e}"
+                        );
+                        self.store
+                            .retry_webhook_delivery(&delivery.id, retry_delay(delivery.attempts))
+                            .await?;
+                    }
+                }
+            }
+
+            if claimed < DELIVERY_BATCH_SIZE as usize {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Queues the event for the tenant's webhooks subscribed to it, returning the
+/// number of deliveries queued
+pub async fn enqueue(
+    store: &(dyn WebhookStore + Send + Sync),
+    tenant_id: &str,
+    event: WebhookEvent,
+    data: serde_json::Value,
+) -> Result<u64> {
+    let payload = WebhookPayload {
+        event,
+        tenant_id: tenant_id.to_string(),
+        created_at: Utc::now(),
+        data,
+    };
+
+    let payload = serde_json::to_value(payload).map_err(InternalSerializationError)?;
+    store
+        .enqueue_webhook_event(tenant_id, event.as_str(), &payload)
+        .await
+}
+
+/// Random hex secret used to sign a webhook's deliveries
+pub fn generate_secret() -> Result<String> {
+    let mut secret = [0u8; SECRET_LENGTH];
+    rand_bytes(&mut secret)?;
+    Ok(hex::encode(secret))
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`, sent prefixed with `sha256=`
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> Result<String> {
+    let key = PKey::hmac(secret.as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(format!("{timestamp}.").as_bytes())?;
+    signer.update(body)?;
+    Ok(hex::encode(signer.sign_to_vec()?))
+}
+
+/// Webhook URLs must be absolute https URLs whose host only resolves to
+/// public addresses, returning the parsed URL and the resolved addresses
+pub async fn validate_url(url: &str) -> Result<(reqwest::Url, Vec<SocketAddr>)> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| InvalidWebhook(format!("invalid url: {e}")))?;
+    if parsed.scheme() != "https" {
+        return Err(InvalidWebhook(format!(
+            "unsupported url scheme {}",
+            parsed.scheme()
+        )));
+    }
+
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| InvalidWebhook("url has no host".to_string()))?;
+    let port = parsed.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = match parsed.domain() {
+        Some(domain) => tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|e| InvalidWebhook(format!("failed to resolve {domain}: {e}")))?
+            .collect(),
+        None => {
+            // IPv6 hosts keep their brackets
+            let ip = host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .map_err(|e| InvalidWebhook(format!("invalid host {host}: {e}")))?;
+            vec![SocketAddr::new(ip, port)]
+        }
+    };
+
+    if addrs.is_empty() {
+        return Err(InvalidWebhook(format!("{host} did not resolve")));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip_addr(addr.ip())) {
+        return Err(InvalidWebhook(format!(
+            "{host} resolves to the non-public address {}",
+            addr.ip()
+        )));
+    }
+
+    Ok((parsed, addrs))
+}
+
+fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.clamp(0, 16) as u32;
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.pow(exponent))
+        .min(RETRY_MAX_DELAY)
+}
+
+async fn deliver(delivery: &WebhookDelivery) -> std::result::Result<(), String> {
+    // Checked again since the host may resolve differently than when the
+    // webhook was created, and the connection is pinned to the checked
+    // addresses so that it can't be resolved a third time
+    let (url, addrs) = validate_url(&delivery.url)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut http_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(DELIVERY_TIMEOUT);
+    if let Some(domain) = url.domain() {
+        http_client = http_client.resolve_to_addrs(domain, &addrs);
+    }
+    let http_client = http_client.build().map_err(|e| e.to_string())?;
+
+    let body = serde_json::to_vec(&delivery.payload.0).map_err(|e| e.to_string())?;
+    let timestamp = Utc::now().timestamp();
+    let signature = sign(&delivery.secret, timestamp, &body).map_err(|e| e.to_string())?;
+
+    let response = http_client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, format!("sha256={signature}"))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("unexpected status {}", response.status()))
+    }
+}
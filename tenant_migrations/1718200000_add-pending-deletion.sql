@@ -0,0 +1,6 @@
+alter table public.tenants
+    add deletion_requested_at timestamptz;
+
+create index tenants_deletion_requested_at_idx
+    on public.tenants (deletion_requested_at)
+    where deletion_requested_at is not null;
@@ -0,0 +1,37 @@
+create table public.tenant_provider_status
+(
+    tenant_id        varchar(255) not null references public.tenants (id) on delete cascade,
+    provider         varchar(16)  not null,
+    suspended        boolean      not null default false,
+    suspended_reason text,
+    updated_at       timestamptz  not null default now(),
+
+    primary key (tenant_id, provider)
+);
+
+-- Suspensions caused by invalid credentials only apply to the affected provider
+insert into public.tenant_provider_status (tenant_id, provider, suspended, suspended_reason)
+select id, 'apns', true, suspended_reason
+from public.tenants
+where suspended
+  and suspended_reason in ('Invalid APNS Credentials', 'APNs certificate expired');
+
+insert into public.tenant_provider_status (tenant_id, provider, suspended, suspended_reason)
+select id, 'fcm', true, suspended_reason
+from public.tenants
+where suspended
+  and suspended_reason = 'Invalid FCM Credentials';
+
+-- Other tenant-wide suspensions apply to every provider
+insert into public.tenant_provider_status (tenant_id, provider, suspended, suspended_reason)
+select tenants.id, providers.provider, true, coalesce(tenants.suspended_reason, 'Suspended')
+from public.tenants
+cross join (values ('apns'), ('fcm'), ('fcm_v1')) as providers (provider)
+where tenants.suspended
+  and tenants.suspended_reason is distinct from 'Invalid APNS Credentials'
+  and tenants.suspended_reason is distinct from 'APNs certificate expired'
+  and tenants.suspended_reason is distinct from 'Invalid FCM Credentials';
+
+alter table public.tenants
+    drop column suspended,
+    drop column suspended_reason;
@@ -0,0 +1,27 @@
+create table public.tenant_webhooks
+(
+    id         varchar(255) primary key default gen_random_uuid(),
+    tenant_id  varchar(255) not null references public.tenants (id) on delete cascade,
+    url        text         not null,
+    secret     text         not null,
+    events     text[]       not null,
+    created_at timestamptz  not null default now()
+);
+
+create index tenant_webhooks_tenant_id_idx on public.tenant_webhooks (tenant_id);
+
+-- Outbox of events waiting to be delivered, rows are deleted once delivered or
+-- after the last attempt
+create table public.tenant_webhook_deliveries
+(
+    id              varchar(255) primary key default gen_random_uuid(),
+    webhook_id      varchar(255) not null references public.tenant_webhooks (id) on delete cascade,
+    event           text         not null,
+    payload         jsonb        not null,
+    attempts        integer      not null default 0,
+    next_attempt_at timestamptz  not null default now(),
+    created_at      timestamptz  not null default now()
+);
+
+create index tenant_webhook_deliveries_next_attempt_at_idx
+    on public.tenant_webhook_deliveries (next_attempt_at);
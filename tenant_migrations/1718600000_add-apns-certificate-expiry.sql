@@ -0,0 +1,11 @@
+alter table public.tenants
+    add apns_certificate_not_after timestamptz;
+
+-- Set once the tenant has been warned about the certificate expiring, reset
+-- when a new certificate is uploaded
+alter table public.tenants
+    add apns_certificate_expiry_notified_at timestamptz;
+
+create index tenants_apns_certificate_not_after_idx
+    on public.tenants (apns_certificate_not_after)
+    where apns_certificate_not_after is not null;
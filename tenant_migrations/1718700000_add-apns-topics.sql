@@ -0,0 +1,4 @@
+-- Topics clients can register for besides the default `apns_topic`, e.g. beta
+-- or white-label builds sharing the tenant's APNs key
+alter table public.tenants
+    add apns_topics text[] not null default '{}';
@@ -0,0 +1,11 @@
+-- Named FCM v1 service accounts, keyed by slot, for tenants with a Firebase
+-- project per app flavour. `fcm_v1_credentials` remains the default slot
+alter table public.tenants
+    add fcm_v1_slots jsonb not null default '{}';
+
+-- Each slot's credentials are suspended separately. The slot is empty for the
+-- default slot and for the other providers' credentials
+alter table public.tenant_provider_status
+    add slot varchar(64) not null default '',
+    drop constraint tenant_provider_status_pkey,
+    add primary key (tenant_id, provider, slot);
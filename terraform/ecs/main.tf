@@ -73,6 +73,7 @@ resource "aws_ecs_task_definition" "app_task_definition" {
         { name = "PUBLIC_URL", value = "https://${var.fqdn}" },
         { name = "LOG_LEVEL", value = "info,echo-server=info" },
         { name = "DATABASE_URL", value = var.database_url },
+        { name = "TENANCY_MODE", value = "multi" },
         { name = "TENANT_DATABASE_URL", value = var.tenant_database_url },
         { name = "CORS_ALLOWED_ORIGINS", value = var.allowed_origins },
         { name = "TELEMETRY_PROMETHEUS_PORT", value = local.prometheus_port },
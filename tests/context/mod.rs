@@ -1,8 +1,10 @@
+#[cfg(feature = "analytics")]
+use echo_server::config::AnalyticsExportBackend;
 use {
     self::server::EchoServer,
     async_trait::async_trait,
     echo_server::{
-        config::Config,
+        config::{Config, LogFormat, LogRotation, TenancyMode},
         state::{ClientStoreArc, NotificationStoreArc, TenantStoreArc},
     },
     sqlx::{Pool, Postgres},
@@ -39,8 +41,13 @@ impl TestContext for ConfigContext {
             public_url: format!("http://127.0.0.1:{public_port}"),
             log_level: "info,echo-server=info".into(),
             log_level_otel: "info,echo-server=trace".into(),
+            log_format: LogFormat::Text,
+            log_file: None,
+            log_file_rotation: LogRotation::Daily,
             disable_header: true,
             validate_signatures: false,
+            shutdown_grace_period_secs: 0,
+            shutdown_drain_timeout_secs: 5,
             relay_public_key: env::var("RELAY_PUBLIC_KEY").unwrap_or(
                 // Default relay public key if env not set
                 // TODO I don't think this is used in the tests, so this should be refactored/removed
@@ -48,30 +55,34 @@ impl TestContext for ConfigContext {
             ),
             database_url: env::var("DATABASE_URL")
                 .expect("DATABASE_URL environment variable is not set"),
+            #[cfg(feature = "multitenant")]
+            tenancy_mode: TenancyMode::Multi,
+            #[cfg(not(feature = "multitenant"))]
+            tenancy_mode: TenancyMode::Single,
             tenant_database_url: env::var("TENANT_DATABASE_URL")
                 .expect("TENANT_DATABASE_URL environment variable is not set"),
-            #[cfg(feature = "multitenant")]
             jwt_secret: "n/a".to_string(),
+            tenant_archive_private_key: None,
+            fcm_v1_token_endpoint: None,
+            fcm_v1_endpoint: None,
+            apns_certificate_expiry_warning_days: 30,
+            legacy_fcm_deprecated: false,
+            // The webhook store tests claim the queued deliveries themselves
+            webhook_dispatcher_enabled: false,
             otel_exporter_otlp_endpoint: None,
             telemetry_prometheus_port: Some(self::server::get_random_port()),
-            #[cfg(not(feature = "multitenant"))]
+            metrics_tenant_labels: vec![],
             apns_type: None,
-            #[cfg(not(feature = "multitenant"))]
             apns_certificate: None,
-            #[cfg(not(feature = "multitenant"))]
             apns_certificate_password: None,
-            #[cfg(not(feature = "multitenant"))]
             apns_pkcs8_pem: None,
-            #[cfg(not(feature = "multitenant"))]
             apns_team_id: None,
-            #[cfg(not(feature = "multitenant"))]
             apns_key_id: None,
-            #[cfg(not(feature = "multitenant"))]
             apns_topic: None,
-            #[cfg(not(feature = "multitenant"))]
             fcm_api_key: None,
-            #[cfg(not(feature = "multitenant"))]
             fcm_v1_credentials: None,
+            credentials_reload_interval_secs: 0,
+            legacy_fcm_fail_fast: false,
             #[cfg(any(feature = "analytics", feature = "geoblock"))]
             s3_endpoint: None,
             #[cfg(any(feature = "analytics", feature = "geoblock"))]
@@ -79,7 +90,16 @@ impl TestContext for ConfigContext {
             #[cfg(any(feature = "analytics", feature = "geoblock"))]
             geoip_db_key: None,
             #[cfg(feature = "analytics")]
+            analytics_export_backend: AnalyticsExportBackend::Local,
+            #[cfg(feature = "analytics")]
             analytics_export_bucket: "example-bucket".to_string(),
+            #[cfg(feature = "analytics")]
+            analytics_export_dir: Some(
+                env::temp_dir()
+                    .join("echo-analytics")
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
             is_test: true,
             cors_allowed_origins: vec!["*".to_string()],
             #[cfg(feature = "geoblock")]
@@ -89,12 +109,19 @@ impl TestContext for ConfigContext {
     }
 }
 
+impl EchoServerContext {
+    /// Starts a server with a config that differs from the default test config,
+    /// the caller tears it down
+    pub async fn start(config: Config) -> Self {
+        let server = EchoServer::start(config.clone()).await;
+        Self { server, config }
+    }
+}
+
 #[async_trait]
 impl AsyncTestContext for EchoServerContext {
     async fn setup() -> Self {
-        let config = ConfigContext::setup().config;
-        let server = EchoServer::start(ConfigContext::setup().config).await;
-        Self { server, config }
+        Self::start(ConfigContext::setup().config).await
     }
 
     async fn teardown(mut self) {
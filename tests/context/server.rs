@@ -27,12 +27,17 @@ impl EchoServer {
         }
     }
 
+    /// Sends the shutdown signal without waiting for the server to stop
+    pub fn signal_shutdown(&self) {
+        let _ = self.shutdown_signal.send(());
+    }
+
     pub async fn shutdown(&mut self) {
         if self.is_shutdown {
             return;
         }
         self.is_shutdown = true;
-        let _ = self.shutdown_signal.send(());
+        self.signal_shutdown();
         wait_for_server_to_shutdown(self.public_addr.port())
             .await
             .unwrap();
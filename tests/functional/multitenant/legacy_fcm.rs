@@ -0,0 +1,174 @@
+use {
+    crate::{
+        context::{ConfigContext, EchoServerContext, StoreContext},
+        functional::multitenant::generate_random_tenant_id,
+    },
+    echo_server::{
+        config::Config,
+        handlers::{create_tenant::TenantRegisterBody, push_message::PushMessageBody},
+        providers::{LegacyPushMessage, MessagePayload, ProviderKind},
+        stores::{
+            client::{Client, DEFAULT_DEVICE_ID},
+            tenant::{CredentialsKind, TenantFcmUpdateParams, TenantUpdateParams},
+        },
+    },
+    test_context::{test_context, AsyncTestContext, TestContext},
+    uuid::Uuid,
+};
+
+/// Registers a tenant that only has a legacy FCM API key along with an FCM
+/// client, returns the tenant and client ids
+async fn create_legacy_fcm_client(ctx: &StoreContext) -> (String, String) {
+    let tenant_id = Uuid::new_v4().to_string();
+    ctx.tenants
+        .create_tenant(TenantUpdateParams {
+            id: tenant_id.clone(),
+        })
+        .await
+        .expect("failed to create tenant");
+    ctx.tenants
+        .update_tenant_fcm(
+            &tenant_id,
+            TenantFcmUpdateParams {
+                fcm_api_key: "legacy-api-key".to_string(),
+            },
+        )
+        .await
+        .expect("failed to update tenant's FCM API key");
+
+    let client_id = format!("id-{}", Uuid::new_v4());
+    ctx.clients
+        .create_client(
+            &tenant_id,
+            &client_id,
+            Client {
+                tenant_id: tenant_id.clone(),
+                push_type: ProviderKind::Fcm,
+                token: format!("token-{}", Uuid::new_v4()),
+                always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
+            },
+            None,
+        )
+        .await
+        .expect("failed to create client");
+
+    (tenant_id, client_id)
+}
+
+/// Pushes to the client and checks it's rejected with `legacy_fcm_deprecated`
+async fn assert_push_deprecated(server: &EchoServerContext, tenant_id: &str, client_id: &str) {
+    let payload = PushMessageBody {
+        raw: None,
+        legacy: Some(LegacyPushMessage {
+            id: Uuid::new_v4().to_string().into(),
+            payload: MessagePayload {
+                topic: Uuid::new_v4().to_string().into(),
+                flags: 0,
+                blob: Uuid::new_v4().to_string().into(),
+            },
+        }),
+    };
+    let response = reqwest::Client::new()
+        .post(format!(
+            "http://{}/{}/clients/{}",
+            server.server.public_addr, tenant_id, client_id
+        ))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::GONE);
+
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["errors"][0]["name"], "legacy_fcm_deprecated");
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn legacy_fcm_fail_fast(ctx: &mut StoreContext) {
+    let server = EchoServerContext::start(Config {
+        legacy_fcm_fail_fast: true,
+        ..ConfigContext::setup().config
+    })
+    .await;
+
+    let (tenant_id, client_id) = create_legacy_fcm_client(ctx).await;
+    assert_push_deprecated(&server, &tenant_id, &client_id).await;
+
+    // Failing fast leaves the credentials alone
+    let tenant = ctx.tenants.get_tenant(&tenant_id).await.unwrap();
+    assert!(tenant.is_legacy_fcm_only());
+    assert_eq!(tenant.suspended_reason(CredentialsKind::Fcm), None);
+
+    server.teardown().await;
+}
+
+/// Without the legacy FCM client there's nothing to send legacy FCM clients
+/// with, regardless of `LEGACY_FCM_FAIL_FAST`
+#[cfg(not(feature = "legacy_fcm"))]
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn legacy_fcm_client_without_feature(ctx: &mut StoreContext) {
+    let server = EchoServerContext::start(ConfigContext::setup().config).await;
+
+    let (tenant_id, client_id) = create_legacy_fcm_client(ctx).await;
+    assert_push_deprecated(&server, &tenant_id, &client_id).await;
+
+    server.teardown().await;
+}
+
+#[tokio::test]
+async fn legacy_fcm_upload_rejected() {
+    let server = EchoServerContext::start(Config {
+        legacy_fcm_deprecated: true,
+        ..ConfigContext::setup().config
+    })
+    .await;
+    let (tenant_id, jwt_token) = generate_random_tenant_id(&server.config.jwt_secret);
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://{}/tenants", server.server.public_addr))
+        .bearer_auth(&jwt_token)
+        .json(&TenantRegisterBody {
+            id: tenant_id.clone(),
+        })
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let form = reqwest::multipart::Form::new().text("api_key", "legacy-api-key");
+    let response = client
+        .post(format!(
+            "http://{}/tenants/{}/fcm",
+            server.server.public_addr, tenant_id
+        ))
+        .bearer_auth(&jwt_token)
+        .multipart(form)
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::GONE);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["errors"][0]["name"], "legacy_fcm_deprecated");
+
+    // The key wasn't stored
+    let response = client
+        .get(format!(
+            "http://{}/tenants/{}",
+            server.server.public_addr, tenant_id
+        ))
+        .bearer_auth(&jwt_token)
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["legacy_fcm_only"], false);
+
+    server.teardown().await;
+}
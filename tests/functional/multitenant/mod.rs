@@ -13,6 +13,7 @@ mod apns;
 mod fcm;
 #[cfg(feature = "fcmv1_tests")]
 mod fcm_v1;
+mod legacy_fcm;
 mod tenancy;
 
 /// Struct to hold claims for JWT validation
@@ -32,6 +33,33 @@ async fn test_health(ctx: &mut EchoServerContext) {
     assert!(body.is_success());
 }
 
+#[test_context(EchoServerContext)]
+#[tokio::test]
+async fn test_health_live(ctx: &mut EchoServerContext) {
+    let response = reqwest::get(format!("http://{}/health/live", ctx.server.public_addr))
+        .await
+        .expect("Failed to call /health/live");
+    assert!(response.status().is_success());
+
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["status"], "ok");
+}
+
+#[test_context(EchoServerContext)]
+#[tokio::test]
+async fn test_health_ready(ctx: &mut EchoServerContext) {
+    let response = reqwest::get(format!("http://{}/health/ready", ctx.server.public_addr))
+        .await
+        .expect("Failed to call /health/ready");
+    assert!(response.status().is_success());
+
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["database"]["ok"], true);
+    assert!(body["database"]["migration_version"].is_i64());
+    assert_eq!(body["tenant_database"]["ok"], true);
+}
+
 pub fn generate_random_tenant_id(jwt_secret: &str) -> (String, String) {
     let charset = "1234567890";
     let tenant_id = generate(12, charset);
@@ -1,6 +1,12 @@
 use {
     crate::{context::EchoServerContext, functional::multitenant::generate_random_tenant_id},
-    echo_server::handlers::create_tenant::TenantRegisterBody,
+    echo_server::{
+        handlers::{
+            create_tenant::TenantRegisterBody,
+            test_tenant::{TestNotificationBody, TestNotificationResponse},
+        },
+        tenant_archive::{ImportSummary, TenantArchive, TENANT_ARCHIVE_VERSION},
+    },
     test_context::test_context,
 };
 
@@ -69,3 +75,160 @@ async fn tenant_register_get_delete(ctx: &mut EchoServerContext) {
     // TODO: this should be changed to 404
     assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
 }
+
+#[test_context(EchoServerContext)]
+#[tokio::test]
+async fn tenant_export_import(ctx: &mut EchoServerContext) {
+    let (tenant_id, jwt_token) = generate_random_tenant_id(&ctx.config.jwt_secret);
+
+    // Register tenant
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://{}/tenants", ctx.server.public_addr))
+        .bearer_auth(&jwt_token)
+        .json(&TenantRegisterBody {
+            id: tenant_id.clone(),
+        })
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // Export tenant
+    let response = client
+        .get(format!(
+            "http://{}/tenants/{}/export",
+            ctx.server.public_addr, tenant_id
+        ))
+        .bearer_auth(&jwt_token)
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let archive = response
+        .json::<TenantArchive>()
+        .await
+        .expect("Invalid archive");
+    assert_eq!(archive.version, TENANT_ARCHIVE_VERSION);
+    assert_eq!(archive.tenant.id, tenant_id);
+
+    // Import tenant, re-importing into an existing tenant updates it
+    let response = client
+        .post(format!("http://{}/tenants/import", ctx.server.public_addr))
+        .bearer_auth(&jwt_token)
+        .json(&archive)
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let summary = response
+        .json::<ImportSummary>()
+        .await
+        .expect("Invalid summary");
+    assert_eq!(summary.tenant_id, tenant_id);
+
+    // Get tenant again
+    let response = client
+        .get(format!(
+            "http://{}/tenants/{}",
+            ctx.server.public_addr, tenant_id
+        ))
+        .bearer_auth(&jwt_token)
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[test_context(EchoServerContext)]
+#[tokio::test]
+async fn tenant_test_notification(ctx: &mut EchoServerContext) {
+    let (tenant_id, jwt_token) = generate_random_tenant_id(&ctx.config.jwt_secret);
+
+    // Register tenant
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://{}/tenants", ctx.server.public_addr))
+        .bearer_auth(&jwt_token)
+        .json(&TenantRegisterBody {
+            id: tenant_id.clone(),
+        })
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // Send to a raw token
+    let response = client
+        .post(format!(
+            "http://{}/tenants/{}/test",
+            ctx.server.public_addr, tenant_id
+        ))
+        .bearer_auth(&jwt_token)
+        .json(&TestNotificationBody {
+            client_id: None,
+            token: Some("test-token".to_string()),
+            push_type: Some("noop".to_string()),
+            title: None,
+            body: None,
+        })
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = response
+        .json::<TestNotificationResponse>()
+        .await
+        .expect("Invalid response");
+    assert_eq!(response.results.len(), 1);
+    assert_eq!(response.results[0].push_type, "noop");
+    assert!(response.results[0].ok);
+    assert!(response.results[0].error.is_none());
+
+    // Failures are reported in the device's result
+    let response = client
+        .post(format!(
+            "http://{}/tenants/{}/test",
+            ctx.server.public_addr, tenant_id
+        ))
+        .bearer_auth(&jwt_token)
+        .json(&TestNotificationBody {
+            client_id: None,
+            token: Some("test-token".to_string()),
+            push_type: Some("apns".to_string()),
+            title: None,
+            body: None,
+        })
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = response
+        .json::<TestNotificationResponse>()
+        .await
+        .expect("Invalid response");
+    assert_eq!(response.results.len(), 1);
+    assert_eq!(response.results[0].push_type, "apns");
+    assert!(!response.results[0].ok);
+    assert!(response.results[0].response.is_none());
+    assert!(response.results[0].error.is_some());
+
+    // A raw token requires the push type
+    let response = client
+        .post(format!(
+            "http://{}/tenants/{}/test",
+            ctx.server.public_addr, tenant_id
+        ))
+        .bearer_auth(&jwt_token)
+        .json(&TestNotificationBody {
+            client_id: None,
+            token: Some("test-token".to_string()),
+            push_type: None,
+            title: None,
+            body: None,
+        })
+        .send()
+        .await
+        .expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,94 @@
+use {
+    crate::context::{ConfigContext, StoreContext},
+    echo_server::{
+        credentials_reload::reload_credentials,
+        state::new_state,
+        stores::tenant::{DefaultTenantStore, TenantStore, DEFAULT_TENANT_ID},
+    },
+    std::sync::Arc,
+    test_context::{test_context, TestContext},
+};
+
+fn vars(extra: &[(&str, &str)]) -> Vec<(String, String)> {
+    let config = ConfigContext::setup().config;
+    let mut vars = vec![
+        ("PUBLIC_URL".to_string(), config.public_url),
+        ("RELAY_PUBLIC_KEY".to_string(), config.relay_public_key),
+        ("DATABASE_URL".to_string(), config.database_url),
+        (
+            "TENANT_DATABASE_URL".to_string(),
+            config.tenant_database_url,
+        ),
+        ("ANALYTICS_EXPORT_BUCKET".to_string(), "bucket".to_string()),
+        ("BLOCKED_COUNTRIES".to_string(), String::new()),
+    ];
+    vars.extend(
+        extra
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string())),
+    );
+    vars
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn reload_replaces_changed_credentials(ctx: &mut StoreContext) {
+    let config = Arc::new(ConfigContext::setup().config);
+    let store = Arc::new(DefaultTenantStore::new(config.clone()).unwrap());
+    let state = new_state(
+        (*config).clone(),
+        ctx.clients.clone(),
+        ctx.notifications.clone(),
+        store.clone(),
+    )
+    .unwrap();
+
+    let reloaded = reload_credentials(&state, &store, vars(&[("FCM_API_KEY", "key")]))
+        .await
+        .unwrap();
+    assert!(reloaded);
+    let tenant = store.get_tenant(DEFAULT_TENANT_ID).await.unwrap();
+    assert_eq!(tenant.fcm_api_key.as_deref(), Some("key"));
+
+    // Unchanged credentials are not swapped in again
+    let reloaded = reload_credentials(&state, &store, vars(&[("FCM_API_KEY", "key")]))
+        .await
+        .unwrap();
+    assert!(!reloaded);
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn bad_reload_keeps_previous_credentials(ctx: &mut StoreContext) {
+    let config = Arc::new(ConfigContext::setup().config);
+    let store = Arc::new(DefaultTenantStore::new(config.clone()).unwrap());
+    let state = new_state(
+        (*config).clone(),
+        ctx.clients.clone(),
+        ctx.notifications.clone(),
+        store.clone(),
+    )
+    .unwrap();
+    let previous = store.get_tenant(DEFAULT_TENANT_ID).await.unwrap();
+
+    // Incomplete token config
+    let res = reload_credentials(&state, &store, vars(&[("APNS_TYPE", "Token")])).await;
+    assert!(res.is_err());
+
+    // Complete but unusable key
+    let res = reload_credentials(
+        &state,
+        &store,
+        vars(&[
+            ("APNS_TYPE", "Token"),
+            ("APNS_TOPIC", "com.example.app"),
+            ("APNS_PKCS8_PEM", "bm90IGEga2V5"),
+            ("APNS_KEY_ID", "key"),
+            ("APNS_TEAM_ID", "team"),
+        ]),
+    )
+    .await;
+    assert!(res.is_err());
+
+    assert_eq!(store.get_tenant(DEFAULT_TENANT_ID).await.unwrap(), previous);
+}
@@ -1,6 +1,7 @@
 /// Tests against the handlers
 use {crate::context::EchoServerContext, test_context::test_context};
 
+mod credentials_reload;
 mod push;
 mod registration;
 
@@ -13,3 +14,30 @@ async fn test_health(ctx: &mut EchoServerContext) {
         .status();
     assert!(body.is_success());
 }
+
+#[test_context(EchoServerContext)]
+#[tokio::test]
+async fn test_health_live(ctx: &mut EchoServerContext) {
+    let response = reqwest::get(format!("http://{}/health/live", ctx.server.public_addr))
+        .await
+        .expect("Failed to call /health/live");
+    assert!(response.status().is_success());
+
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["status"], "ok");
+}
+
+#[test_context(EchoServerContext)]
+#[tokio::test]
+async fn test_health_ready(ctx: &mut EchoServerContext) {
+    let response = reqwest::get(format!("http://{}/health/ready", ctx.server.public_addr))
+        .await
+        .expect("Failed to call /health/ready");
+    assert!(response.status().is_success());
+
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["database"]["ok"], true);
+    assert!(body["database"]["migration_version"].is_i64());
+    assert!(body.get("tenant_database").is_none());
+}
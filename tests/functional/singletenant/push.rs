@@ -1,19 +1,29 @@
 use {
-    crate::context::EchoServerContext,
+    crate::context::{ConfigContext, EchoServerContext},
     echo_server::{
+        config::Config,
         handlers::{push_message::PushMessageBody, register_client::RegisterBody},
         providers::{LegacyPushMessage, MessagePayload, RawPushMessage},
     },
     ed25519_dalek::SigningKey,
     hyper::StatusCode,
     relay_rpc::domain::{ClientId, DecodedClientId},
-    std::sync::Arc,
-    test_context::test_context,
+    std::{sync::Arc, time::Duration},
+    test_context::{test_context, AsyncTestContext, TestContext},
     uuid::Uuid,
     wiremock::{http::Method, matchers::method, Mock, MockServer, ResponseTemplate},
 };
 
 async fn create_client(ctx: &mut EchoServerContext, always_raw: bool) -> (ClientId, MockServer) {
+    create_client_with_response(ctx, always_raw, ResponseTemplate::new(StatusCode::OK)).await
+}
+
+/// Registers a client whose pushes are answered with `response`
+async fn create_client_with_response(
+    ctx: &mut EchoServerContext,
+    always_raw: bool,
+    response: ResponseTemplate,
+) -> (ClientId, MockServer) {
     let keypair = SigningKey::generate(&mut rand::thread_rng());
 
     let random_client_id = DecodedClientId::from_key(&keypair.verifying_key());
@@ -31,7 +41,7 @@ async fn create_client(ctx: &mut EchoServerContext, always_raw: bool) -> (Client
     let mock_server = {
         let mock_server = MockServer::start().await;
         Mock::given(method(Method::GET))
-            .respond_with(ResponseTemplate::new(StatusCode::OK))
+            .respond_with(response)
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -44,6 +54,9 @@ async fn create_client(ctx: &mut EchoServerContext, always_raw: bool) -> (Client
         push_type: "noop".to_string(),
         token: token.clone(),
         always_raw: Some(always_raw),
+        device_id: None,
+        apns_topic: None,
+        fcm_v1_slot: None,
     };
 
     // Register client
@@ -240,3 +253,64 @@ async fn test_push_always_raw(ctx: &mut EchoServerContext) {
         .expect("Call failed");
     assert_eq!(response.status(), StatusCode::ACCEPTED);
 }
+
+#[tokio::test]
+async fn test_shutdown_drains_in_flight_pushes() {
+    let mut ctx = EchoServerContext::start(Config {
+        shutdown_grace_period_secs: 1,
+        ..ConfigContext::setup().config
+    })
+    .await;
+    // The push is still being sent once the grace period is over
+    let (client_id, _mock_server) = create_client_with_response(
+        &mut ctx,
+        false,
+        ResponseTemplate::new(StatusCode::OK).set_delay(Duration::from_secs(2)),
+    )
+    .await;
+
+    let payload = PushMessageBody {
+        raw: None,
+        legacy: Some(LegacyPushMessage {
+            id: Uuid::new_v4().to_string().into(),
+            payload: MessagePayload {
+                topic: Uuid::new_v4().to_string().into(),
+                blob: Uuid::new_v4().to_string().into(),
+                flags: 0,
+            },
+        }),
+    };
+    let push = tokio::spawn(
+        reqwest::Client::new()
+            .post(format!(
+                "http://{}/clients/{}",
+                ctx.server.public_addr, client_id
+            ))
+            .json(&payload)
+            .send(),
+    );
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    ctx.server.signal_shutdown();
+
+    // Readiness fails during the grace period while requests are still served
+    let ready_url = format!("http://{}/health/ready", ctx.server.public_addr);
+    let mut response = reqwest::get(&ready_url).await.expect("Call failed");
+    for _ in 0..20 {
+        if response.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        response = reqwest::get(&ready_url).await.expect("Call failed");
+    }
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["status"], "draining");
+
+    // The in-flight push finishes after the server stopped accepting
+    // connections
+    let response = push.await.unwrap().expect("Call failed");
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+
+    ctx.teardown().await;
+}
@@ -18,6 +18,9 @@ async fn test_registration(ctx: &mut EchoServerContext) {
         push_type: "noop".to_string(),
         token: "test".to_string(),
         always_raw: Some(false),
+        device_id: None,
+        apns_topic: None,
+        fcm_v1_slot: None,
     };
 
     let jwt = relay_rpc::auth::AuthToken::new(client_id.value().to_string())
@@ -50,6 +53,9 @@ async fn test_registration(ctx: &mut EchoServerContext) {
         push_type: "noop".to_string(),
         token: "new_token".to_string(),
         always_raw: Some(false),
+        device_id: None,
+        apns_topic: None,
+        fcm_v1_slot: None,
     };
     let response = client
         .post(format!("http://{}/clients", ctx.server.public_addr))
@@ -87,6 +93,9 @@ async fn test_deregistration(ctx: &mut EchoServerContext) {
         push_type: "noop".to_string(),
         token: "test".to_string(),
         always_raw: Some(false),
+        device_id: None,
+        apns_topic: None,
+        fcm_v1_slot: None,
     };
 
     let client = reqwest::Client::new();
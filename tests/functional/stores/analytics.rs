@@ -0,0 +1,112 @@
+use {
+    crate::{
+        context::{ConfigContext, StoreContext},
+        functional::stores::gen_id,
+    },
+    echo_server::{
+        analytics::PushAnalytics,
+        handlers::{emit_credentials_updated, emit_tenant_suspended},
+        providers::ProviderKind,
+        state,
+        stores::{
+            client::{Client, DEFAULT_DEVICE_ID},
+            tenant::{CredentialsKind, TenantUpdateParams},
+        },
+        tenant_deletion::purge_tenant,
+    },
+    parquet::file::reader::{FileReader, SerializedFileReader},
+    std::{
+        net::{IpAddr, Ipv4Addr},
+        path::Path,
+    },
+    test_context::{test_context, TestContext},
+};
+
+/// Rows in the parquet files exported for the data kind
+fn exported_rows(export_dir: &Path, data_kind: &str) -> i64 {
+    let mut rows = 0;
+    let mut dirs = vec![export_dir.join("echo").join(data_kind)];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "parquet")
+            {
+                let reader = SerializedFileReader::new(std::fs::File::open(path).unwrap()).unwrap();
+                rows += reader.metadata().file_metadata().num_rows();
+            }
+        }
+    }
+    rows
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn analytics_tenant_events_are_exported(ctx: &mut StoreContext) {
+    let export_dir = std::env::temp_dir().join(format!("echo-analytics-{}", gen_id()));
+    let mut state = state::new_state(
+        ConfigContext::setup().config,
+        ctx.clients.clone(),
+        ctx.notifications.clone(),
+        ctx.tenants.clone(),
+    )
+    .unwrap();
+    state.analytics = Some(PushAnalytics::with_local_export(
+        export_dir.clone(),
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        None,
+    ));
+
+    let tenant_id = gen_id();
+    ctx.tenants
+        .create_tenant(TenantUpdateParams {
+            id: tenant_id.clone(),
+        })
+        .await
+        .unwrap();
+    for _ in 0..2 {
+        ctx.clients
+            .create_client(
+                &tenant_id,
+                &format!("id-{}", gen_id()),
+                Client {
+                    tenant_id: tenant_id.clone(),
+                    push_type: ProviderKind::Noop,
+                    token: format!("token-{}", gen_id()),
+                    always_raw: false,
+                    device_id: DEFAULT_DEVICE_ID.to_string(),
+                    apns_topic: None,
+                    fcm_v1_slot: None,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+    }
+
+    emit_credentials_updated(&state, &tenant_id, CredentialsKind::FcmV1, false, false).await;
+    emit_tenant_suspended(
+        &state,
+        &tenant_id,
+        CredentialsKind::Apns,
+        "suspended by test",
+    )
+    .await;
+    ctx.tenants
+        .mark_tenant_pending_deletion(&tenant_id)
+        .await
+        .unwrap();
+    purge_tenant(&state, &tenant_id).await.unwrap();
+
+    state.analytics.take().unwrap().flush().await;
+
+    assert_eq!(exported_rows(&export_dir, "tenant_credentials_updates"), 1);
+    assert_eq!(exported_rows(&export_dir, "tenant_suspensions"), 1);
+    // A record per deleted device
+    assert_eq!(exported_rows(&export_dir, "client_deletions"), 2);
+
+    std::fs::remove_dir_all(export_dir).unwrap();
+}
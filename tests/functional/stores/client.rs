@@ -4,7 +4,12 @@ use {
         functional::stores::{gen_id, TENANT_ID},
     },
     echo_server::{
-        handlers::push_message::PushMessageBody, providers::ProviderKind, stores::client::Client,
+        handlers::push_message::PushMessageBody,
+        providers::ProviderKind,
+        stores::{
+            client::{Client, DEFAULT_DEVICE_ID},
+            StoreError,
+        },
     },
     test_context::test_context,
 };
@@ -23,6 +28,9 @@ async fn client_creation(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Noop,
                 token,
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -52,6 +60,9 @@ async fn client_creation_concurrent(ctx: &mut StoreContext) {
                         push_type: ProviderKind::Noop,
                         token,
                         always_raw: false,
+                        device_id: DEFAULT_DEVICE_ID.to_string(),
+                        apns_topic: None,
+                        fcm_v1_slot: None,
                     },
                     None,
                 )
@@ -81,6 +92,9 @@ async fn client_creation_fcm(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Fcm,
                 token,
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -104,6 +118,9 @@ async fn client_creation_apns(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Apns,
                 token,
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -129,6 +146,9 @@ async fn client_upsert_token(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Fcm,
                 token: token.clone(),
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -171,6 +191,9 @@ async fn client_upsert_token(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Apns,
                 token: updated_token.clone(),
                 always_raw: true,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -203,6 +226,9 @@ async fn client_upsert_id(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Fcm,
                 token: token.clone(),
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -245,6 +271,9 @@ async fn client_upsert_id(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Fcm,
                 token: token.clone(),
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -280,6 +309,9 @@ async fn client_create_same_id_and_token(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Fcm,
                 token: token.clone(),
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -322,6 +354,9 @@ async fn client_create_same_id_and_token(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Noop,
                 token: token.clone(),
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -353,6 +388,9 @@ async fn client_deletion(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Noop,
                 token,
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -376,6 +414,9 @@ async fn client_fetch(ctx: &mut StoreContext) {
                 push_type: ProviderKind::Noop,
                 token: token.clone(),
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -390,3 +431,429 @@ async fn client_fetch(ctx: &mut StoreContext) {
     // Cleaning up records
     ctx.clients.delete_client(TENANT_ID, &id).await.unwrap();
 }
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn client_multiple_devices(ctx: &mut StoreContext) {
+    let id = format!("id-{}", gen_id());
+    let phone_token = format!("token-{}", gen_id());
+    let tablet_token = format!("token-{}", gen_id());
+
+    for (device_id, token) in [("phone", &phone_token), ("tablet", &tablet_token)] {
+        ctx.clients
+            .create_client(
+                TENANT_ID,
+                &id,
+                Client {
+                    tenant_id: TENANT_ID.to_string(),
+                    push_type: ProviderKind::Noop,
+                    token: token.clone(),
+                    always_raw: false,
+                    device_id: device_id.to_string(),
+                    apns_topic: None,
+                    fcm_v1_slot: None,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+    }
+
+    // The second device must not overwrite the first one
+    let devices = ctx
+        .clients
+        .get_client_devices(TENANT_ID, &id)
+        .await
+        .unwrap();
+    assert_eq!(devices.len(), 2);
+    assert!(devices.iter().any(|d| d.token == phone_token));
+    assert!(devices.iter().any(|d| d.token == tablet_token));
+
+    // Deleting a single device keeps the others
+    ctx.clients
+        .delete_client_device(TENANT_ID, &id, "phone")
+        .await
+        .unwrap();
+    let devices = ctx
+        .clients
+        .get_client_devices(TENANT_ID, &id)
+        .await
+        .unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].device_id, "tablet");
+    assert_eq!(devices[0].token, tablet_token);
+
+    // Cleaning up records
+    ctx.clients.delete_client(TENANT_ID, &id).await.unwrap();
+    assert!(ctx
+        .clients
+        .get_client_devices(TENANT_ID, &id)
+        .await
+        .is_err());
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn client_bulk_creation(ctx: &mut StoreContext) {
+    let ids = (0..150)
+        .map(|_| format!("id-{}", gen_id()))
+        .collect::<Vec<_>>();
+    let shared_token = format!("token-{}", gen_id());
+
+    let clients = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            // The last two clients register the same token, so the token moves
+            // to the last one the same way it would with `create_client`
+            let token = if i >= ids.len() - 2 {
+                shared_token.clone()
+            } else {
+                format!("token-{}", gen_id())
+            };
+            (
+                id.clone(),
+                Client {
+                    tenant_id: TENANT_ID.to_string(),
+                    push_type: ProviderKind::Noop,
+                    token,
+                    always_raw: false,
+                    device_id: DEFAULT_DEVICE_ID.to_string(),
+                    apns_topic: None,
+                    fcm_v1_slot: None,
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let results = ctx
+        .clients
+        .create_clients(TENANT_ID, clients, None)
+        .await
+        .unwrap();
+    assert_eq!(results.len(), ids.len());
+    assert!(results.iter().all(|result| result.is_ok()));
+
+    // Clients spanning multiple chunks are all stored
+    ctx.clients.get_client(TENANT_ID, &ids[0]).await.unwrap();
+    ctx.clients.get_client(TENANT_ID, &ids[120]).await.unwrap();
+
+    // The token was moved from the second to last client to the last one
+    assert!(ctx
+        .clients
+        .get_client(TENANT_ID, &ids[ids.len() - 2])
+        .await
+        .is_err());
+    let client = ctx
+        .clients
+        .get_client(TENANT_ID, &ids[ids.len() - 1])
+        .await
+        .unwrap();
+    assert_eq!(client.token, shared_token);
+
+    // Cleaning up records
+    for id in &ids {
+        let _ = ctx.clients.delete_client(TENANT_ID, id).await;
+    }
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn client_tenant_deletion(ctx: &mut StoreContext) {
+    let tenant_id = format!("tenant-{}", gen_id());
+    let ids = (0..3)
+        .map(|_| format!("id-{}", gen_id()))
+        .collect::<Vec<_>>();
+
+    for id in &ids {
+        ctx.clients
+            .create_client(
+                &tenant_id,
+                id,
+                Client {
+                    tenant_id: tenant_id.clone(),
+                    push_type: ProviderKind::Noop,
+                    token: format!("token-{}", gen_id()),
+                    always_raw: false,
+                    device_id: DEFAULT_DEVICE_ID.to_string(),
+                    apns_topic: None,
+                    fcm_v1_slot: None,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+    }
+
+    // Clients are deleted in batches until none are left
+    let deleted = ctx
+        .clients
+        .delete_tenant_clients(&tenant_id, 2)
+        .await
+        .unwrap();
+    assert_eq!(deleted.len(), 2);
+    assert!(deleted
+        .iter()
+        .all(|(_, device_id, push_type)| device_id == DEFAULT_DEVICE_ID
+            && *push_type == ProviderKind::Noop));
+    let deleted = ctx
+        .clients
+        .delete_tenant_clients(&tenant_id, 2)
+        .await
+        .unwrap();
+    assert_eq!(deleted.len(), 1);
+
+    let clients = ctx.clients.get_tenant_clients(&tenant_id).await.unwrap();
+    assert!(clients.is_empty());
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn client_find_by_token(ctx: &mut StoreContext) {
+    let id = format!("id-{}", gen_id());
+    let token = format!("token-{}", gen_id());
+
+    ctx.clients
+        .create_client(
+            TENANT_ID,
+            &id,
+            Client {
+                tenant_id: TENANT_ID.to_string(),
+                push_type: ProviderKind::Noop,
+                token: token.clone(),
+                always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
+            },
+            None,
+        )
+        .await
+        .unwrap();
+
+    let (found_id, client) = ctx.clients.get_client_by_token(&token).await.unwrap();
+    assert_eq!(found_id, id);
+    assert_eq!(client.token, token);
+
+    // Cleaning up records
+    ctx.clients.delete_client(TENANT_ID, &id).await.unwrap();
+    assert!(ctx.clients.get_client_by_token(&token).await.is_err());
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn client_apns_topic(ctx: &mut StoreContext) {
+    let id = format!("id-{}", gen_id());
+    let token = format!("token-{}", gen_id());
+    ctx.clients
+        .create_client(
+            TENANT_ID,
+            &id,
+            Client {
+                tenant_id: TENANT_ID.to_string(),
+                push_type: ProviderKind::Apns,
+                token,
+                always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: Some("com.example.wallet.beta".to_string()),
+                fcm_v1_slot: None,
+            },
+            None,
+        )
+        .await
+        .unwrap();
+
+    let client = ctx.clients.get_client(TENANT_ID, &id).await.unwrap();
+    assert_eq!(
+        client.apns_topic.as_deref(),
+        Some("com.example.wallet.beta")
+    );
+
+    // Cleaning up records
+    ctx.clients.delete_client(TENANT_ID, &id).await.unwrap();
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn client_upsert_id_ignores_other_tenants(ctx: &mut StoreContext) {
+    let other_tenant_id = format!("tenant-{}", gen_id());
+    let client_id = format!("id-{}", gen_id());
+    let token = format!("token-{}", gen_id());
+    let client = |tenant_id: &str, token: &str, device_id: &str| Client {
+        tenant_id: tenant_id.to_string(),
+        push_type: ProviderKind::Fcm,
+        token: token.to_string(),
+        always_raw: false,
+        device_id: device_id.to_string(),
+        apns_topic: None,
+        fcm_v1_slot: None,
+    };
+
+    // The same client id in another tenant, on another device so that both
+    // rows exist
+    ctx.clients
+        .create_client(
+            TENANT_ID,
+            &client_id,
+            client(TENANT_ID, &token, DEFAULT_DEVICE_ID),
+            None,
+        )
+        .await
+        .unwrap();
+    ctx.clients
+        .create_client(
+            &other_tenant_id,
+            &client_id,
+            client(&other_tenant_id, &format!("token-{}", gen_id()), "other"),
+            None,
+        )
+        .await
+        .unwrap();
+
+    let body = PushMessageBody {
+        raw: None,
+        legacy: None,
+    };
+    let notification_id = format!("id-{}", gen_id());
+    ctx.notifications
+        .create_or_update_notification(&notification_id, TENANT_ID, &client_id, &body)
+        .await
+        .unwrap();
+    let other_notification_id = format!("id-{}", gen_id());
+    ctx.notifications
+        .create_or_update_notification(&other_notification_id, &other_tenant_id, &client_id, &body)
+        .await
+        .unwrap();
+
+    // The token moves to another client id, leaving the client without devices
+    let updated_id = format!("id-{}", gen_id());
+    ctx.clients
+        .create_client(
+            TENANT_ID,
+            &updated_id,
+            client(TENANT_ID, &token, DEFAULT_DEVICE_ID),
+            None,
+        )
+        .await
+        .unwrap();
+
+    // The other tenant's client with the same id doesn't keep the notification
+    assert!(ctx
+        .notifications
+        .get_notification(&notification_id, &client_id, TENANT_ID)
+        .await
+        .is_err());
+    // and keeps its own
+    ctx.notifications
+        .get_notification(&other_notification_id, &client_id, &other_tenant_id)
+        .await
+        .unwrap();
+    let devices = ctx
+        .clients
+        .get_client_devices(&other_tenant_id, &client_id)
+        .await
+        .unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].device_id, "other");
+
+    ctx.clients
+        .delete_client(TENANT_ID, &updated_id)
+        .await
+        .unwrap();
+    ctx.clients
+        .delete_client(&other_tenant_id, &client_id)
+        .await
+        .unwrap();
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn client_reregister_with_token_of_another_client(ctx: &mut StoreContext) {
+    let client_id = format!("id-{}", gen_id());
+    let other_client_id = format!("id-{}", gen_id());
+    let token = format!("token-{}", gen_id());
+    let other_token = format!("token-{}", gen_id());
+    let client = |token: &str| Client {
+        tenant_id: TENANT_ID.to_string(),
+        push_type: ProviderKind::Fcm,
+        token: token.to_string(),
+        always_raw: false,
+        device_id: DEFAULT_DEVICE_ID.to_string(),
+        apns_topic: None,
+        fcm_v1_slot: None,
+    };
+
+    ctx.clients
+        .create_client(TENANT_ID, &client_id, client(&token), None)
+        .await
+        .unwrap();
+    ctx.clients
+        .create_client(TENANT_ID, &other_client_id, client(&other_token), None)
+        .await
+        .unwrap();
+
+    // The device's row and the other client's row both match the registration
+    ctx.clients
+        .create_client(TENANT_ID, &client_id, client(&other_token), None)
+        .await
+        .unwrap();
+
+    let devices = ctx
+        .clients
+        .get_client_devices(TENANT_ID, &client_id)
+        .await
+        .unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].token, other_token);
+    assert!(ctx
+        .clients
+        .get_client_devices(TENANT_ID, &other_client_id)
+        .await
+        .is_err());
+    assert!(ctx.clients.get_client_by_token(&token).await.is_err());
+
+    ctx.clients
+        .delete_client(TENANT_ID, &client_id)
+        .await
+        .unwrap();
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn client_devices_share_always_raw(ctx: &mut StoreContext) {
+    let id = format!("id-{}", gen_id());
+    let client = |device_id: &str, always_raw: bool| Client {
+        tenant_id: TENANT_ID.to_string(),
+        push_type: ProviderKind::Noop,
+        token: format!("token-{}", gen_id()),
+        always_raw,
+        device_id: device_id.to_string(),
+        apns_topic: None,
+        fcm_v1_slot: None,
+    };
+
+    ctx.clients
+        .create_client(TENANT_ID, &id, client("phone", true), None)
+        .await
+        .unwrap();
+
+    // Another device with a different payload shape is rejected
+    let res = ctx
+        .clients
+        .create_client(TENANT_ID, &id, client("tablet", false), None)
+        .await;
+    assert!(matches!(res, Err(StoreError::AlwaysRawMismatch(_))));
+
+    ctx.clients
+        .create_client(TENANT_ID, &id, client("tablet", true), None)
+        .await
+        .unwrap();
+    let devices = ctx
+        .clients
+        .get_client_devices(TENANT_ID, &id)
+        .await
+        .unwrap();
+    assert_eq!(devices.len(), 2);
+
+    ctx.clients.delete_client(TENANT_ID, &id).await.unwrap();
+}
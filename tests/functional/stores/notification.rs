@@ -4,8 +4,10 @@ use {
         functional::stores::{gen_id, TENANT_ID},
     },
     echo_server::{
-        handlers::push_message::PushMessageBody, providers::ProviderKind, state::ClientStoreArc,
-        stores::client::Client,
+        handlers::push_message::PushMessageBody,
+        providers::ProviderKind,
+        state::ClientStoreArc,
+        stores::client::{Client, DEFAULT_DEVICE_ID},
     },
     test_context::test_context,
 };
@@ -23,6 +25,9 @@ pub async fn create_client(client_store: &ClientStoreArc) -> String {
                 push_type: ProviderKind::Noop,
                 token,
                 always_raw: false,
+                device_id: DEFAULT_DEVICE_ID.to_string(),
+                apns_topic: None,
+                fcm_v1_slot: None,
             },
             None,
         )
@@ -1,8 +1,13 @@
 use {
     crate::context::StoreContext,
-    echo_server::stores::tenant::{
-        TenantApnsUpdateAuth, TenantApnsUpdateParams, TenantFcmUpdateParams,
-        TenantFcmV1UpdateParams, TenantUpdateParams,
+    chrono::Utc,
+    echo_server::{
+        error::Error,
+        providers::ProviderKind,
+        stores::tenant::{
+            CredentialsKind, TenantApnsUpdateAuth, TenantApnsUpdateParams, TenantFcmUpdateParams,
+            TenantFcmV1UpdateParams, TenantUpdateParams, DEFAULT_FCM_V1_SLOT,
+        },
     },
     test_context::test_context,
     uuid::Uuid,
@@ -401,3 +406,441 @@ async fn tenant_delete_apns_token_auth(ctx: &mut StoreContext) {
     assert_eq!(res.apns_certificate, None);
     assert_eq!(res.apns_certificate_password, None);
 }
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn tenant_pending_deletion(ctx: &mut StoreContext) {
+    let id = Uuid::new_v4().to_string();
+
+    ctx.tenants
+        .create_tenant(TenantUpdateParams { id: id.clone() })
+        .await
+        .expect("creation failed");
+
+    ctx.tenants
+        .mark_tenant_pending_deletion(&id)
+        .await
+        .expect("marking failed");
+
+    // Pending tenants can't be fetched or re-created
+    assert!(ctx.tenants.get_tenant(&id).await.is_err());
+    assert!(ctx
+        .tenants
+        .create_tenant(TenantUpdateParams { id: id.clone() })
+        .await
+        .is_err());
+
+    let pending = ctx
+        .tenants
+        .get_tenants_pending_deletion()
+        .await
+        .expect("listing failed");
+    assert!(pending.contains(&id));
+
+    ctx.tenants
+        .delete_tenant(&id)
+        .await
+        .expect("deletion failed");
+    let pending = ctx
+        .tenants
+        .get_tenants_pending_deletion()
+        .await
+        .expect("listing failed");
+    assert!(!pending.contains(&id));
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn tenant_credentials_suspension(ctx: &mut StoreContext) {
+    let id = Uuid::new_v4().to_string();
+
+    ctx.tenants
+        .create_tenant(TenantUpdateParams { id: id.clone() })
+        .await
+        .expect("creation failed");
+
+    ctx.tenants
+        .suspend_tenant_credentials(&id, CredentialsKind::Fcm, None, "Invalid FCM Credentials")
+        .await
+        .expect("suspension failed");
+
+    // Only the suspended credentials are affected
+    let tenant = ctx.tenants.get_tenant(&id).await.expect("get failed");
+    assert_eq!(
+        tenant.suspended_reason(CredentialsKind::Fcm),
+        Some("Invalid FCM Credentials")
+    );
+    assert_eq!(tenant.suspended_reason(CredentialsKind::Apns), None);
+    assert_eq!(tenant.provider_status.len(), 1);
+
+    ctx.tenants
+        .unsuspend_tenant_credentials(&id, CredentialsKind::Fcm, None)
+        .await
+        .expect("unsuspension failed");
+
+    let tenant = ctx.tenants.get_tenant(&id).await.expect("get failed");
+    assert_eq!(tenant.suspended_reason(CredentialsKind::Fcm), None);
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn tenant_apns_certificate_expiry(ctx: &mut StoreContext) {
+    let id = Uuid::new_v4().to_string();
+
+    ctx.tenants
+        .create_tenant(TenantUpdateParams { id: id.clone() })
+        .await
+        .expect("creation failed");
+    ctx.tenants
+        .update_tenant_apns_auth(
+            &id,
+            TenantApnsUpdateAuth::Certificate {
+                apns_certificate: "example certificate".to_string(),
+                apns_certificate_password: "example password".to_string(),
+            },
+        )
+        .await
+        .expect("update failed");
+
+    // Certificates uploaded before expiries were recorded are backfilled
+    let missing = ctx
+        .tenants
+        .get_tenants_missing_apns_certificate_expiry()
+        .await
+        .expect("listing failed");
+    assert!(missing.iter().any(|tenant| tenant.id == id));
+
+    let backfilled_not_after = Utc::now() + chrono::Duration::days(90);
+    ctx.tenants
+        .backfill_tenant_apns_certificate_expiry(&id, "other certificate", backfilled_not_after)
+        .await
+        .expect("backfill failed");
+    let tenant = ctx.tenants.get_tenant(&id).await.expect("get failed");
+    assert_eq!(tenant.apns_certificate_not_after, None);
+
+    ctx.tenants
+        .backfill_tenant_apns_certificate_expiry(&id, "example certificate", backfilled_not_after)
+        .await
+        .expect("backfill failed");
+    let tenant = ctx.tenants.get_tenant(&id).await.expect("get failed");
+    assert_eq!(
+        tenant.apns_certificate_not_after.map(|t| t.timestamp()),
+        Some(backfilled_not_after.timestamp())
+    );
+    let missing = ctx
+        .tenants
+        .get_tenants_missing_apns_certificate_expiry()
+        .await
+        .expect("listing failed");
+    assert!(!missing.iter().any(|tenant| tenant.id == id));
+
+    let not_after = Utc::now() + chrono::Duration::days(7);
+    let tenant = ctx
+        .tenants
+        .update_tenant_apns_certificate_expiry(&id, not_after)
+        .await
+        .expect("update failed");
+    assert_eq!(
+        tenant.apns_certificate_not_after.map(|t| t.timestamp()),
+        Some(not_after.timestamp())
+    );
+
+    let expiring = ctx
+        .tenants
+        .get_tenants_with_apns_certificate_expiring(Utc::now() + chrono::Duration::days(30))
+        .await
+        .expect("listing failed");
+    assert!(expiring.iter().any(|tenant| tenant.id == id));
+
+    let expiring = ctx
+        .tenants
+        .get_tenants_with_apns_certificate_expiring(Utc::now())
+        .await
+        .expect("listing failed");
+    assert!(!expiring.iter().any(|tenant| tenant.id == id));
+
+    // Only the first instance to mark the tenant sends the warning
+    assert!(ctx
+        .tenants
+        .mark_tenant_apns_certificate_expiry_notified(&id)
+        .await
+        .expect("marking failed"));
+    assert!(!ctx
+        .tenants
+        .mark_tenant_apns_certificate_expiry_notified(&id)
+        .await
+        .expect("marking failed"));
+    let tenant = ctx.tenants.get_tenant(&id).await.expect("get failed");
+    assert!(tenant.apns_certificate_expiry_notified_at.is_some());
+
+    // Likewise for the suspension of the expired certificate
+    assert!(ctx
+        .tenants
+        .suspend_tenant_credentials_once(
+            &id,
+            CredentialsKind::Apns,
+            None,
+            "APNs certificate expired"
+        )
+        .await
+        .expect("suspension failed"));
+    assert!(!ctx
+        .tenants
+        .suspend_tenant_credentials_once(
+            &id,
+            CredentialsKind::Apns,
+            None,
+            "APNs certificate expired"
+        )
+        .await
+        .expect("suspension failed"));
+    let tenant = ctx.tenants.get_tenant(&id).await.expect("get failed");
+    assert_eq!(
+        tenant.suspended_reason(CredentialsKind::Apns),
+        Some("APNs certificate expired")
+    );
+
+    // Switching to token auth clears the expiry
+    let tenant = ctx
+        .tenants
+        .update_tenant_apns_auth(
+            &id,
+            TenantApnsUpdateAuth::Token {
+                apns_pkcs8_pem: "example pem".to_string(),
+                apns_key_id: "example key id".to_string(),
+                apns_team_id: "example team id".to_string(),
+            },
+        )
+        .await
+        .expect("update failed");
+    assert_eq!(tenant.apns_certificate_not_after, None);
+    assert_eq!(tenant.apns_certificate_expiry_notified_at, None);
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn tenant_apns_topics(ctx: &mut StoreContext) {
+    let id = Uuid::new_v4().to_string();
+
+    ctx.tenants
+        .create_tenant(TenantUpdateParams { id: id.clone() })
+        .await
+        .expect("creation failed");
+    ctx.tenants
+        .update_tenant_apns(
+            &id,
+            TenantApnsUpdateParams {
+                apns_topic: "com.example.wallet".to_string(),
+            },
+        )
+        .await
+        .expect("update failed");
+
+    let tenant = ctx
+        .tenants
+        .update_tenant_apns_topics(&id, vec!["com.example.wallet.beta".to_string()])
+        .await
+        .expect("update failed");
+    assert_eq!(
+        tenant.apns_topics,
+        vec!["com.example.wallet.beta".to_string()]
+    );
+    assert!(tenant.has_apns_topic("com.example.wallet"));
+    assert!(tenant.has_apns_topic("com.example.wallet.beta"));
+    assert!(!tenant.has_apns_topic("com.example.other"));
+
+    // Removing the APNs credentials removes the additional topics
+    let tenant = ctx
+        .tenants
+        .update_tenant_delete_apns(&id)
+        .await
+        .expect("delete failed");
+    assert!(tenant.apns_topics.is_empty());
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn tenant_fcm_v1_slots(ctx: &mut StoreContext) {
+    let id = Uuid::new_v4().to_string();
+
+    ctx.tenants
+        .create_tenant(TenantUpdateParams { id: id.clone() })
+        .await
+        .expect("creation failed");
+
+    let tenant = ctx
+        .tenants
+        .update_tenant_fcm_v1_slot(
+            &id,
+            "beta",
+            TenantFcmV1UpdateParams {
+                fcm_v1_credentials: "beta credentials".to_string(),
+            },
+        )
+        .await
+        .expect("update failed");
+    assert_eq!(
+        tenant.fcm_v1_slots.get("beta").map(String::as_str),
+        Some("beta credentials")
+    );
+    assert!(tenant.has_fcm_v1_slot("beta"));
+    assert!(!tenant.has_fcm_v1_slot(DEFAULT_FCM_V1_SLOT));
+    assert!(tenant.provider_kinds().contains(&ProviderKind::Fcm));
+
+    // Replacing a slot keeps the others
+    ctx.tenants
+        .update_tenant_fcm_v1_slot(
+            &id,
+            "internal",
+            TenantFcmV1UpdateParams {
+                fcm_v1_credentials: "internal credentials".to_string(),
+            },
+        )
+        .await
+        .expect("update failed");
+    let tenant = ctx
+        .tenants
+        .update_tenant_fcm_v1_slot(
+            &id,
+            "beta",
+            TenantFcmV1UpdateParams {
+                fcm_v1_credentials: "new beta credentials".to_string(),
+            },
+        )
+        .await
+        .expect("update failed");
+    assert_eq!(tenant.fcm_v1_slots.len(), 2);
+    assert_eq!(
+        tenant.fcm_v1_slots.get("beta").map(String::as_str),
+        Some("new beta credentials")
+    );
+
+    let tenant = ctx
+        .tenants
+        .update_tenant_delete_fcm_v1_slot(&id, "beta")
+        .await
+        .expect("delete failed");
+    assert!(!tenant.has_fcm_v1_slot("beta"));
+    assert!(tenant.has_fcm_v1_slot("internal"));
+
+    assert!(matches!(
+        ctx.tenants
+            .update_tenant_delete_fcm_v1_slot(&id, "beta")
+            .await,
+        Err(Error::FcmV1SlotNotFound(_))
+    ));
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn tenant_fcm_v1_slot_suspension(ctx: &mut StoreContext) {
+    let id = Uuid::new_v4().to_string();
+
+    ctx.tenants
+        .create_tenant(TenantUpdateParams { id: id.clone() })
+        .await
+        .expect("creation failed");
+    ctx.tenants
+        .update_tenant_fcm_v1_slot(
+            &id,
+            "beta",
+            TenantFcmV1UpdateParams {
+                fcm_v1_credentials: "beta credentials".to_string(),
+            },
+        )
+        .await
+        .expect("update failed");
+
+    // Suspending a slot leaves the default slot and the other slots untouched
+    ctx.tenants
+        .suspend_tenant_credentials(
+            &id,
+            CredentialsKind::FcmV1,
+            Some("beta"),
+            "Invalid FCM Credentials",
+        )
+        .await
+        .expect("suspension failed");
+    let tenant = ctx.tenants.get_tenant(&id).await.expect("get failed");
+    assert_eq!(
+        tenant.slot_suspended_reason(CredentialsKind::FcmV1, Some("beta")),
+        Some("Invalid FCM Credentials")
+    );
+    assert_eq!(tenant.suspended_reason(CredentialsKind::FcmV1), None);
+    assert_eq!(
+        tenant.slot_suspended_reason(CredentialsKind::FcmV1, Some("internal")),
+        None
+    );
+
+    // The default slot can be named explicitly
+    ctx.tenants
+        .suspend_tenant_credentials(
+            &id,
+            CredentialsKind::FcmV1,
+            Some(DEFAULT_FCM_V1_SLOT),
+            "Invalid FCM Credentials",
+        )
+        .await
+        .expect("suspension failed");
+    let tenant = ctx.tenants.get_tenant(&id).await.expect("get failed");
+    assert_eq!(
+        tenant.suspended_reason(CredentialsKind::FcmV1),
+        Some("Invalid FCM Credentials")
+    );
+
+    ctx.tenants
+        .unsuspend_tenant_credentials(&id, CredentialsKind::FcmV1, Some("beta"))
+        .await
+        .expect("unsuspension failed");
+    let tenant = ctx.tenants.get_tenant(&id).await.expect("get failed");
+    assert_eq!(
+        tenant.slot_suspended_reason(CredentialsKind::FcmV1, Some("beta")),
+        None
+    );
+    assert_eq!(
+        tenant.suspended_reason(CredentialsKind::FcmV1),
+        Some("Invalid FCM Credentials")
+    );
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn tenant_legacy_fcm_only(ctx: &mut StoreContext) {
+    let id = Uuid::new_v4().to_string();
+
+    ctx.tenants
+        .create_tenant(TenantUpdateParams { id: id.clone() })
+        .await
+        .expect("creation failed");
+    let tenant = ctx
+        .tenants
+        .update_tenant_fcm(
+            &id,
+            TenantFcmUpdateParams {
+                fcm_api_key: "example api key".to_string(),
+            },
+        )
+        .await
+        .expect("update failed");
+    assert!(tenant.is_legacy_fcm_only());
+    assert_eq!(
+        tenant.credentials_kind(&ProviderKind::Fcm),
+        Some(CredentialsKind::Fcm)
+    );
+
+    let tenant = ctx
+        .tenants
+        .update_tenant_fcm_v1(
+            &id,
+            TenantFcmV1UpdateParams {
+                fcm_v1_credentials: "example credentials".to_string(),
+            },
+        )
+        .await
+        .expect("update failed");
+    assert!(!tenant.is_legacy_fcm_only());
+    assert_eq!(
+        tenant.credentials_kind(&ProviderKind::Fcm),
+        Some(CredentialsKind::FcmV1)
+    );
+}
@@ -0,0 +1,118 @@
+use {
+    crate::context::StoreContext,
+    echo_server::{
+        error::Error,
+        stores::{
+            tenant::TenantUpdateParams,
+            webhook::{WebhookDelivery, WebhookStore},
+        },
+        webhooks::{self, WebhookEvent},
+    },
+    serde_json::json,
+    sqlx::PgPool,
+    std::time::Duration,
+    test_context::test_context,
+    uuid::Uuid,
+};
+
+const EXAMPLE_URL: &str = "https://example.com/webhook";
+
+/// Other tests share the queue, so only the tenant's deliveries are claimed
+async fn claim(pool: &PgPool, tenant_id: &str) -> Option<WebhookDelivery> {
+    pool.claim_tenant_webhook_deliveries(tenant_id, 1, Duration::from_secs(60))
+        .await
+        .expect("failed to claim deliveries")
+        .pop()
+}
+
+#[test_context(StoreContext)]
+#[tokio::test]
+async fn tenant_webhooks(ctx: &mut StoreContext) {
+    let tenant_id = Uuid::new_v4().to_string();
+    ctx.tenants
+        .create_tenant(TenantUpdateParams {
+            id: tenant_id.clone(),
+        })
+        .await
+        .expect("failed to create tenant");
+
+    let webhook = ctx
+        .tenant_pool
+        .create_webhook(
+            &tenant_id,
+            EXAMPLE_URL,
+            "secret",
+            &[WebhookEvent::TenantSuspended.as_str().to_string()],
+        )
+        .await
+        .expect("failed to create webhook");
+    assert_eq!(webhook.url, EXAMPLE_URL);
+
+    let webhooks = ctx
+        .tenant_pool
+        .get_webhooks(&tenant_id)
+        .await
+        .expect("failed to get webhooks");
+    assert_eq!(webhooks, vec![webhook.clone()]);
+
+    // Only subscribed events are queued
+    let queued = webhooks::enqueue(
+        ctx.tenant_pool.as_ref(),
+        &tenant_id,
+        WebhookEvent::CredentialsUpdated,
+        json!({}),
+    )
+    .await
+    .expect("failed to enqueue event");
+    assert_eq!(queued, 0);
+
+    let queued = webhooks::enqueue(
+        ctx.tenant_pool.as_ref(),
+        &tenant_id,
+        WebhookEvent::TenantSuspended,
+        json!({ "credentials": "apns" }),
+    )
+    .await
+    .expect("failed to enqueue event");
+    assert_eq!(queued, 1);
+
+    let delivery = claim(&ctx.tenant_pool, &tenant_id)
+        .await
+        .expect("delivery wasn't claimed");
+    assert_eq!(delivery.event, WebhookEvent::TenantSuspended.as_str());
+    assert_eq!(delivery.url, EXAMPLE_URL);
+    assert_eq!(delivery.attempts, 1);
+
+    // Leased until retried
+    assert!(claim(&ctx.tenant_pool, &tenant_id).await.is_none());
+    ctx.tenant_pool
+        .retry_webhook_delivery(&delivery.id, Duration::ZERO)
+        .await
+        .expect("failed to retry delivery");
+    let delivery = claim(&ctx.tenant_pool, &tenant_id)
+        .await
+        .expect("delivery wasn't claimed");
+    assert_eq!(delivery.attempts, 2);
+
+    ctx.tenant_pool
+        .delete_webhook_delivery(&delivery.id)
+        .await
+        .expect("failed to delete delivery");
+    ctx.tenant_pool
+        .retry_webhook_delivery(&delivery.id, Duration::ZERO)
+        .await
+        .expect("failed to retry delivery");
+    assert!(claim(&ctx.tenant_pool, &tenant_id).await.is_none());
+
+    let delete_res = ctx
+        .tenant_pool
+        .delete_webhook(&tenant_id, &webhook.id)
+        .await;
+    assert!(delete_res.is_ok());
+
+    let delete_res = ctx
+        .tenant_pool
+        .delete_webhook(&tenant_id, &webhook.id)
+        .await;
+    assert!(matches!(delete_res, Err(Error::WebhookNotFound(_))));
+}
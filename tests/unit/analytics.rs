@@ -0,0 +1,44 @@
+use {
+    echo_server::analytics::local::{LocalConfig, LocalExporter},
+    std::net::{IpAddr, Ipv4Addr},
+    wc::analytics::Exporter,
+};
+
+#[tokio::test]
+pub async fn local_export_writes_time_partitioned_files() {
+    let export_dir = std::env::temp_dir().join(format!("echo-analytics-{}", uuid::Uuid::new_v4()));
+    let exporter = LocalExporter::new(LocalConfig {
+        export_dir: export_dir.clone(),
+        export_prefix: "echo/messages".to_string(),
+        export_name: "push_messages".to_string(),
+        node_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        file_extension: "parquet".to_string(),
+    });
+
+    // Batches exported at the same time don't overwrite each other
+    exporter.clone().export(b"batch".to_vec()).await.unwrap();
+    exporter.export(b"batch".to_vec()).await.unwrap();
+
+    let now = chrono::Utc::now();
+    let hour_dir = export_dir
+        .join("echo/messages")
+        .join(format!("dt={}", now.format("%Y-%m-%d")))
+        .join(format!("hr={}", now.format("%H")));
+    let files = std::fs::read_dir(&hour_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+
+    assert_eq!(files.len(), 2);
+    for file in files {
+        let file_name = file.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(
+            file_name.contains("_push_messages_127.0.0.1_"),
+            "{file_name}"
+        );
+        assert!(file_name.ends_with(".parquet"), "{file_name}");
+        assert_eq!(std::fs::read(&file).unwrap(), b"batch");
+    }
+
+    std::fs::remove_dir_all(export_dir).unwrap();
+}
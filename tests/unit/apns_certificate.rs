@@ -0,0 +1,85 @@
+use {
+    base64::{engine::general_purpose::STANDARD, Engine as _},
+    chrono::Utc,
+    echo_server::{
+        error::Error, handlers::update_apns::apns_certificate_not_after,
+        stores::tenant::TenantApnsUpdateAuth,
+    },
+    openssl::{
+        asn1::Asn1Time,
+        hash::MessageDigest,
+        pkcs12::Pkcs12,
+        pkey::PKey,
+        rsa::Rsa,
+        x509::{X509NameBuilder, X509},
+    },
+};
+
+const EXAMPLE_PASSWORD: &str = "example password";
+
+/// Base64 encoded p12 with a self-signed certificate valid between the times
+fn certificate(not_before: &Asn1Time, not_after: &Asn1Time) -> String {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "echo-server").unwrap();
+    let name = name.build();
+
+    let mut builder = X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_issuer_name(&name).unwrap();
+    builder.set_pubkey(&key).unwrap();
+    builder.set_not_before(not_before).unwrap();
+    builder.set_not_after(not_after).unwrap();
+    builder.sign(&key, MessageDigest::sha256()).unwrap();
+    let certificate = builder.build();
+
+    let p12 = Pkcs12::builder()
+        .name("echo-server")
+        .pkey(&key)
+        .cert(&certificate)
+        .build2(EXAMPLE_PASSWORD)
+        .unwrap();
+    STANDARD.encode(p12.to_der().unwrap())
+}
+
+fn auth(apns_certificate: String) -> TenantApnsUpdateAuth {
+    TenantApnsUpdateAuth::Certificate {
+        apns_certificate,
+        apns_certificate_password: EXAMPLE_PASSWORD.to_string(),
+    }
+}
+
+#[test]
+fn apns_certificate_expiry() {
+    let now = Utc::now().timestamp();
+    let not_after = Asn1Time::from_unix(now + 10 * 24 * 60 * 60).unwrap();
+    let certificate = certificate(&Asn1Time::from_unix(now).unwrap(), &not_after);
+
+    let parsed = apns_certificate_not_after(&auth(certificate))
+        .unwrap()
+        .expect("certificate has an expiry");
+    assert_eq!(parsed.timestamp(), now + 10 * 24 * 60 * 60);
+}
+
+#[test]
+fn apns_certificate_expired() {
+    let now = Utc::now().timestamp();
+    let certificate = certificate(
+        &Asn1Time::from_unix(now - 20 * 24 * 60 * 60).unwrap(),
+        &Asn1Time::from_unix(now - 10 * 24 * 60 * 60).unwrap(),
+    );
+
+    let res = apns_certificate_not_after(&auth(certificate));
+    assert!(matches!(res, Err(Error::ApnsCertificateExpired)));
+}
+
+#[test]
+fn apns_token_has_no_expiry() {
+    let res = apns_certificate_not_after(&TenantApnsUpdateAuth::Token {
+        apns_pkcs8_pem: "example pem".to_string(),
+        apns_key_id: "example key id".to_string(),
+        apns_team_id: "example team id".to_string(),
+    });
+    assert!(matches!(res, Ok(None)));
+}
@@ -0,0 +1,89 @@
+use echo_server::{
+    error::Error, handlers::update_apns::ApnsUpdateBody, stores::tenant::TenantApnsUpdateAuth,
+};
+
+fn invalid_fields(body: ApnsUpdateBody) -> Vec<String> {
+    match body.validate() {
+        Err(Error::InvalidApnsUpdateBody(fields)) => {
+            fields.into_iter().map(|field| field.field).collect()
+        }
+        res => panic!("expected invalid fields, got {res:?}"),
+    }
+}
+
+#[test]
+pub fn topic_only_update() {
+    let update = serde_json::from_str::<ApnsUpdateBody>(r#"{"apns_topic":"com.example.wallet"}"#)
+        .unwrap()
+        .validate()
+        .unwrap();
+    assert_eq!(update.topic.as_deref(), Some("com.example.wallet"));
+    assert!(update.auth.is_none());
+}
+
+#[test]
+pub fn json_token_update() {
+    let update = serde_json::from_str::<ApnsUpdateBody>(
+        r#"{"apns_pkcs8_pem":"cGVt","apns_key_id":"key-id","apns_team_id":"team-id"}"#,
+    )
+    .unwrap()
+    .validate()
+    .unwrap();
+    assert!(matches!(
+        update.auth,
+        Some(TenantApnsUpdateAuth::Token { apns_pkcs8_pem, .. }) if apns_pkcs8_pem == "cGVt"
+    ));
+}
+
+#[test]
+pub fn certificate_password_defaults_to_empty() {
+    let update = ApnsUpdateBody {
+        apns_certificate: Some("Y2VydA==".to_string()),
+        ..Default::default()
+    }
+    .validate()
+    .unwrap();
+    assert!(matches!(
+        update.auth,
+        Some(TenantApnsUpdateAuth::Certificate { apns_certificate_password, .. })
+            if apns_certificate_password.is_empty()
+    ));
+}
+
+#[test]
+pub fn invalid_combinations_name_fields() {
+    assert_eq!(invalid_fields(ApnsUpdateBody::default()), ["apns_topic"]);
+    assert_eq!(
+        invalid_fields(ApnsUpdateBody {
+            apns_pkcs8_pem: Some("cGVt".to_string()),
+            ..Default::default()
+        }),
+        ["apns_key_id", "apns_team_id"]
+    );
+    assert_eq!(
+        invalid_fields(ApnsUpdateBody {
+            apns_certificate: Some("Y2VydA==".to_string()),
+            apns_key_id: Some("key-id".to_string()),
+            ..Default::default()
+        }),
+        ["apns_key_id"]
+    );
+    assert_eq!(
+        invalid_fields(ApnsUpdateBody {
+            apns_certificate_password: Some("password".to_string()),
+            ..Default::default()
+        }),
+        ["apns_certificate"]
+    );
+}
+
+#[test]
+pub fn rejects_invalid_base64() {
+    assert_eq!(
+        invalid_fields(ApnsUpdateBody {
+            apns_certificate: Some("not base64!".to_string()),
+            ..Default::default()
+        }),
+        ["apns_certificate"]
+    );
+}
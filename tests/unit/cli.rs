@@ -0,0 +1,119 @@
+use {
+    echo_server::{admin, cli, stores::tenant::CredentialsKind},
+    std::path::PathBuf,
+};
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+pub fn server_without_subcommand() {
+    assert_eq!(cli::Command::parse(&[]).unwrap(), None);
+}
+
+#[test]
+pub fn server_check_config() {
+    assert!(cli::is_check_config(&args(&["--check-config"])));
+    assert!(!cli::is_check_config(&[]));
+    assert!(!cli::is_check_config(&args(&["--check-config", "extra"])));
+    assert!(!cli::is_check_config(&args(&["export-tenant", "tenant"])));
+}
+
+#[test]
+pub fn server_export_tenant() {
+    let command = cli::Command::parse(&args(&[
+        "export-tenant",
+        "tenant",
+        "--output",
+        "tenant.json",
+    ]))
+    .unwrap();
+
+    assert_eq!(
+        command,
+        Some(cli::Command::ExportTenant {
+            tenant_id: "tenant".to_string(),
+            recipient_key: None,
+            output: Some(PathBuf::from("tenant.json")),
+        })
+    );
+}
+
+#[test]
+pub fn server_rejects_unknown_option() {
+    assert!(cli::Command::parse(&args(&["import-tenant", "tenant.json", "--key", "key"])).is_err());
+}
+
+#[test]
+pub fn admin_suspend_requires_reason() {
+    assert!(admin::Command::parse(&args(&["tenant", "suspend", "tenant"])).is_err());
+
+    let command =
+        admin::Command::parse(&args(&["tenant", "suspend", "tenant", "--reason", "abuse"]))
+            .unwrap();
+    assert_eq!(
+        command,
+        admin::Command::SuspendTenant {
+            tenant_id: "tenant".to_string(),
+            reason: "abuse".to_string(),
+            credentials: CredentialsKind::ALL.to_vec(),
+        }
+    );
+}
+
+#[test]
+pub fn admin_suspend_single_provider() {
+    let command = admin::Command::parse(&args(&[
+        "tenant",
+        "suspend",
+        "tenant",
+        "--reason",
+        "abuse",
+        "--provider",
+        "fcm_v1",
+    ]))
+    .unwrap();
+    assert!(matches!(
+        command,
+        admin::Command::SuspendTenant { credentials, .. } if credentials == [CredentialsKind::FcmV1]
+    ));
+
+    assert!(admin::Command::parse(&args(&[
+        "tenant",
+        "unsuspend",
+        "tenant",
+        "--provider",
+        "sms"
+    ]))
+    .is_err());
+}
+
+#[test]
+pub fn admin_test_push_defaults() {
+    let command = admin::Command::parse(&args(&["test-push", "tenant", "client"])).unwrap();
+    assert!(matches!(command, admin::Command::TestPush { title, .. } if !title.is_empty()));
+}
+
+#[test]
+pub fn admin_fcm_v1_slot() {
+    let command = admin::Command::parse(&args(&[
+        "credentials",
+        "fcm-v1",
+        "tenant",
+        "service-account.json",
+        "--slot",
+        "beta",
+    ]))
+    .unwrap();
+    assert!(matches!(
+        command,
+        admin::Command::UploadFcmV1Credentials { slot: Some(slot), .. } if slot == "beta"
+    ));
+}
+
+#[test]
+pub fn admin_rejects_unknown_command() {
+    assert!(admin::Command::parse(&args(&["tenant", "rename", "tenant"])).is_err());
+    assert!(admin::Command::parse(&[]).is_err());
+}
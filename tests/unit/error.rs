@@ -0,0 +1,18 @@
+use echo_server::{error::Error, stores::tenant::CredentialsKind};
+
+#[test]
+pub fn variant_name() {
+    assert_eq!(Error::ClientNotFound.variant_name(), "ClientNotFound");
+    assert_eq!(
+        Error::BadDeviceToken("Token is not registered").variant_name(),
+        "BadDeviceToken"
+    );
+    assert_eq!(
+        Error::ProviderSuspended(CredentialsKind::Apns).variant_name(),
+        "ProviderSuspended"
+    );
+    assert_eq!(
+        Error::BulkLimitExceeded(2, 1).variant_name(),
+        "BulkLimitExceeded"
+    );
+}
@@ -0,0 +1,110 @@
+use {
+    echo_server::{
+        error::Error,
+        providers::fcm_v1_validation::{validate_credentials, FcmV1Endpoints, FcmV1ValidationStep},
+    },
+    openssl::{pkey::PKey, rsa::Rsa},
+    serde_json::json,
+    wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    },
+};
+
+const EXAMPLE_PROJECT_ID: &str = "example-project";
+const EXAMPLE_ACCESS_TOKEN: &str = "example-access-token";
+
+fn service_account(token_uri: &str) -> String {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let private_key = String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+    json!({
+        "type": "service_account",
+        "project_id": EXAMPLE_PROJECT_ID,
+        "private_key_id": "example-key-id",
+        "private_key": private_key,
+        "client_email": "echo@example-project.iam.gserviceaccount.com",
+        "client_id": "1234",
+        "token_uri": token_uri,
+    })
+    .to_string()
+}
+
+async fn mock_endpoints(token_status: u16, send_status: u16) -> (MockServer, FcmV1Endpoints) {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .respond_with(ResponseTemplate::new(token_status).set_body_json(json!({
+            "access_token": EXAMPLE_ACCESS_TOKEN,
+            "expires_in": 3599,
+            "token_type": "Bearer",
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/v1/projects/{EXAMPLE_PROJECT_ID}/messages:send"
+        )))
+        .and(header(
+            "authorization",
+            format!("Bearer {EXAMPLE_ACCESS_TOKEN}").as_str(),
+        ))
+        .respond_with(ResponseTemplate::new(send_status).set_body_json(json!({})))
+        .mount(&server)
+        .await;
+
+    let endpoints = FcmV1Endpoints {
+        token: Some(format!("{}/token", server.uri())),
+        fcm: server.uri(),
+    };
+    (server, endpoints)
+}
+
+#[tokio::test]
+async fn valid_credentials() {
+    let (_server, endpoints) = mock_endpoints(200, 200).await;
+    let credentials = service_account("https://oauth2.googleapis.com/token");
+
+    validate_credentials(&reqwest::Client::new(), &endpoints, &credentials)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn rejected_token_exchange() {
+    let (_server, endpoints) = mock_endpoints(401, 200).await;
+    let credentials = service_account("https://oauth2.googleapis.com/token");
+
+    let error = validate_credentials(&reqwest::Client::new(), &endpoints, &credentials)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        Error::FcmV1CredentialValidation(FcmV1ValidationStep::TokenExchange, _)
+    ));
+}
+
+#[tokio::test]
+async fn rejected_dry_run() {
+    let (_server, endpoints) = mock_endpoints(200, 403).await;
+    let credentials = service_account("https://oauth2.googleapis.com/token");
+
+    let error = validate_credentials(&reqwest::Client::new(), &endpoints, &credentials)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        Error::FcmV1CredentialValidation(FcmV1ValidationStep::DryRun, _)
+    ));
+}
+
+#[tokio::test]
+async fn malformed_service_account() {
+    let error = validate_credentials(
+        &reqwest::Client::new(),
+        &FcmV1Endpoints::default(),
+        "not json",
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(error, Error::FcmV1InvalidServiceAccountKey(_)));
+}
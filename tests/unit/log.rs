@@ -0,0 +1,153 @@
+use {
+    axum::http::{HeaderMap, HeaderValue},
+    echo_server::log::{set_remote_parent, JsonLayer},
+    opentelemetry::{
+        global,
+        sdk::{propagation::TraceContextPropagator, trace::TracerProvider},
+        trace::{TraceContextExt, TraceId, TracerProvider as _},
+    },
+    serde_json::Value,
+    std::{
+        io,
+        sync::{Arc, Mutex},
+    },
+    tracing_opentelemetry::OpenTelemetrySpanExt,
+    tracing_subscriber::{fmt::MakeWriter, prelude::*},
+};
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+/// Collects everything written to the logs.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Output {
+    fn lines(&self) -> Vec<Value> {
+        let output = self.0.lock().unwrap();
+        output
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+}
+
+impl io::Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Output {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+fn capture(f: impl FnOnce()) -> Vec<Value> {
+    let output = Output::default();
+    let subscriber = tracing_subscriber::registry().with(JsonLayer::new(output.clone()));
+    tracing::subscriber::with_default(subscriber, f);
+    output.lines()
+}
+
+#[test]
+fn json_events_carry_span_fields() {
+    let lines = capture(|| {
+        let request = tracing::info_span!("request", request_id = "req", tenant_id = "outer");
+        let _request = request.enter();
+        let push = tracing::info_span!("push", tenant_id = "tenant", client_id = "client");
+        let _push = push.enter();
+        tracing::info!(provider = "fcm", "sent");
+    });
+
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    assert_eq!(line["level"], "INFO");
+    assert_eq!(line["span"], "push");
+    assert_eq!(line["message"], "sent");
+    assert_eq!(line["provider"], "fcm");
+    assert_eq!(line["request_id"], "req");
+    // Inner spans take precedence
+    assert_eq!(line["tenant_id"], "tenant");
+    assert_eq!(line["client_id"], "client");
+}
+
+#[test]
+fn json_events_redact_sensitive_fields() {
+    let lines = capture(|| {
+        let span = tracing::info_span!("register", token = tracing::field::Empty);
+        span.record("token", "device-token");
+        let _span = span.enter();
+        tracing::warn!(
+            fcm_api_key = "api-key",
+            refresh_token = "refresh-token",
+            webhook_secret = "webhook-secret",
+            token_client_id = "client",
+            "registered"
+        );
+    });
+
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    assert_eq!(line["token"], "[redacted]");
+    assert_eq!(line["fcm_api_key"], "[redacted]");
+    assert_eq!(line["refresh_token"], "[redacted]");
+    assert_eq!(line["webhook_secret"], "[redacted]");
+    assert_eq!(line["token_client_id"], "client");
+    assert_eq!(line["message"], "registered");
+
+    let output = lines.iter().map(Value::to_string).collect::<String>();
+    for secret in ["device-token", "api-key", "refresh-token", "webhook-secret"] {
+        assert!(!output.contains(secret), "{secret} was logged");
+    }
+}
+
+/// Trace id of a request span continued from the `traceparent` header, if any
+fn request_trace_id(traceparent: Option<&str>) -> TraceId {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    // Spans are only traced while the provider is alive
+    let provider = TracerProvider::builder().build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+    let mut headers = HeaderMap::new();
+    if let Some(traceparent) = traceparent {
+        headers.insert("traceparent", HeaderValue::from_str(traceparent).unwrap());
+    }
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request");
+        set_remote_parent(&span, &headers);
+        span.context().span().span_context().trace_id()
+    })
+}
+
+#[test]
+fn traceparent_continues_the_trace() {
+    let trace_id = request_trace_id(Some(&format!("00-{TRACE_ID}-00f067aa0ba902b7-01")));
+
+    assert_eq!(trace_id, TraceId::from_hex(TRACE_ID).unwrap());
+}
+
+#[test]
+fn malformed_traceparent_starts_a_new_trace() {
+    let trace_id = request_trace_id(Some(&format!("00-{TRACE_ID}-not-a-span-id-01")));
+
+    assert_ne!(trace_id, TraceId::INVALID);
+    assert_ne!(trace_id, TraceId::from_hex(TRACE_ID).unwrap());
+}
+
+#[test]
+fn missing_traceparent_starts_a_new_trace() {
+    let trace_id = request_trace_id(None);
+
+    assert_ne!(trace_id, TraceId::INVALID);
+    assert_ne!(trace_id, TraceId::from_hex(TRACE_ID).unwrap());
+}
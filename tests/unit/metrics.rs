@@ -0,0 +1,28 @@
+use echo_server::metrics::{Metrics, OTHER_TENANT_LABEL};
+
+#[test]
+fn tenant_labels_fall_back_to_other() {
+    let metrics = Metrics::new().with_tenant_labels(["tenant-a".to_string()]);
+
+    assert_eq!(
+        metrics.tenant_label(Some("tenant-a")).as_deref(),
+        Some("tenant-a")
+    );
+    // Tenants that aren't in the allow-list and unknown tenants share a label
+    assert_eq!(
+        metrics.tenant_label(Some("tenant-b")).as_deref(),
+        Some(OTHER_TENANT_LABEL)
+    );
+    assert_eq!(
+        metrics.tenant_label(None).as_deref(),
+        Some(OTHER_TENANT_LABEL)
+    );
+    assert_eq!(OTHER_TENANT_LABEL, "other");
+}
+
+#[test]
+fn tenant_label_is_left_out_without_allow_list() {
+    let metrics = Metrics::new();
+
+    assert_eq!(metrics.tenant_label(Some("tenant-a")), None);
+}
@@ -1,2 +1,15 @@
+#[cfg(feature = "analytics")]
+mod analytics;
+mod apns_certificate;
+mod apns_update;
+mod cli;
+mod config;
+mod error;
+mod fcm_v1_validation;
+mod log;
 mod messages;
+mod metrics;
 mod middleware;
+mod tenant_archive;
+mod tenant_store;
+mod webhooks;
@@ -0,0 +1,66 @@
+use {
+    base64::{engine::general_purpose::STANDARD, Engine as _},
+    echo_server::{
+        stores::tenant::TenantApnsUpdateAuth,
+        tenant_archive::{decrypt_credentials, encrypt_credentials, TenantCredentials},
+    },
+    openssl::pkey::PKey,
+};
+
+const EXAMPLE_TENANT_ID: &str = "example-tenant";
+
+fn generate_key_pair() -> (String, String) {
+    let key = PKey::generate_x25519().unwrap();
+    (
+        STANDARD.encode(key.raw_private_key().unwrap()),
+        STANDARD.encode(key.raw_public_key().unwrap()),
+    )
+}
+
+fn example_credentials() -> TenantCredentials {
+    TenantCredentials {
+        fcm_api_key: Some("fcm-api-key".to_string()),
+        fcm_v1_credentials: None,
+        fcm_v1_slots: Default::default(),
+        apns: Some(TenantApnsUpdateAuth::Token {
+            apns_pkcs8_pem: "pkcs8-pem".to_string(),
+            apns_key_id: "key-id".to_string(),
+            apns_team_id: "team-id".to_string(),
+        }),
+    }
+}
+
+#[test]
+pub fn credentials_encryption_round_trip() {
+    let (private_key, public_key) = generate_key_pair();
+
+    let encrypted =
+        encrypt_credentials(&example_credentials(), EXAMPLE_TENANT_ID, &public_key).unwrap();
+    assert!(!encrypted.ciphertext.contains("fcm-api-key"));
+
+    let decrypted = decrypt_credentials(&encrypted, EXAMPLE_TENANT_ID, &private_key).unwrap();
+    assert_eq!(decrypted.fcm_api_key, Some("fcm-api-key".to_string()));
+    assert!(matches!(
+        decrypted.apns,
+        Some(TenantApnsUpdateAuth::Token { apns_key_id, .. }) if apns_key_id == "key-id"
+    ));
+}
+
+#[test]
+pub fn credentials_decryption_fails_with_other_key() {
+    let (_, public_key) = generate_key_pair();
+    let (other_private_key, _) = generate_key_pair();
+
+    let encrypted =
+        encrypt_credentials(&example_credentials(), EXAMPLE_TENANT_ID, &public_key).unwrap();
+    assert!(decrypt_credentials(&encrypted, EXAMPLE_TENANT_ID, &other_private_key).is_err());
+}
+
+#[test]
+pub fn credentials_decryption_fails_for_other_tenant() {
+    let (private_key, public_key) = generate_key_pair();
+
+    let encrypted =
+        encrypt_credentials(&example_credentials(), EXAMPLE_TENANT_ID, &public_key).unwrap();
+    assert!(decrypt_credentials(&encrypted, "other-tenant", &private_key).is_err());
+}
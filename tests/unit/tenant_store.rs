@@ -0,0 +1,73 @@
+use {
+    echo_server::{
+        config::load_config,
+        stores::tenant::{CredentialsKind, DefaultTenantStore, TenantStore, DEFAULT_TENANT_ID},
+    },
+    std::sync::Arc,
+};
+
+fn store() -> DefaultTenantStore {
+    let config = load_config(
+        [
+            ("PUBLIC_URL", "http://127.0.0.1:3000"),
+            ("RELAY_PUBLIC_KEY", "key"),
+            ("DATABASE_URL", "postgres://localhost/echo"),
+            ("TENANT_DATABASE_URL", "postgres://localhost/tenants"),
+            ("ANALYTICS_EXPORT_BUCKET", "bucket"),
+            ("BLOCKED_COUNTRIES", ""),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string())),
+    )
+    .unwrap();
+    DefaultTenantStore::new(Arc::new(config)).unwrap()
+}
+
+#[tokio::test]
+pub async fn single_tenant_suspensions_are_kept_in_memory() {
+    let store = store();
+
+    store
+        .suspend_tenant_credentials(DEFAULT_TENANT_ID, CredentialsKind::Fcm, None, "bad key")
+        .await
+        .unwrap();
+    let tenant = store.get_tenant(DEFAULT_TENANT_ID).await.unwrap();
+    assert_eq!(
+        tenant.suspended_reason(CredentialsKind::Fcm),
+        Some("bad key")
+    );
+    assert_eq!(tenant.suspended_reason(CredentialsKind::Apns), None);
+
+    // Only the first suspension is claimed
+    assert!(!store
+        .suspend_tenant_credentials_once(DEFAULT_TENANT_ID, CredentialsKind::Fcm, None, "again")
+        .await
+        .unwrap());
+
+    // Reloading unchanged credentials keeps the suspension
+    let mut reloaded = tenant.clone();
+    reloaded.provider_status = Default::default();
+    assert!(!store.replace_tenant(reloaded.clone()).await);
+    let tenant = store.get_tenant(DEFAULT_TENANT_ID).await.unwrap();
+    assert_eq!(
+        tenant.suspended_reason(CredentialsKind::Fcm),
+        Some("bad key")
+    );
+
+    // while changed credentials lift it
+    reloaded.fcm_api_key = Some("new key".to_string());
+    assert!(store.replace_tenant(reloaded).await);
+    let tenant = store.get_tenant(DEFAULT_TENANT_ID).await.unwrap();
+    assert_eq!(tenant.suspended_reason(CredentialsKind::Fcm), None);
+
+    store
+        .suspend_tenant_credentials(DEFAULT_TENANT_ID, CredentialsKind::Fcm, None, "bad key")
+        .await
+        .unwrap();
+    store
+        .unsuspend_tenant_credentials(DEFAULT_TENANT_ID, CredentialsKind::Fcm, None)
+        .await
+        .unwrap();
+    let tenant = store.get_tenant(DEFAULT_TENANT_ID).await.unwrap();
+    assert_eq!(tenant.suspended_reason(CredentialsKind::Fcm), None);
+}
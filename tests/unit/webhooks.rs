@@ -0,0 +1,57 @@
+use echo_server::webhooks::{generate_secret, sign, validate_url, WebhookEvent};
+
+#[test]
+fn webhook_signature() {
+    let signature = sign("secret", 1700000000, br#"{"event":"tenant_suspended"}"#).unwrap();
+    assert_eq!(
+        signature,
+        "793f3ec6d63e831d90c3ee8f5761598adb63047e4569870db0b3643330e25d43"
+    );
+
+    // Secrets are random
+    assert_ne!(generate_secret().unwrap(), generate_secret().unwrap());
+}
+
+#[tokio::test]
+async fn webhook_url_validation() {
+    let (_, addrs) = validate_url("https://93.184.216.34/webhook").await.unwrap();
+    assert_eq!(addrs, vec!["93.184.216.34:443".parse().unwrap()]);
+    assert!(validate_url("https://[2606:2800:220:1::]:8443")
+        .await
+        .is_ok());
+
+    assert!(validate_url("http://93.184.216.34/webhook").await.is_err());
+    assert!(validate_url("ftp://example.com").await.is_err());
+    assert!(validate_url("not a url").await.is_err());
+}
+
+#[tokio::test]
+async fn webhook_url_rejects_internal_addresses() {
+    for url in [
+        "https://localhost:8080",
+        "https://127.0.0.1",
+        "https://10.0.0.1",
+        "https://172.16.0.1",
+        "https://192.168.1.1",
+        "https://100.64.0.1",
+        "https://169.254.169.254/latest/meta-data",
+        "https://0.0.0.0",
+        "https://[::1]",
+        "https://[fd00::1]",
+        "https://[fe80::1]",
+        "https://[::ffff:127.0.0.1]",
+    ] {
+        assert!(validate_url(url).await.is_err(), "{url}");
+    }
+}
+
+#[test]
+fn webhook_event_names() {
+    for event in WebhookEvent::ALL {
+        assert_eq!(WebhookEvent::try_from(event.as_str()).unwrap(), event);
+        assert_eq!(
+            serde_json::to_value(event).unwrap(),
+            serde_json::Value::from(event.as_str())
+        );
+    }
+}